@@ -0,0 +1,17 @@
+#!/usr/bin/env bash
+# Runs the checks a change has to pass, with the default features and with
+# none, as every feature has to be optional. The query macros check against
+# the database in DATABASE_URL, which is created from schema.sql.
+set -euo pipefail
+cd "$(dirname "$0")"
+
+export DATABASE_URL="sqlite:$PWD/db.db"
+rm -f db.db
+sqlite3 db.db < schema.sql
+
+for features in "" "--no-default-features"; do
+	echo "checking with ${features:-the default features}"
+	cargo build --workspace $features
+	cargo clippy --workspace --all-targets $features -- -D warnings
+	cargo test --workspace $features
+done
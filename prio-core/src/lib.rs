@@ -0,0 +1,158 @@
+//! The parts of prio that don't need a database or a file system: computing
+//! ratings from pairwise votes and picking which entries to compare next.
+//! Everything works on indices into a list of items owned by the caller.
+
+#[cfg(feature = "wasm")]
+mod wasm;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use skillratings::elo::{elo, EloConfig, EloRating};
+use skillratings::glicko2::{glicko2, Glicko2Config};
+use skillratings::Outcomes;
+
+pub use skillratings::glicko2::Glicko2Rating as Rating;
+
+/// A comparison between `items[left]` and `items[right]`, `vote` being
+/// positive if left won, negative if right won and 0 for a draw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Vote {
+    pub left: usize,
+    pub right: usize,
+    pub vote: i64,
+}
+
+/// How ratings are updated after a vote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum System {
+    #[default]
+    Glicko2,
+    /// Elo with a k-factor of 32. Elo has no notion of deviation, so it
+    /// shrinks by 5% with every vote instead, down to 30, for sampling to
+    /// still prefer items with few votes.
+    Elo,
+}
+
+/// Applies the Glicko-2 update for a single vote.
+pub fn apply(ratings: &mut [Rating], vote: &Vote) {
+    apply_with(ratings, vote, System::Glicko2, 1.0);
+}
+
+/// Applies a single vote using `system`, counting it for `weight` between 0
+/// and 1: both ratings only move that part of the way to their new values.
+pub fn apply_with(ratings: &mut [Rating], vote: &Vote, system: System, weight: f64) {
+    let outcome = match vote.vote {
+        0 => Outcomes::DRAW,
+        ..=-1 => Outcomes::LOSS,
+        1.. => Outcomes::WIN,
+    };
+
+    let (old_left, old_right) = (ratings[vote.left], ratings[vote.right]);
+    let (left, right) = match system {
+        System::Glicko2 => glicko2(&old_left, &old_right, &outcome, &Glicko2Config::new()),
+        System::Elo => {
+            let (left, right) = elo(
+                &EloRating {
+                    rating: old_left.rating,
+                },
+                &EloRating {
+                    rating: old_right.rating,
+                },
+                &outcome,
+                &EloConfig::new(),
+            );
+            let shrink = |old: Rating, rating: f64| Rating {
+                rating,
+                deviation: (old.deviation * 0.95).max(30.0),
+                ..old
+            };
+            (
+                shrink(old_left, left.rating),
+                shrink(old_right, right.rating),
+            )
+        }
+    };
+
+    if weight >= 1.0 {
+        ratings[vote.left] = left;
+        ratings[vote.right] = right;
+        return;
+    }
+    let towards = |old: Rating, new: Rating| Rating {
+        rating: old.rating + weight * (new.rating - old.rating),
+        deviation: old.deviation + weight * (new.deviation - old.deviation),
+        volatility: old.volatility + weight * (new.volatility - old.volatility),
+    };
+    ratings[vote.left] = towards(old_left, left);
+    ratings[vote.right] = towards(old_right, right);
+}
+
+/// Replays the votes in order, returning the rating of each of the `items`.
+pub fn rate<'a>(items: usize, votes: impl IntoIterator<Item = &'a Vote>) -> Vec<Rating> {
+    let mut ratings = vec![Rating::new(); items];
+    for vote in votes {
+        apply(&mut ratings, vote);
+    }
+    ratings
+}
+
+/// Picks `n` distinct indices, preferring items with a high deviation as
+/// comparing those tells us the most. Returns fewer if there are fewer items.
+pub fn sample<R: Rng + ?Sized>(ratings: &[Rating], n: usize, rng: &mut R) -> Vec<usize> {
+    let indices: Vec<usize> = (0..ratings.len()).collect();
+    let mut res: Vec<usize> = indices
+        .choose_multiple_weighted(rng, n, |i| ratings[*i].deviation)
+        .unwrap()
+        .copied()
+        .collect();
+    res.sort_unstable();
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn rate_puts_winners_above_losers() {
+        let votes = [
+            Vote {
+                left: 0,
+                right: 1,
+                vote: 1,
+            },
+            Vote {
+                left: 2,
+                right: 1,
+                vote: -1,
+            },
+            Vote {
+                left: 0,
+                right: 2,
+                vote: 0,
+            },
+        ];
+        let ratings = rate(4, &votes);
+        assert_eq!(ratings.len(), 4);
+        assert!(ratings[0].rating > ratings[1].rating);
+        assert!(ratings[1].rating > ratings[2].rating);
+        // The item without votes is left alone.
+        assert_eq!(ratings[3], Rating::new());
+        assert!(ratings[0].deviation < ratings[3].deviation);
+    }
+
+    #[test]
+    fn sample_picks_distinct_indices() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let ratings = vec![Rating::new(); 5];
+        for _ in 0..20 {
+            let pair = sample(&ratings, 2, &mut rng);
+            assert_eq!(pair.len(), 2);
+            assert!(pair[0] < pair[1] && pair[1] < 5);
+        }
+        assert_eq!(sample(&ratings[..1], 2, &mut rng), vec![0]);
+    }
+}
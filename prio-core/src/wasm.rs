@@ -0,0 +1,49 @@
+use wasm_bindgen::prelude::*;
+
+use crate::{rate, sample, Rating, Vote};
+
+/// Votes are passed flattened as `[left, right, vote, left, right, vote, ...]`,
+/// with `left` and `right` indices of the `items`.
+fn ratings_of(items: usize, votes: &[i32]) -> Result<Vec<Rating>, JsError> {
+    if !votes.len().is_multiple_of(3) {
+        return Err(JsError::new("votes has to hold three numbers per vote"));
+    }
+    let index = |i: i32| {
+        usize::try_from(i)
+            .ok()
+            .filter(|&i| i < items)
+            .ok_or_else(|| {
+                JsError::new(&format!("{} isn't the index of one of {} items", i, items))
+            })
+    };
+    let votes = votes
+        .chunks_exact(3)
+        .map(|v| {
+            Ok(Vote {
+                left: index(v[0])?,
+                right: index(v[1])?,
+                vote: v[2] as i64,
+            })
+        })
+        .collect::<Result<Vec<_>, JsError>>()?;
+    Ok(rate(items, &votes))
+}
+
+/// Returns `[rating, deviation, ...]` for each of the items.
+#[wasm_bindgen]
+pub fn ratings(items: usize, votes: &[i32]) -> Result<Vec<f64>, JsError> {
+    Ok(ratings_of(items, votes)?
+        .into_iter()
+        .flat_map(|r| [r.rating, r.deviation])
+        .collect())
+}
+
+/// Picks the indices of the next pair to compare.
+#[wasm_bindgen]
+pub fn pick_pair(items: usize, votes: &[i32]) -> Result<Vec<u32>, JsError> {
+    let ratings = ratings_of(items, votes)?;
+    Ok(sample(&ratings, 2, &mut rand::thread_rng())
+        .into_iter()
+        .map(|i| i as u32)
+        .collect())
+}
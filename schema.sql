@@ -1,15 +1,21 @@
+-- The latest schema, see MIGRATIONS in src/schema.rs for how databases
+-- created from an older version are brought up to date.
+
 CREATE TABLE entries (
-	path TEXT NOT NULL PRIMARY KEY
+	path TEXT NOT NULL PRIMARY KEY,
+	id INTEGER, -- stable identifier, see entries_id_idx
+	root TEXT NOT NULL DEFAULT '' -- name of the entries directory, '' for the main one
 	--deleted BOOLEAN NOT NULL
 	--hash TEXT NOT NULL,
 	--info_yaml TEXT,
 	--ordering INTEGER NOT NULL DEFAULT 0,
 	--checked INTEGER NOT NULL DEFAULT 0,
 );
+CREATE UNIQUE INDEX entries_id_idx ON entries(id);
 
 CREATE TABLE file_contents (
 	path TEXT NOT NULL,
-	content BLOB, -- if NULL the entry was deleted from disk
+	content BLOB, -- NULL for deletions recorded before status_history existed
 
 	at INTEGER NOT NULL,
 
@@ -17,15 +23,139 @@ CREATE TABLE file_contents (
 );
 CREATE INDEX file_contents_idx ON file_contents(path);
 
+-- Votes keep the paths the entries had when they were cast, which can be
+-- an alias by now. Read them from resolved_votes to have the current paths.
 CREATE TABLE entry_votes (
-	left_path TEXT NOT NULL,
+	id INTEGER PRIMARY KEY AUTOINCREMENT, -- never reused, see prio votes
+	left_path TEXT NOT NULL, -- an entry or an alias
 	right_path TEXT NOT NULL,
 	vote INTEGER NOT NULL,
+	criterion TEXT, -- what the vote was judged on, NULL for overall importance
+	confidence TEXT, -- sure or leaning, NULL when not asked
 
-	at INTEGER NOT NULL,
-
-	FOREIGN KEY (left_path) REFERENCES entries(path),
-	FOREIGN KEY (right_path) REFERENCES entries(path)
+	at INTEGER NOT NULL
 );
 CREATE INDEX entry_votes_left_path_idx ON entry_votes(left_path);
 CREATE INDEX entry_votes_right_path_idx ON entry_votes(right_path);
+
+-- Paths entries had before they were moved, see schema::move_entry. The
+-- votes under an alias up to last_vote are those of the entry it refers to,
+-- later ones are of whatever entry was added at that path since.
+CREATE TABLE entry_aliases (
+	alias TEXT NOT NULL,
+	path TEXT NOT NULL, -- the entry it refers to now
+	last_vote INTEGER NOT NULL, -- the highest vote id when it was moved
+
+	PRIMARY KEY (alias, last_vote),
+	FOREIGN KEY (path) REFERENCES entries(path)
+);
+CREATE INDEX entry_aliases_path_idx ON entry_aliases(path);
+
+CREATE VIEW resolved_votes AS
+SELECT
+	v.id,
+	COALESCE(
+		(
+			SELECT a.path FROM entry_aliases a
+			WHERE a.alias = v.left_path AND v.id <= a.last_vote
+			ORDER BY a.last_vote LIMIT 1
+		),
+		v.left_path
+	) AS left_path,
+	COALESCE(
+		(
+			SELECT a.path FROM entry_aliases a
+			WHERE a.alias = v.right_path AND v.id <= a.last_vote
+			ORDER BY a.last_vote LIMIT 1
+		),
+		v.right_path
+	) AS right_path,
+	v.vote,
+	v.criterion,
+	v.confidence,
+	v.at
+FROM entry_votes v;
+
+CREATE TABLE status_history (
+	path TEXT NOT NULL,
+	status TEXT NOT NULL, -- active, done, dropped, snoozed or deleted
+	actor TEXT NOT NULL,
+	reason TEXT,
+
+	at INTEGER NOT NULL,
+
+	FOREIGN KEY (path) REFERENCES entries(path)
+);
+CREATE INDEX status_history_idx ON status_history(path);
+
+CREATE TABLE audit_log (
+	id INTEGER PRIMARY KEY,
+	action TEXT NOT NULL,
+	detail TEXT NOT NULL, -- JSON encoded audit::Change
+	actor TEXT NOT NULL,
+	undone INTEGER NOT NULL DEFAULT 0, -- 1 if undone, 2 if undone and no longer redoable
+
+	at INTEGER NOT NULL
+);
+
+-- The ranking as of the last change, so simple queries don't have to compute
+-- ratings from all votes.
+CREATE TABLE ranking_cache (
+	path TEXT NOT NULL PRIMARY KEY,
+	id INTEGER NOT NULL,
+	rank INTEGER NOT NULL,
+	title TEXT NOT NULL,
+	rating REAL NOT NULL,
+	deviation REAL NOT NULL,
+
+	FOREIGN KEY (path) REFERENCES entries(path)
+);
+
+-- SM-2 review schedule of prio resurface, independent of the ranking. Entries
+-- without a row have never been resurfaced and are due.
+CREATE TABLE resurface_schedule (
+	path TEXT NOT NULL PRIMARY KEY,
+	repetitions INTEGER NOT NULL,
+	interval_days INTEGER NOT NULL,
+	ease REAL NOT NULL,
+
+	due_at INTEGER NOT NULL,
+
+	FOREIGN KEY (path) REFERENCES entries(path)
+);
+
+-- Values of the custom fields declared in the config, set with prio set.
+-- Values from an entry's front matter take precedence and aren't stored.
+CREATE TABLE entry_fields (
+	path TEXT NOT NULL,
+	name TEXT NOT NULL,
+	value TEXT NOT NULL, -- canonical form, see config::FieldType::parse
+
+	PRIMARY KEY (path, name),
+	FOREIGN KEY (path) REFERENCES entries(path)
+);
+
+CREATE TABLE settings (
+	key TEXT NOT NULL PRIMARY KEY,
+	value TEXT NOT NULL -- JSON
+);
+
+-- Ratings computed by prio recompute with other parameters, to compare with
+-- the live ranking.
+CREATE TABLE experiments (
+	name TEXT NOT NULL PRIMARY KEY,
+	params TEXT NOT NULL, -- JSON encoded rating::Params
+
+	created_at INTEGER NOT NULL
+);
+
+CREATE TABLE experiment_scores (
+	experiment TEXT NOT NULL,
+	path TEXT NOT NULL,
+	rating REAL NOT NULL,
+	deviation REAL NOT NULL,
+
+	PRIMARY KEY (experiment, path),
+	FOREIGN KEY (experiment) REFERENCES experiments(name),
+	FOREIGN KEY (path) REFERENCES entries(path)
+);
@@ -0,0 +1,310 @@
+use std::borrow::BorrowMut;
+use std::fmt::Display;
+
+use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, SqliteConnection};
+
+use crate::status::Status;
+use crate::Confidence;
+
+/// A single mutation of the database, as recorded in the audit_log table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Change {
+    /// sync found a new file.
+    Add { path: String },
+    /// sync stored a new revision of a file.
+    Content { path: String, at: i64 },
+    /// sync found the file of an entry under another path, with the same
+    /// content, and moved the entry there.
+    Rename { from: String, to: String },
+    Vote {
+        vote_id: i64,
+        winner: String,
+        loser: String,
+        vote: i64,
+        at: i64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        criterion: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        confidence: Option<Confidence>,
+    },
+    /// The outcome of an earlier vote was changed with `prio votes edit`.
+    VoteEdit { vote_id: i64, from: i64, to: i64 },
+    /// An earlier vote was deleted with `prio votes delete`, with everything
+    /// needed to put it back.
+    VoteDelete {
+        vote_id: i64,
+        winner: String,
+        loser: String,
+        vote: i64,
+        at: i64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        criterion: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        confidence: Option<Confidence>,
+    },
+    Status {
+        path: String,
+        from: Status,
+        to: Status,
+        reason: Option<String>,
+    },
+    /// A custom field was set with `prio set`, None meaning unset.
+    Field {
+        path: String,
+        name: String,
+        from: Option<String>,
+        to: Option<String>,
+    },
+    /// An entry was removed from the database with `prio purge`, along with
+    /// this many revisions and votes. Changes before it can't be undone or
+    /// redone anymore, as they could involve the entry.
+    Purge {
+        path: String,
+        revisions: i64,
+        votes: i64,
+    },
+    /// Several changes made by one command, like done on more than one
+    /// entry, that are undone and redone together.
+    Bulk { changes: Vec<Change> },
+}
+
+impl Change {
+    /// Whether `prio undo` can revert this change. Changes made by sync mirror
+    /// the entries directory, so reverting those would only be undone again by
+    /// the next sync.
+    pub fn undoable(&self) -> bool {
+        matches!(
+            self,
+            Change::Vote { .. }
+                | Change::VoteEdit { .. }
+                | Change::VoteDelete { .. }
+                | Change::Status { .. }
+                | Change::Field { .. }
+                | Change::Bulk { .. }
+        )
+    }
+
+    fn action(&self) -> &'static str {
+        match self {
+            Change::Add { .. } => "add",
+            Change::Content { .. } => "content",
+            Change::Rename { .. } => "rename",
+            Change::Vote { .. } => "vote",
+            Change::VoteEdit { .. } => "vote_edit",
+            Change::VoteDelete { .. } => "vote_delete",
+            Change::Status { .. } => "status",
+            Change::Field { .. } => "field",
+            Change::Purge { .. } => "purge",
+            Change::Bulk { .. } => "bulk",
+        }
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Add { path } => write!(f, "added {}", path),
+            Change::Content { path, .. } => write!(f, "new revision of {}", path),
+            Change::Rename { from, to } => write!(f, "renamed {} to {}", from, to),
+            Change::Vote {
+                winner,
+                loser,
+                vote,
+                criterion,
+                ..
+            } => {
+                match vote {
+                    0 => write!(f, "voted {} and {} equal", winner, loser)?,
+                    ..=-1 => write!(f, "voted {} over {}", loser, winner)?,
+                    1.. => write!(f, "voted {} over {}", winner, loser)?,
+                }
+                if let Some(criterion) = criterion {
+                    write!(f, " on {}", criterion)?;
+                }
+                Ok(())
+            }
+            Change::VoteEdit { vote_id, from, to } => {
+                let outcome = |vote: &i64| match vote {
+                    0 => "draw",
+                    ..=-1 => "loss",
+                    1.. => "win",
+                };
+                write!(
+                    f,
+                    "changed vote {} from {} to {}",
+                    vote_id,
+                    outcome(from),
+                    outcome(to)
+                )
+            }
+            Change::VoteDelete {
+                vote_id,
+                winner,
+                loser,
+                ..
+            } => write!(f, "deleted vote {} on {} and {}", vote_id, winner, loser),
+            Change::Status {
+                path,
+                from,
+                to,
+                reason,
+            } => {
+                write!(f, "{} went from {} to {}", path, from, to)?;
+                if let Some(reason) = reason {
+                    write!(f, " ({})", reason)?;
+                }
+                Ok(())
+            }
+            Change::Field { path, name, to, .. } => match to {
+                Some(to) => write!(f, "set {} of {} to {}", name, path, to),
+                None => write!(f, "unset {} of {}", name, path),
+            },
+            Change::Purge {
+                path,
+                revisions,
+                votes,
+            } => write!(
+                f,
+                "purged {} with {} revisions and {} votes",
+                path, revisions, votes
+            ),
+            Change::Bulk { changes } => {
+                for (i, change) in changes.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}", change)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub change: Change,
+    pub actor: String,
+    pub at: DateTime<Utc>,
+    pub undone: bool,
+}
+
+pub async fn record(conn: &mut SqliteConnection, change: &Change, actor: &str) -> Result<i64> {
+    let action = change.action();
+    let detail = serde_json::to_string(change)?;
+    let ts = Utc::now().timestamp();
+
+    // Like in any editor, a new change makes it impossible to redo earlier
+    // undone changes.
+    if change.undoable() || matches!(change, Change::Purge { .. }) {
+        query!("UPDATE audit_log SET undone = 2 WHERE undone = 1")
+            .execute(conn.borrow_mut())
+            .await?;
+    }
+
+    let res = query!(
+        r#"
+            INSERT INTO audit_log
+                (action, detail, actor, at)
+            VALUES
+                (?1, ?2, ?3, ?4)
+        "#,
+        action,
+        detail,
+        actor,
+        ts
+    )
+    .execute(conn)
+    .await?;
+    Ok(res.last_insert_rowid())
+}
+
+/// Returns all audit entries recorded at or after `since`, oldest first.
+pub async fn entries_since(
+    conn: &mut SqliteConnection,
+    since: DateTime<Utc>,
+) -> Result<Vec<AuditEntry>> {
+    let since = since.timestamp();
+
+    query!(
+        r#"
+            SELECT id, detail, actor, at, undone
+            FROM audit_log
+            WHERE at >= ?1
+            ORDER BY id ASC
+        "#,
+        since
+    )
+    .fetch_all(conn)
+    .await?
+    .into_iter()
+    .map(|r| {
+        Ok(AuditEntry {
+            id: r.id,
+            change: serde_json::from_str(&r.detail)?,
+            actor: r.actor,
+            at: Utc.timestamp_opt(r.at, 0).unwrap(),
+            undone: r.undone != 0,
+        })
+    })
+    .collect()
+}
+
+/// The most recent change that is still in effect and can be undone, or
+/// the most recent such vote.
+pub async fn last_undoable(
+    conn: &mut SqliteConnection,
+    only_votes: bool,
+) -> Result<Option<(i64, Change)>> {
+    let rows = query!(
+        r#"
+            SELECT id AS "id!", detail
+            FROM audit_log
+            WHERE undone = 0
+                AND (
+                    action = 'vote'
+                    OR (NOT ?1 AND action IN ('vote_edit', 'vote_delete', 'status', 'field', 'bulk'))
+                )
+                AND id > (SELECT IFNULL(MAX(id), 0) FROM audit_log WHERE action = 'purge')
+            ORDER BY id DESC
+            LIMIT 1
+        "#,
+        only_votes
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    rows.map(|r| Ok((r.id, serde_json::from_str(&r.detail)?)))
+        .transpose()
+}
+
+/// The change that was undone last and can still be redone.
+pub async fn next_redoable(conn: &mut SqliteConnection) -> Result<Option<(i64, Change)>> {
+    let rows = query!(
+        r#"
+            SELECT id, detail
+            FROM audit_log
+            WHERE undone = 1
+            ORDER BY id ASC
+            LIMIT 1
+        "#
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    rows.map(|r| Ok((r.id, serde_json::from_str(&r.detail)?)))
+        .transpose()
+}
+
+pub async fn set_undone(conn: &mut SqliteConnection, id: i64, undone: bool) -> Result<()> {
+    let undone = undone as i64;
+    query!("UPDATE audit_log SET undone = ?1 WHERE id = ?2", undone, id)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
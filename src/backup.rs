@@ -0,0 +1,117 @@
+//! Snapshots of the database, see prio backup and prio restore-backup. They
+//! are kept in Config::backup_dir, hidden next to the database by default so
+//! syncing never picks them up.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use chrono::Local;
+use sqlx::SqliteConnection;
+use tokio::fs;
+
+use crate::config::Config;
+use crate::error;
+
+/// What follows the name of the database in the name of a backup, before its
+/// timestamp.
+const SUFFIX: &str = ".backup-";
+
+fn prefix(db: &Path) -> String {
+    format!("{}{}", db.file_name().unwrap().to_string_lossy(), SUFFIX)
+}
+
+pub fn dir(config: &Config) -> PathBuf {
+    match &config.backup_dir {
+        Some(dir) => dir.clone(),
+        None => config
+            .db()
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join(".backups"),
+    }
+}
+
+/// The backups of the database, oldest first.
+pub async fn list(config: &Config) -> Result<Vec<PathBuf>> {
+    let prefix = prefix(&config.db());
+    let mut entries = match fs::read_dir(dir(config)).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut backups = vec![];
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_name().to_string_lossy().starts_with(&prefix) {
+            backups.push(entry.path());
+        }
+    }
+    // The timestamps sort like the times they stand for.
+    backups.sort();
+    Ok(backups)
+}
+
+/// Writes a consistent copy of the database with VACUUM INTO, returning its
+/// path.
+pub async fn create(conn: &mut SqliteConnection, config: &Config) -> Result<PathBuf> {
+    let dir = dir(config);
+    fs::create_dir_all(&dir).await?;
+    let name = format!(
+        "{}{}",
+        prefix(&config.db()),
+        Local::now().format("%Y%m%d-%H%M%S%.3f")
+    );
+    let path = dir.join(name);
+    if fs::try_exists(&path).await? {
+        bail!("{} already exists", path.display());
+    }
+
+    sqlx::query("VACUUM INTO ?1")
+        .bind(path.to_string_lossy().into_owned())
+        .execute(conn)
+        .await?;
+    Ok(path)
+}
+
+/// Removes all but the `keep` newest backups, returning the removed ones.
+pub async fn prune(config: &Config, keep: usize) -> Result<Vec<PathBuf>> {
+    let mut backups = list(config).await?;
+    let old = backups.len().saturating_sub(keep);
+    backups.truncate(old);
+    for path in &backups {
+        fs::remove_file(path).await?;
+    }
+    Ok(backups)
+}
+
+/// The backup `name` refers to: a path, or the name of a file in the backup
+/// directory.
+pub async fn find(config: &Config, name: &str) -> Result<PathBuf> {
+    let path = Path::new(name);
+    if path.components().count() > 1 && fs::try_exists(path).await? {
+        return Ok(path.to_path_buf());
+    }
+    list(config)
+        .await?
+        .into_iter()
+        .find(|p| p.file_name().is_some_and(|n| n == name))
+        .ok_or_else(|| error::not_found(format!("no backup {}, see prio restore-backup", name)))
+}
+
+/// Replaces the database by the backup at `path`. No connection to the
+/// database may be open.
+pub async fn restore(config: &Config, path: &Path) -> Result<()> {
+    let db = config.db();
+    // A journal left by a crash would otherwise be applied to the backup.
+    for suffix in ["-journal", "-wal", "-shm"] {
+        let mut journal = db.clone().into_os_string();
+        journal.push(suffix);
+        if let Err(e) = fs::remove_file(&journal).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(e.into());
+            }
+        }
+    }
+    fs::copy(path, &db).await?;
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use anyhow::Result;
+use sqlx::{query, Connection, SqliteConnection};
+
+use crate::util::path_str;
+use crate::File;
+
+/// Replaces the cached ranking, `ranking` being the active entries with the
+/// highest rated first.
+pub async fn refresh(conn: &mut SqliteConnection, ranking: &[&File]) -> Result<()> {
+    let mut tx = conn.begin().await?;
+
+    query!("DELETE FROM ranking_cache")
+        .execute(&mut *tx)
+        .await?;
+    for (i, item) in ranking.iter().enumerate() {
+        let path = path_str(&item.path);
+        let rank = i as i64 + 1;
+        query!(
+            r#"
+                INSERT INTO ranking_cache
+                    (path, id, rank, title, rating, deviation)
+                VALUES
+                    (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+            path,
+            item.id,
+            rank,
+            item.title,
+            item.rating.rating,
+            item.rating.deviation
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub active: i64,
+    pub top: Option<String>,
+}
+
+pub async fn summary(conn: &mut SqliteConnection) -> Result<Summary> {
+    let r = query!(
+        r#"
+            SELECT
+                COUNT(*) AS "active!: i64",
+                (SELECT title FROM ranking_cache WHERE rank = 1) AS "top?: String"
+            FROM ranking_cache
+        "#
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(Summary {
+        active: r.active,
+        top: r.top,
+    })
+}
+
+/// Deviations of the active entries as of the last refresh.
+pub async fn deviations(conn: &mut SqliteConnection) -> Result<Vec<f64>> {
+    let rows = query!("SELECT deviation FROM ranking_cache")
+        .fetch_all(conn)
+        .await?;
+    Ok(rows.into_iter().map(|r| r.deviation).collect())
+}
@@ -0,0 +1,46 @@
+const BINS: usize = 10;
+const WIDTH: usize = 40;
+
+/// Renders a horizontal histogram of the ratings, pointing out the bin that
+/// contains `marked`.
+pub fn histogram(ratings: &[f64], marked: Option<f64>) -> Vec<String> {
+    let Some(min) = ratings.iter().copied().reduce(f64::min) else {
+        return vec![];
+    };
+    let max = ratings.iter().copied().fold(min, f64::max);
+
+    let bins = if max > min {
+        BINS.min(ratings.len())
+    } else {
+        1
+    };
+    let size = ((max - min) / bins as f64).max(1.0);
+    let bin_of = |rating: f64| (((rating - min) / size) as usize).min(bins - 1);
+
+    let mut counts = vec![0; bins];
+    for rating in ratings {
+        counts[bin_of(*rating)] += 1;
+    }
+    let most = counts.iter().copied().max().unwrap();
+
+    // highest ratings on top, like show
+    (0..bins)
+        .rev()
+        .map(|i| {
+            let from = min + i as f64 * size;
+            let bar = "#".repeat((counts[i] * WIDTH).div_ceil(most));
+            let marker = match marked {
+                Some(rating) if bin_of(rating) == i => " <",
+                _ => "",
+            };
+            format!(
+                "{:>5}-{:<5} | {} {}{}",
+                from as i64,
+                (from + size) as i64,
+                bar,
+                counts[i],
+                marker
+            )
+        })
+        .collect()
+}
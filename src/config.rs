@@ -0,0 +1,529 @@
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{Duration, NaiveDate, Utc};
+use serde::Deserialize;
+
+use crate::error;
+use crate::status::Status;
+use crate::util::normalize_path;
+use crate::{Confidence, File};
+
+/// How to order entries with exactly the same rating.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TieBreaker {
+    /// Most recently edited, voted on or changed first.
+    Activity,
+    /// Oldest first.
+    Created,
+    /// Alphabetically.
+    Path,
+}
+
+/// What show, stats, votes and export print.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// For people.
+    #[default]
+    Text,
+    /// For jq and other scripts.
+    Json,
+    /// For spreadsheets, only supported by export.
+    Csv,
+}
+
+/// How show draws images on the terminal, see images::show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageProtocol {
+    Kitty,
+    Iterm,
+    Sixel,
+}
+
+/// Type of a custom field, see Config::fields.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum FieldType {
+    /// One of the listed values.
+    Enum {
+        values: Vec<String>,
+    },
+    Int,
+    /// A date like `2024-01-31`.
+    Date,
+}
+
+impl FieldType {
+    /// Checks `value` against the type, returning it in the form it is
+    /// stored and compared in.
+    pub fn parse(&self, value: &str) -> Result<String> {
+        let value = value.trim();
+        match self {
+            FieldType::Enum { values } => {
+                if !values.iter().any(|v| v == value) {
+                    bail!("{:?} isn't one of {}", value, values.join(", "));
+                }
+                Ok(value.to_string())
+            }
+            FieldType::Int => {
+                let n: i64 = value
+                    .parse()
+                    .map_err(|_| anyhow!("{:?} isn't an integer", value))?;
+                Ok(n.to_string())
+            }
+            FieldType::Date => {
+                let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                    .map_err(|_| anyhow!("{:?} isn't a date like 2024-01-31", value))?;
+                Ok(date.format("%Y-%m-%d").to_string())
+            }
+        }
+    }
+}
+
+/// Which files in the entries directories are entries.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Walk {
+    /// How deep to look into subdirectories, 1 being only the directory
+    /// itself.
+    pub max_depth: Option<usize>,
+    /// Only files with one of these extensions, e.g. `["md", "txt"]`. Any
+    /// file when empty.
+    pub extensions: Vec<String>,
+    /// Minimum file size in bytes.
+    pub min_size: Option<u64>,
+    /// Maximum file size in bytes.
+    pub max_size: Option<u64>,
+    /// Files or directories, relative to an entries directory, that are never
+    /// synced. The database and the files next to it are always excluded.
+    pub exclude: Vec<PathBuf>,
+}
+
+impl Walk {
+    /// Whether the file at `relative` in an entries directory, `depth` levels
+    /// deep, is an entry.
+    pub fn includes(&self, relative: &Path, depth: usize, size: u64) -> bool {
+        let extension = relative.extension().and_then(|ext| ext.to_str());
+        self.max_depth.is_none_or(|max| depth <= max)
+            && (self.extensions.is_empty()
+                || extension.is_some_and(|ext| self.extensions.iter().any(|e| e == ext)))
+            && self.min_size.is_none_or(|min| size >= min)
+            && self.max_size.is_none_or(|max| size <= max)
+            && !self.exclude.iter().any(|e| relative.starts_with(e))
+    }
+}
+
+/// How much more vote's sampler picks entries edited recently, so their
+/// rating catches up with what they say now.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecentEdits {
+    /// Factor on the chance of picking an entry with a new revision in the
+    /// last `days` days, 1 for no preference.
+    pub weight: f64,
+    pub days: u32,
+}
+
+impl Default for RecentEdits {
+    fn default() -> Self {
+        RecentEdits {
+            weight: 1.0,
+            days: 7,
+        }
+    }
+}
+
+impl RecentEdits {
+    /// The factor for `item`. Only edits count, not the first revision.
+    pub fn weight(&self, item: &File) -> f64 {
+        let since = Utc::now() - Duration::days(self.days as i64);
+        if item.file_contents.len() > 1 && item.last_content().at >= since {
+            self.weight
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Makes the ratings of entries that changed a lot since they were last
+/// voted on less certain again, so they get compared sooner.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EditBump {
+    /// Part of the lines of a revision that has to differ from the previous
+    /// one, between 0 and 1. Defaults to 0.5.
+    pub changed: f64,
+    /// Added to the deviation at the time of the revision, up to that of an
+    /// entry without votes. Defaults to 100.
+    pub deviation: f64,
+}
+
+impl Default for EditBump {
+    fn default() -> Self {
+        EditBump {
+            changed: 0.5,
+            deviation: 100.0,
+        }
+    }
+}
+
+/// Limits on the ratings shown by show, its chart and export, so a few
+/// entries with runaway ratings don't squash everything else. Ratings and
+/// the ranking themselves are left alone.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Clamp {
+    /// Show lower ratings as this.
+    pub floor: Option<f64>,
+    /// Show higher ratings as this.
+    pub ceiling: Option<f64>,
+    /// Fraction of the entries at either end to show with the rating of the
+    /// last one inside, e.g. 0.02 to cut at the 2nd and 98th percentile.
+    pub winsorize: Option<f64>,
+}
+
+impl Clamp {
+    /// The range to show `ratings` in, with f64::clamp.
+    pub fn bounds(&self, ratings: &[f64]) -> (f64, f64) {
+        let mut low = self.floor.unwrap_or(f64::NEG_INFINITY);
+        let mut high = self.ceiling.unwrap_or(f64::INFINITY);
+        if let Some(fraction) = self.winsorize.filter(|_| !ratings.is_empty()) {
+            let mut sorted = ratings.to_vec();
+            sorted.sort_by(f64::total_cmp);
+            let cut =
+                ((sorted.len() as f64 * fraction.clamp(0.0, 0.5)) as usize).min(sorted.len() - 1);
+            low = low.max(sorted[cut]);
+            high = high.min(sorted[sorted.len() - 1 - cut]);
+        }
+        (low.min(high), high)
+    }
+}
+
+/// Entries that vote never pairs up, see Config::never_compare.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NeverCompare {
+    /// Entries with this tag.
+    pub tag: String,
+    /// Only excludes pairs with an entry with this tag, any pair when not
+    /// set.
+    pub with: Option<String>,
+}
+
+/// A collection of entries with its own database, selected with --profile.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub dir: PathBuf,
+    /// Defaults to `.db.db` in `dir`.
+    pub db: Option<PathBuf>,
+    /// Like Config::roots, the roots of the main collection don't apply.
+    #[serde(default)]
+    pub roots: BTreeMap<String, PathBuf>,
+}
+
+/// Which entries prio autoprune takes out of the active pool.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AutoPrune {
+    /// Entries that haven't been edited, voted on or changed for this many
+    /// months and were in the bottom tenth of the ranking all that time, as
+    /// checked now and 30, 60, ... days ago. Entries whose page is gone are
+    /// pruned regardless, see File::dead_link.
+    pub months: u32,
+    /// Either snoozed or dropped.
+    pub status: Status,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// How to derive the title of entries without a `display` front matter
+    /// field, e.g. `"{project}: {first_line}"`. See `File::make_title`.
+    pub title_template: Option<String>,
+    /// Applied in order when ratings are equal, path is always used last.
+    pub tie_breakers: Vec<TieBreaker>,
+    /// Program and arguments of an external sampler used by vote. It gets a
+    /// JSON array of candidate entries on stdin and has to print a JSON array
+    /// with the indices of the two entries to compare.
+    pub sampler_command: Option<Vec<String>>,
+    /// Program and arguments of a text to speech command used by speak, e.g.
+    /// `["espeak-ng"]`. It gets the text on stdin.
+    pub speak_command: Option<Vec<String>>,
+    /// Program and arguments of the command `prio drift --notify` alerts
+    /// with, getting the message as its last argument. Defaults to
+    /// `["notify-send", "prio"]`.
+    pub notify_command: Option<Vec<String>>,
+    /// Program and arguments of a command that renders entries for show on
+    /// a terminal, e.g. `["glow", "-"]` or `["bat", "--language", "md"]`,
+    /// getting the content on stdin. Without it Markdown entries get basic
+    /// styling, see render::terminal.
+    pub render_command: Option<Vec<String>>,
+    /// How to draw the images entries reference after their content in
+    /// show, when built with the images feature. Kitty and iTerm are detected
+    /// when not set, sixel has to be set explicitly. Without one the image
+    /// files are listed.
+    pub image_protocol: Option<ImageProtocol>,
+    /// What show, stats and votes print, overridden by --format.
+    pub format: Format,
+    /// ISO 639-3 codes of the languages entries are written in, e.g.
+    /// `["nld", "eng"]`. Language detection only picks between these, which
+    /// is a lot more reliable for short entries. Empty means any language.
+    pub languages: Vec<String>,
+    pub autoprune: Option<AutoPrune>,
+    /// Entries with a deviation above this aren't settled yet, see prio
+    /// needs-votes. Defaults to 150.
+    pub settled_deviation: f64,
+    pub recent_edits: RecentEdits,
+    /// Raise the deviation of entries on large edits, off when not set.
+    pub edit_bump: Option<EditBump>,
+    /// Where prio backup writes snapshots of the database, `.backups` next
+    /// to the database when not set.
+    pub backup_dir: Option<PathBuf>,
+    /// Snapshot the database before commands that remove things, like
+    /// remove, autoprune --apply and merge. On by default.
+    pub auto_backup: bool,
+    /// Number of snapshots kept, the oldest are removed after each new one.
+    /// Defaults to 10.
+    pub backup_keep: usize,
+    /// Suggest taking a break after this many votes in a row.
+    pub break_after: Option<usize>,
+    /// Start each vote session with this many pairs with a clear expected
+    /// outcome, to check whether the usual criterion is being used.
+    pub calibration_pairs: usize,
+    /// Pairs vote never asks about, e.g. `[{ tag = "reference" }, { tag =
+    /// "car", with = "bike" }]`. Entries can also list the paths of entries
+    /// they shouldn't be compared with under `never_compare` in their front
+    /// matter.
+    pub never_compare: Vec<NeverCompare>,
+    /// Ask how sure you are after each vote, sure or leaning.
+    pub ask_confidence: bool,
+    /// How much a leaning vote counts compared to a sure one, between 0 and
+    /// 1. Defaults to 0.5.
+    pub leaning_weight: f64,
+    /// Only count votes on these criteria when computing ratings, `overall`
+    /// standing for votes without one. All votes are counted when not set.
+    pub include_criteria: Option<Vec<String>>,
+    /// Don't count votes on these criteria when computing ratings.
+    pub exclude_criteria: Vec<String>,
+    /// More directories with entries by name, tracked in the same database.
+    /// Their entries are stored as `name:relative/path`.
+    pub roots: BTreeMap<String, PathBuf>,
+    /// Fail syncing on the first file that can't be read, instead of
+    /// skipping it.
+    pub strict_sync: bool,
+    pub walk: Walk,
+    pub clamp: Clamp,
+    /// The main directory with entries, overridden by `PRIO_DIR`. Defaults to
+    /// `~/entries`.
+    pub dir: PathBuf,
+    /// The database, overridden by `PRIO_DB`. Defaults to `.db.db` in `dir`.
+    pub db: Option<PathBuf>,
+    /// Other collections by name, e.g. `work = { dir = "~/work/entries" }`,
+    /// used instead of `dir`, `db` and `roots` with `--profile work`.
+    pub profiles: BTreeMap<String, Profile>,
+    /// Command and arguments run by a bare `prio`, e.g. `["dashboard"]`.
+    /// Defaults to show.
+    pub default_command: Option<Vec<String>>,
+    /// Custom metadata fields by name, e.g. `impact = { type = "int" }`.
+    /// Values come from the front matter of entries, or else from `prio set`.
+    /// Front matter values that don't fit the type are ignored.
+    pub fields: BTreeMap<String, FieldType>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            title_template: None,
+            tie_breakers: vec![TieBreaker::Activity, TieBreaker::Created, TieBreaker::Path],
+            sampler_command: None,
+            speak_command: None,
+            notify_command: None,
+            render_command: None,
+            image_protocol: None,
+            format: Format::Text,
+            languages: Vec::new(),
+            autoprune: None,
+            settled_deviation: 150.0,
+            recent_edits: RecentEdits::default(),
+            edit_bump: None,
+            backup_dir: None,
+            auto_backup: true,
+            backup_keep: 10,
+            break_after: None,
+            calibration_pairs: 0,
+            never_compare: Vec::new(),
+            ask_confidence: false,
+            leaning_weight: 0.5,
+            include_criteria: None,
+            exclude_criteria: Vec::new(),
+            roots: BTreeMap::new(),
+            strict_sync: false,
+            walk: Walk::default(),
+            clamp: Clamp::default(),
+            dir: std::env::var_os("HOME")
+                .map(PathBuf::from)
+                .unwrap_or_default()
+                .join("entries"),
+            db: None,
+            profiles: BTreeMap::new(),
+            default_command: None,
+            fields: BTreeMap::new(),
+        }
+    }
+}
+
+/// Replaces a leading `~` in a path from the config file by the home
+/// directory.
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Where the config file is read from.
+pub fn path() -> Option<PathBuf> {
+    let dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(dir.join("prio").join("config.toml"))
+}
+
+impl Config {
+    /// All directories with entries, the main one being named "".
+    pub fn roots(&self) -> Vec<(&str, &Path)> {
+        let mut roots = vec![("", self.dir.as_path())];
+        roots.extend(self.roots.iter().map(|(k, v)| (k.as_str(), v.as_path())));
+        roots
+    }
+
+    /// The root an entry stored as `path` is in, and its path relative to it.
+    pub fn split_root<'a>(&self, path: &'a str) -> (&'a str, &'a str) {
+        match path.split_once(':') {
+            Some((root, rest)) if self.roots.contains_key(root) => (root, rest),
+            _ => ("", path),
+        }
+    }
+
+    /// How the file at `relative` in `root` is stored in the database.
+    pub fn entry_path(root: &str, relative: &Path) -> PathBuf {
+        let relative = normalize_path(&relative.to_string_lossy());
+        match root {
+            "" => PathBuf::from(relative),
+            root => PathBuf::from(format!("{}:{}", root, relative)),
+        }
+    }
+
+    /// How the entry at `path`, as given on the command line, is stored:
+    /// paths in the directory of a root are taken relative to it.
+    pub fn resolve(&self, path: &str) -> PathBuf {
+        let full = Path::new(path);
+        self.roots()
+            .into_iter()
+            .find_map(|(root, dir)| Some(Config::entry_path(root, full.strip_prefix(dir).ok()?)))
+            .unwrap_or_else(|| PathBuf::from(normalize_path(path)))
+    }
+
+    /// Where the entry stored as `path` is on disk.
+    pub fn locate(&self, path: &str) -> PathBuf {
+        match self.split_root(path) {
+            ("", path) => self.dir.join(path),
+            (root, rest) => self.roots[root].join(rest),
+        }
+    }
+
+    /// Whether `path` would be located inside its root: relative and without
+    /// `..`, so it's safe to write a file for it.
+    pub fn is_inside(&self, path: &str) -> bool {
+        let (_, rest) = self.split_root(path);
+        !rest.is_empty()
+            && Path::new(rest)
+                .components()
+                .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    }
+
+    pub fn db(&self) -> PathBuf {
+        match &self.db {
+            Some(db) => db.clone(),
+            None => self.dir.join(".db.db"),
+        }
+    }
+
+    pub fn field(&self, name: &str) -> Result<&FieldType> {
+        self.fields
+            .get(name)
+            .ok_or_else(|| anyhow!("no field {:?} in the config", name))
+    }
+
+    /// How much a vote counts towards the ratings given how sure it was.
+    pub fn vote_weight(&self, confidence: Option<Confidence>) -> f64 {
+        match confidence {
+            Some(Confidence::Leaning) => self.leaning_weight.clamp(0.0, 1.0),
+            Some(Confidence::Sure) | None => 1.0,
+        }
+    }
+
+    /// Whether votes on `criterion` count towards the ratings.
+    pub fn counts_criterion(&self, criterion: Option<&str>) -> bool {
+        let criterion = criterion.unwrap_or("overall");
+        let included = match &self.include_criteria {
+            Some(include) => include.iter().any(|c| c == criterion),
+            None => true,
+        };
+        included && !self.exclude_criteria.iter().any(|c| c == criterion)
+    }
+
+    /// Loads the config file, falling back to the defaults when it doesn't
+    /// exist, and applies the profile and then `PRIO_DIR` and `PRIO_DB`.
+    pub fn load(profile: Option<&str>) -> Result<Config> {
+        let mut config = match path() {
+            None => Config::default(),
+            Some(path) => match std::fs::read_to_string(&path) {
+                Ok(s) => {
+                    toml::from_str(&s).with_context(|| format!("parsing {}", path.display()))?
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Config::default(),
+                Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+            },
+        };
+        let weight = config.recent_edits.weight;
+        if !(weight.is_finite() && weight >= 0.0) {
+            return Err(error::invalid(format!(
+                "recent_edits.weight has to be 0 or more, not {}",
+                weight
+            )));
+        }
+
+        if let Some(name) = profile {
+            let Some(profile) = config.profiles.get(name) else {
+                let known: Vec<_> = config.profiles.keys().map(String::as_str).collect();
+                if known.is_empty() {
+                    bail!("no profile {:?}, the config doesn't have any", name);
+                }
+                bail!("no profile {:?}, there are: {}", name, known.join(", "));
+            };
+            config.dir = profile.dir.clone();
+            config.db = profile.db.clone();
+            config.roots = profile.roots.clone();
+        }
+        config.dir = expand_home(&config.dir);
+        config.db = config.db.as_deref().map(expand_home);
+        config.backup_dir = config.backup_dir.as_deref().map(expand_home);
+        for dir in config.roots.values_mut() {
+            *dir = expand_home(dir);
+        }
+        if let Some(dir) = std::env::var_os("PRIO_DIR") {
+            config.dir = PathBuf::from(dir);
+        }
+        if let Some(db) = std::env::var_os("PRIO_DB") {
+            config.db = Some(PathBuf::from(db));
+        }
+        Ok(config)
+    }
+}
@@ -0,0 +1,88 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use serde_yaml::Mapping;
+
+use crate::{frontmatter, File};
+
+/// How much energy doing an entry takes, or a user has left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum Energy {
+    Low,
+    Medium,
+    High,
+}
+
+impl FromStr for Energy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.to_lowercase().as_str() {
+            "low" => Energy::Low,
+            "medium" => Energy::Medium,
+            "high" => Energy::High,
+            _ => bail!("unknown energy {:?}, expected low, medium or high", s),
+        })
+    }
+}
+
+/// Where the user is and how much energy they have, to only pick entries that
+/// can be done right now.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct Situation {
+    /// Only entries that can be done in this context, e.g. home for entries
+    /// tagged @home. Entries without a context can be done anywhere.
+    #[arg(long = "context", alias = "at", value_name = "CONTEXT")]
+    pub at: Option<String>,
+    /// Only entries that take at most this much energy. Entries that don't
+    /// say take medium energy.
+    #[arg(long, value_enum)]
+    pub energy: Option<Energy>,
+}
+
+impl Situation {
+    pub fn allows(&self, item: &File) -> bool {
+        let context_ok = self.at.as_ref().is_none_or(|context| {
+            let contexts = contexts(item);
+            let context = context.trim_start_matches('@');
+            contexts.is_empty() || contexts.iter().any(|c| c.eq_ignore_ascii_case(context))
+        });
+        let energy_ok = self
+            .energy
+            .is_none_or(|energy| self::energy(item).unwrap_or(Energy::Medium) <= energy);
+        context_ok && energy_ok
+    }
+}
+
+fn front_matter(item: &File) -> Option<Mapping> {
+    let content = String::from_utf8_lossy(&item.last_content().content);
+    frontmatter::split(&content).0
+}
+
+/// The contexts an entry can be done in: its `@name` tags and the values of
+/// its `context` front matter field.
+pub fn contexts(item: &File) -> Vec<String> {
+    let mut contexts: Vec<String> = item
+        .tags()
+        .iter()
+        .filter_map(|tag| tag.strip_prefix('@'))
+        .map(str::to_string)
+        .collect();
+    if let Some(fm) = front_matter(item) {
+        contexts.extend(frontmatter::list(&fm, "context"));
+    }
+    contexts
+}
+
+/// The `energy` front matter field, or else a tag like `lowenergy` or
+/// `#lowenergy`.
+pub fn energy(item: &File) -> Option<Energy> {
+    if let Some(energy) = front_matter(item).and_then(|fm| frontmatter::scalar(&fm, "energy")) {
+        return energy.parse().ok();
+    }
+    item.tags().iter().find_map(|tag| {
+        tag.trim_start_matches('#')
+            .strip_suffix("energy")
+            .and_then(|level| level.trim_end_matches(['-', '_']).parse().ok())
+    })
+}
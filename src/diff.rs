@@ -0,0 +1,118 @@
+/// A line of the diff between two texts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Diffs the lines of `old` and `new` through their longest common
+/// subsequence. Quadratic in the lines that differ, which is fine for the
+/// size of entries.
+pub fn lines<'a>(old: &'a str, new: &'a str) -> Vec<Line<'a>> {
+    let old: Vec<_> = old.lines().collect();
+    let new: Vec<_> = new.lines().collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    // lcs[i][j] is the length of the longest common subsequence of a[i..]
+    // and b[j..].
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut res: Vec<_> = old[..prefix].iter().map(|l| Line::Same(l)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            res.push(Line::Same(a[i]));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            res.push(Line::Removed(a[i]));
+            i += 1;
+        } else {
+            res.push(Line::Added(b[j]));
+            j += 1;
+        }
+    }
+    res.extend(old[old.len() - suffix..].iter().map(|l| Line::Same(l)));
+    res
+}
+
+/// Renders the diff in unified format without the file headers, keeping
+/// `context` unchanged lines around every change. Empty if the texts have
+/// the same lines.
+pub fn unified(old: &str, new: &str, context: usize) -> Vec<String> {
+    let diff = lines(old, new);
+    let changed: Vec<_> = diff
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| !matches!(l, Line::Same(_)))
+        .map(|(i, _)| i)
+        .collect();
+
+    // Changes less than two contexts apart share a hunk.
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for &i in &changed {
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(diff.len());
+        match hunks.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut res = vec![];
+    for (start, end) in hunks {
+        // Line numbers where the hunk starts in either text.
+        let before = &diff[..start];
+        let old_start = before
+            .iter()
+            .filter(|l| !matches!(l, Line::Added(_)))
+            .count();
+        let new_start = before
+            .iter()
+            .filter(|l| !matches!(l, Line::Removed(_)))
+            .count();
+        let hunk = &diff[start..end];
+        let old_len = hunk.iter().filter(|l| !matches!(l, Line::Added(_))).count();
+        let new_len = hunk
+            .iter()
+            .filter(|l| !matches!(l, Line::Removed(_)))
+            .count();
+
+        // Empty ranges start at the line before them.
+        let first = |start: usize, len: usize| if len == 0 { start } else { start + 1 };
+        res.push(format!(
+            "@@ -{},{} +{},{} @@",
+            first(old_start, old_len),
+            old_len,
+            first(new_start, new_len),
+            new_len
+        ));
+        res.extend(hunk.iter().map(|l| match l {
+            Line::Same(l) => format!(" {}", l),
+            Line::Removed(l) => format!("-{}", l),
+            Line::Added(l) => format!("+{}", l),
+        }));
+    }
+    res
+}
@@ -0,0 +1,341 @@
+//! The whole database as one JSON file, for backups and for moving a
+//! collection to another machine, see prio export --all and prio import.
+
+use std::borrow::BorrowMut;
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, Connection, SqliteConnection};
+
+use crate::{error, votes};
+
+/// The format of the dump, bumped when it changes incompatibly.
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Dump {
+    pub version: u32,
+    pub entries: Vec<Entry>,
+    pub votes: Vec<Vote>,
+    /// The settings table, such as the live rating parameters.
+    pub settings: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub path: String,
+    pub id: Option<i64>,
+    pub root: String,
+    /// Every revision, oldest first.
+    pub revisions: Vec<Revision>,
+    pub status_history: Vec<StatusChange>,
+    pub fields: BTreeMap<String, String>,
+}
+
+impl Entry {
+    /// The latest content, None if the entry is deleted.
+    pub fn file(&self) -> Option<&Content> {
+        if self
+            .status_history
+            .last()
+            .is_some_and(|s| s.status == "deleted")
+        {
+            return None;
+        }
+        self.revisions.last().and_then(|r| r.content.as_ref())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Revision {
+    /// None for deletions recorded before status_history existed.
+    pub content: Option<Content>,
+    pub at: i64,
+}
+
+/// Content is kept readable in the dump when it's text.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Content {
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl Content {
+    fn new(bytes: Vec<u8>) -> Self {
+        match String::from_utf8(bytes) {
+            Ok(text) => Content::Text(text),
+            Err(e) => Content::Bytes(e.into_bytes()),
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Content::Text(text) => text.as_bytes(),
+            Content::Bytes(bytes) => bytes,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusChange {
+    pub status: String,
+    pub actor: String,
+    pub reason: Option<String>,
+    pub at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Vote {
+    pub left: String,
+    pub right: String,
+    pub vote: i64,
+    pub criterion: Option<String>,
+    pub confidence: Option<String>,
+    pub at: i64,
+}
+
+/// Everything needed to rebuild the ranking, deleted entries included. The
+/// audit log, caches, resurface schedule and experiments aren't part of it.
+pub async fn dump(conn: &mut SqliteConnection) -> Result<Dump> {
+    let rows = query!("SELECT path, id, root FROM entries ORDER BY id, path")
+        .fetch_all(conn.borrow_mut())
+        .await?;
+
+    let mut entries = Vec::with_capacity(rows.len());
+    for row in rows {
+        let revisions = query!(
+            "SELECT content, at FROM file_contents WHERE path = ?1 ORDER BY at ASC, rowid ASC",
+            row.path
+        )
+        .map(|r| Revision {
+            content: r.content.map(Content::new),
+            at: r.at,
+        })
+        .fetch_all(conn.borrow_mut())
+        .await?;
+        let status_history = query!(
+            r#"
+                SELECT status, actor, reason, at
+                FROM status_history
+                WHERE path = ?1
+                ORDER BY at ASC, rowid ASC
+            "#,
+            row.path
+        )
+        .map(|r| StatusChange {
+            status: r.status,
+            actor: r.actor,
+            reason: r.reason,
+            at: r.at,
+        })
+        .fetch_all(conn.borrow_mut())
+        .await?;
+        let fields = query!(
+            "SELECT name, value FROM entry_fields WHERE path = ?1",
+            row.path
+        )
+        .map(|r| (r.name, r.value))
+        .fetch_all(conn.borrow_mut())
+        .await?;
+
+        entries.push(Entry {
+            path: row.path,
+            id: row.id,
+            root: row.root,
+            revisions,
+            status_history,
+            fields: fields.into_iter().collect(),
+        });
+    }
+
+    let votes = votes::list(conn, None)
+        .await?
+        .into_iter()
+        .map(|r| Vote {
+            left: r.left_path,
+            right: r.right_path,
+            vote: r.vote,
+            criterion: r.criterion,
+            confidence: r.confidence,
+            at: r.at,
+        })
+        .collect();
+
+    let settings = query!("SELECT key, value FROM settings")
+        .map(|r| (r.key, r.value))
+        .fetch_all(conn)
+        .await?;
+
+    Ok(Dump {
+        version: VERSION,
+        entries,
+        votes,
+        settings: settings.into_iter().collect(),
+    })
+}
+
+/// Loads `dump` into a database without entries, all in one transaction.
+/// Combining collections is what prio merge is for.
+pub async fn load(conn: &mut SqliteConnection, dump: &Dump) -> Result<()> {
+    if dump.version > VERSION {
+        return Err(error::invalid(format!(
+            "the dump has version {}, this prio only knows up to {}",
+            dump.version, VERSION
+        )));
+    }
+
+    let paths: HashSet<&str> = dump.entries.iter().map(|e| e.path.as_str()).collect();
+    for (i, v) in dump.votes.iter().enumerate() {
+        if let Some(path) = [&v.left, &v.right]
+            .into_iter()
+            .find(|path| !paths.contains(path.as_str()))
+        {
+            return Err(error::invalid(format!(
+                "vote {}: {} isn't one of the entries in the dump",
+                i + 1,
+                path
+            )));
+        }
+    }
+
+    let mut tx = conn.begin().await?;
+    let existing = query!(r#"SELECT COUNT(*) AS "count!: i64" FROM entries"#)
+        .fetch_one(&mut *tx)
+        .await?
+        .count;
+    if existing > 0 {
+        return Err(error::conflict(format!(
+            "the database already has {} entries, import into a new one or use prio merge",
+            existing
+        )));
+    }
+
+    for entry in &dump.entries {
+        query!(
+            "INSERT INTO entries (path, id, root) VALUES (?1, ?2, ?3)",
+            entry.path,
+            entry.id,
+            entry.root
+        )
+        .execute(&mut *tx)
+        .await?;
+        for rev in &entry.revisions {
+            let content = rev.content.as_ref().map(Content::as_bytes);
+            query!(
+                "INSERT INTO file_contents (path, content, at) VALUES (?1, ?2, ?3)",
+                entry.path,
+                content,
+                rev.at
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        for s in &entry.status_history {
+            query!(
+                r#"
+                    INSERT INTO status_history
+                        (path, status, actor, reason, at)
+                    VALUES
+                        (?1, ?2, ?3, ?4, ?5)
+                "#,
+                entry.path,
+                s.status,
+                s.actor,
+                s.reason,
+                s.at
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        for (name, value) in &entry.fields {
+            query!(
+                "INSERT INTO entry_fields (path, name, value) VALUES (?1, ?2, ?3)",
+                entry.path,
+                name,
+                value
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    for v in &dump.votes {
+        query!(
+            r#"
+                INSERT INTO entry_votes
+                    (left_path, right_path, vote, criterion, confidence, at)
+                VALUES
+                    (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+            v.left,
+            v.right,
+            v.vote,
+            v.criterion,
+            v.confidence,
+            v.at
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for (key, value) in &dump.settings {
+        query!(
+            r#"
+                INSERT INTO settings (key, value) VALUES (?1, ?2)
+                ON CONFLICT (key) DO UPDATE SET value = excluded.value
+            "#,
+            key,
+            value
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema;
+
+    #[tokio::test]
+    async fn votes_on_entries_outside_the_dump_are_rejected() {
+        let mut conn = schema::memory().await;
+        let dump = Dump {
+            version: VERSION,
+            entries: vec![Entry {
+                path: "a.md".to_string(),
+                id: Some(1),
+                root: String::new(),
+                revisions: vec![Revision {
+                    content: Some(Content::Text("a".to_string())),
+                    at: 0,
+                }],
+                status_history: vec![],
+                fields: BTreeMap::new(),
+            }],
+            votes: vec![Vote {
+                left: "a.md".to_string(),
+                right: "b.md".to_string(),
+                vote: 1,
+                criterion: None,
+                confidence: None,
+                at: 0,
+            }],
+            settings: BTreeMap::new(),
+        };
+
+        let err = load(&mut conn, &dump).await.unwrap_err();
+        assert_eq!(error::kind(&err), Some(error::Kind::InvalidArgument));
+        let entries = query!(r#"SELECT COUNT(*) AS "n!: i64" FROM entries"#)
+            .fetch_one(&mut conn)
+            .await
+            .unwrap()
+            .n;
+        assert_eq!(entries, 0);
+    }
+}
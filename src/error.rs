@@ -0,0 +1,112 @@
+//! Kinds of errors, so those about what the user asked for can be told apart
+//! from prio or its environment failing. Errors stay anyhow errors, ones with
+//! a kind are made with the functions here and found again with kind.
+
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// An entry, version, vote or file that was asked for doesn't exist.
+    NotFound,
+    /// An argument, config value or input file that doesn't make sense.
+    InvalidArgument,
+    /// What was asked for doesn't fit the state of the collection, like
+    /// activating an active entry.
+    Conflict,
+    Io,
+    Db,
+    /// The database or the entries don't hold what they should.
+    Corruption,
+}
+
+impl Kind {
+    /// Whether the error is the user's to fix, so can be reported without
+    /// causes or backtrace.
+    pub fn is_user_error(self) -> bool {
+        matches!(
+            self,
+            Kind::NotFound | Kind::InvalidArgument | Kind::Conflict
+        )
+    }
+
+    /// What prio exits with, from sysexits.h.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Kind::InvalidArgument => 64,
+            Kind::Conflict | Kind::Corruption => 65,
+            Kind::NotFound => 66,
+            Kind::Db => 70,
+            Kind::Io => 74,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Error {
+    kind: Kind,
+    message: String,
+}
+
+impl Error {
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub fn new(kind: Kind, message: impl Display) -> anyhow::Error {
+    Error {
+        kind,
+        message: message.to_string(),
+    }
+    .into()
+}
+
+pub fn not_found(message: impl Display) -> anyhow::Error {
+    new(Kind::NotFound, message)
+}
+
+pub fn invalid(message: impl Display) -> anyhow::Error {
+    new(Kind::InvalidArgument, message)
+}
+
+pub fn conflict(message: impl Display) -> anyhow::Error {
+    new(Kind::Conflict, message)
+}
+
+pub fn corruption(message: impl Display) -> anyhow::Error {
+    new(Kind::Corruption, message)
+}
+
+/// The kind of the first error in the chain of `err` that has one. I/O and
+/// database errors have theirs without being made here, other errors have
+/// none.
+pub fn kind(err: &anyhow::Error) -> Option<Kind> {
+    err.chain().find_map(|cause| {
+        if let Some(e) = cause.downcast_ref::<Error>() {
+            return Some(e.kind);
+        }
+        if cause.is::<std::io::Error>() {
+            return Some(Kind::Io);
+        }
+        match cause.downcast_ref::<sqlx::Error>()? {
+            sqlx::Error::Io(_) => Some(Kind::Io),
+            sqlx::Error::Database(e) => {
+                // SQLITE_CORRUPT and SQLITE_NOTADB, primary or extended.
+                let code = e.code().and_then(|c| c.parse::<i32>().ok()).unwrap_or(0);
+                match code & 0xff {
+                    11 | 26 => Some(Kind::Corruption),
+                    _ => Some(Kind::Db),
+                }
+            }
+            _ => Some(Kind::Db),
+        }
+    })
+}
@@ -0,0 +1,149 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+
+use crate::config::Config;
+use crate::graph::Edge;
+use crate::util::{normalize_path, path_str};
+use crate::{frontmatter, File};
+
+/// How many pairs to draw before concluding that every pair the sampler
+/// comes up with is excluded.
+const DRAWS: usize = 100;
+
+/// Paths of the entries listed under `never_compare` in the front matter of
+/// `item`, as stored.
+pub fn listed(item: &File) -> Vec<PathBuf> {
+    let content = String::from_utf8_lossy(&item.last_content().content);
+    let Some(fm) = frontmatter::split(&content).0 else {
+        return vec![];
+    };
+    frontmatter::list(&fm, "never_compare")
+        .iter()
+        .map(|path| PathBuf::from(normalize_path(path)))
+        .collect()
+}
+
+/// Whether a rule in Config::never_compare without `with` excludes `item`
+/// from every pair.
+pub fn never_compared(config: &Config, item: &File) -> bool {
+    let rules: Vec<_> = config
+        .never_compare
+        .iter()
+        .filter(|rule| rule.with.is_none())
+        .collect();
+    !rules.is_empty() && {
+        let tags = item.tags();
+        rules.iter().any(|rule| tags.contains(&rule.tag))
+    }
+}
+
+/// The pairs of entries that should never be compared, from
+/// Config::never_compare and the `never_compare` front matter lists.
+pub struct Exclusions<'a> {
+    config: &'a Config,
+    pairs: HashSet<(PathBuf, PathBuf)>,
+}
+
+impl<'a> Exclusions<'a> {
+    pub fn new<'b>(config: &'a Config, items: impl IntoIterator<Item = &'b File>) -> Self {
+        let mut pairs = HashSet::new();
+        for item in items {
+            for other in listed(item) {
+                pairs.insert((item.path.clone(), other.clone()));
+                pairs.insert((other, item.path.clone()));
+            }
+        }
+        Exclusions { config, pairs }
+    }
+
+    pub fn allows(&self, a: &File, b: &File) -> bool {
+        if self.pairs.contains(&(a.path.clone(), b.path.clone())) {
+            return false;
+        }
+        if self.config.never_compare.is_empty() {
+            return true;
+        }
+
+        let (a_tags, b_tags) = (a.tags(), b.tags());
+        !self
+            .config
+            .never_compare
+            .iter()
+            .any(|rule| match &rule.with {
+                None => a_tags.contains(&rule.tag) || b_tags.contains(&rule.tag),
+                Some(with) => {
+                    (a_tags.contains(&rule.tag) && b_tags.contains(with))
+                        || (b_tags.contains(&rule.tag) && a_tags.contains(with))
+                }
+            })
+    }
+
+    /// Calls `draw` until it returns a pair that may be compared, or fewer
+    /// than two entries. Samplers pick at random, so an excluded pair
+    /// doesn't mean every pair is.
+    pub fn draw(&self, mut draw: impl FnMut() -> Result<Vec<File>>) -> Result<Vec<File>> {
+        for _ in 0..DRAWS {
+            let pair = draw()?;
+            if pair.len() < 2 || self.allows(&pair[0], &pair[1]) {
+                return Ok(pair);
+            }
+        }
+        bail!(
+            "the last {} pairs drawn should never be compared, see never_compare",
+            DRAWS
+        )
+    }
+}
+
+/// Rules and lists that can't have an effect, and votes cast between entries
+/// that shouldn't have been compared, as messages for doctor.
+pub fn problems(config: &Config, items: &[File], edges: &BTreeMap<Edge, i64>) -> Vec<String> {
+    let mut problems = vec![];
+    let by_path: HashMap<&str, &File> = items.iter().map(|f| (path_str(&f.path), f)).collect();
+
+    let tags: HashSet<String> = items.iter().flat_map(|f| f.tags()).collect();
+    for rule in &config.never_compare {
+        for tag in std::iter::once(&rule.tag).chain(&rule.with) {
+            if !tags.contains(tag) {
+                problems.push(format!("never_compare: no entry is tagged {:?}", tag));
+            }
+        }
+    }
+
+    for item in items {
+        for other in listed(item) {
+            if !by_path.contains_key(path_str(&other)) {
+                problems.push(format!(
+                    "{} lists {} under never_compare, which isn't an entry",
+                    item,
+                    other.display()
+                ));
+            }
+        }
+    }
+
+    let exclusions = Exclusions::new(config, items);
+    let mut votes = 0;
+    let mut example = None;
+    for (edge, count) in edges {
+        let (Some(winner), Some(loser)) = (
+            by_path.get(edge.winner.as_str()),
+            by_path.get(edge.loser.as_str()),
+        ) else {
+            continue;
+        };
+        if !exclusions.allows(winner, loser) {
+            votes += count;
+            example.get_or_insert((winner, loser));
+        }
+    }
+    if let Some((winner, loser)) = example {
+        problems.push(format!(
+            "{} votes compare entries that should never be compared, e.g. {} and {}",
+            votes, winner, loser
+        ));
+    }
+    problems
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use prio_core::Rating;
+use sqlx::{query, Connection, SqliteConnection};
+
+use crate::rating::Params;
+use crate::util::path_str;
+use crate::File;
+
+/// Stores the ratings of `items` as computed with `params` under `name`.
+pub async fn save(
+    conn: &mut SqliteConnection,
+    name: &str,
+    params: &Params,
+    items: &[File],
+) -> Result<()> {
+    let mut tx = conn.begin().await?;
+
+    let exists = query!("SELECT name FROM experiments WHERE name = ?1", name)
+        .fetch_optional(&mut *tx)
+        .await?
+        .is_some();
+    if exists {
+        bail!("experiment {:?} already exists", name);
+    }
+
+    let json = serde_json::to_string(params)?;
+    let now = Utc::now().timestamp();
+    query!(
+        "INSERT INTO experiments (name, params, created_at) VALUES (?1, ?2, ?3)",
+        name,
+        json,
+        now
+    )
+    .execute(&mut *tx)
+    .await?;
+    for item in items {
+        let path = path_str(&item.path);
+        query!(
+            r#"
+                INSERT INTO experiment_scores
+                    (experiment, path, rating, deviation)
+                VALUES
+                    (?1, ?2, ?3, ?4)
+            "#,
+            name,
+            path,
+            item.rating.rating,
+            item.rating.deviation
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct Experiment {
+    pub name: String,
+    pub params: Params,
+    pub created_at: DateTime<Utc>,
+}
+
+pub async fn list(conn: &mut SqliteConnection) -> Result<Vec<Experiment>> {
+    query!(
+        r#"
+            SELECT name, params, created_at
+            FROM experiments
+            ORDER BY created_at ASC, name ASC
+        "#
+    )
+    .fetch_all(conn)
+    .await?
+    .into_iter()
+    .map(|r| {
+        Ok(Experiment {
+            name: r.name,
+            params: serde_json::from_str(&r.params)?,
+            created_at: Utc.timestamp_opt(r.created_at, 0).unwrap(),
+        })
+    })
+    .collect()
+}
+
+pub async fn get(conn: &mut SqliteConnection, name: &str) -> Result<Experiment> {
+    list(conn)
+        .await?
+        .into_iter()
+        .find(|e| e.name == name)
+        .ok_or_else(|| anyhow!("no experiment named {:?}", name))
+}
+
+/// The ratings stored for the experiment by entry path.
+pub async fn scores(conn: &mut SqliteConnection, name: &str) -> Result<HashMap<String, Rating>> {
+    let scores = query!(
+        r#"
+            SELECT path, rating, deviation
+            FROM experiment_scores
+            WHERE experiment = ?1
+        "#,
+        name
+    )
+    .fetch_all(conn)
+    .await?
+    .into_iter()
+    .map(|r| {
+        let rating = Rating {
+            rating: r.rating,
+            deviation: r.deviation,
+            ..Rating::new()
+        };
+        (r.path, rating)
+    })
+    .collect();
+    Ok(scores)
+}
+
+pub async fn delete(conn: &mut SqliteConnection, name: &str) -> Result<()> {
+    let mut tx = conn.begin().await?;
+    query!("DELETE FROM experiment_scores WHERE experiment = ?1", name)
+        .execute(&mut *tx)
+        .await?;
+    let res = query!("DELETE FROM experiments WHERE name = ?1", name)
+        .execute(&mut *tx)
+        .await?;
+    if res.rows_affected() == 0 {
+        bail!("no experiment named {:?}", name);
+    }
+    tx.commit().await?;
+    Ok(())
+}
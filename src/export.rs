@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::frontmatter;
+use crate::graph::Edge;
+use crate::util::path_str;
+use crate::File;
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum GraphFormat {
+    Dot,
+    Graphml,
+}
+
+/// Node sizes between 0.5 and 2 relative to the lowest and highest rating,
+/// after clamping them to `bounds`.
+fn node_sizes<'a>(items: &[&'a File], bounds: (f64, f64)) -> Vec<(&'a File, f64)> {
+    let rating = |f: &File| f.rating.rating.clamp(bounds.0, bounds.1);
+    let ratings = items.iter().map(|f| rating(f));
+    let min = ratings.clone().fold(f64::INFINITY, f64::min);
+    let max = ratings.fold(f64::NEG_INFINITY, f64::max);
+    let range = (max - min).max(1.0);
+
+    items
+        .iter()
+        .map(|f| (*f, 0.5 + 1.5 * (rating(f) - min) / range))
+        .collect()
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The entries as CSV with a header, `rank` being empty for entries that
+/// aren't active.
+pub fn ranking_csv(rows: &[(Option<usize>, &File)]) -> anyhow::Result<String> {
+    let mut w = csv::Writer::from_writer(vec![]);
+    w.write_record([
+        "rank",
+        "id",
+        "path",
+        "title",
+        "status",
+        "rating",
+        "deviation",
+        "votes",
+        "modified",
+    ])?;
+    for (rank, item) in rows {
+        w.write_record([
+            rank.map(|r| r.to_string()).unwrap_or_default(),
+            item.id.to_string(),
+            path_str(&item.path).to_string(),
+            item.title.clone(),
+            item.status().to_string(),
+            format!("{:.1}", item.rating.rating),
+            format!("{:.1}", item.rating.deviation),
+            item.votes.to_string(),
+            item.last_content()
+                .at
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string(),
+        ])?;
+    }
+    Ok(String::from_utf8(w.into_inner()?)?)
+}
+
+pub fn graph(
+    format: GraphFormat,
+    items: &[&File],
+    edges: &BTreeMap<Edge, i64>,
+    bounds: (f64, f64),
+) -> String {
+    let nodes = node_sizes(items, bounds);
+    let mut s = String::new();
+
+    match format {
+        GraphFormat::Dot => {
+            s.push_str("digraph prio {\n");
+            for (item, size) in nodes {
+                writeln!(
+                    s,
+                    "\t\"{}\" [label=\"{}\", rating={:.0}, width={:.2}, height={:.2}];",
+                    dot_escape(path_str(&item.path)),
+                    dot_escape(&item.title),
+                    item.rating.rating,
+                    size,
+                    size / 2.0
+                )
+                .unwrap();
+            }
+            for (edge, count) in edges {
+                writeln!(
+                    s,
+                    "\t\"{}\" -> \"{}\" [label={}, penwidth={}{}];",
+                    dot_escape(&edge.winner),
+                    dot_escape(&edge.loser),
+                    count,
+                    count,
+                    if edge.draw {
+                        ", dir=none, style=dashed"
+                    } else {
+                        ""
+                    }
+                )
+                .unwrap();
+            }
+            s.push_str("}\n");
+        }
+        GraphFormat::Graphml => {
+            s.push_str(concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+                "\t<key id=\"title\" for=\"node\" attr.name=\"title\" attr.type=\"string\"/>\n",
+                "\t<key id=\"rating\" for=\"node\" attr.name=\"rating\" attr.type=\"double\"/>\n",
+                "\t<key id=\"size\" for=\"node\" attr.name=\"size\" attr.type=\"double\"/>\n",
+                "\t<key id=\"votes\" for=\"edge\" attr.name=\"votes\" attr.type=\"int\"/>\n",
+                "\t<key id=\"draw\" for=\"edge\" attr.name=\"draw\" attr.type=\"boolean\"/>\n",
+                "\t<graph edgedefault=\"directed\">\n",
+            ));
+            for (item, size) in nodes {
+                writeln!(
+                    s,
+                    concat!(
+                        "\t\t<node id=\"{}\"><data key=\"title\">{}</data>",
+                        "<data key=\"rating\">{}</data><data key=\"size\">{:.2}</data></node>"
+                    ),
+                    xml_escape(path_str(&item.path)),
+                    xml_escape(&item.title),
+                    item.rating.rating,
+                    size
+                )
+                .unwrap();
+            }
+            for (edge, count) in edges {
+                writeln!(
+                    s,
+                    concat!(
+                        "\t\t<edge source=\"{}\" target=\"{}\"><data key=\"votes\">{}</data>",
+                        "<data key=\"draw\">{}</data></edge>"
+                    ),
+                    xml_escape(&edge.winner),
+                    xml_escape(&edge.loser),
+                    count,
+                    edge.draw
+                )
+                .unwrap();
+            }
+            s.push_str("\t</graph>\n</graphml>\n");
+        }
+    }
+    s
+}
+
+/// A standalone HTML page listing the given entries in order, with their
+/// titles and bodies but nothing else from the database: no paths, ratings
+/// or other entries.
+pub fn share(heading: &str, items: &[&File]) -> String {
+    let mut s = String::new();
+    writeln!(
+        s,
+        concat!(
+            "<!DOCTYPE html>\n",
+            "<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n",
+            "<style>body {{ max-width: 40em; margin: auto; font-family: sans-serif; }} ",
+            "pre {{ white-space: pre-wrap; }}</style>\n",
+            "</head>\n<body>\n<h1>{}</h1>\n<ol>"
+        ),
+        xml_escape(heading),
+        xml_escape(heading)
+    )
+    .unwrap();
+    for item in items {
+        let content = String::from_utf8_lossy(&item.last_content().content);
+        let body = frontmatter::split(&content).1;
+        writeln!(
+            s,
+            "<li><details><summary>{}</summary><pre>{}</pre></details></li>",
+            xml_escape(&item.title),
+            xml_escape(body.trim())
+        )
+        .unwrap();
+    }
+    s.push_str("</ol>\n</body>\n</html>\n");
+    s
+}
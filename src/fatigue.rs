@@ -0,0 +1,151 @@
+//! How reliable votes are depending on how long a vote session has been going
+//! on, to tell when it's time for a break, and on how sure the user said they
+//! were.
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use sqlx::{query, SqliteConnection};
+
+use crate::util::path_str;
+use crate::{Confidence, File};
+
+/// Votes further apart than this belong to different sessions.
+const SESSION_GAP: i64 = 30 * 60;
+
+/// Upsets and votes at positions `start..start + len` within a session.
+#[derive(Debug, Clone)]
+pub struct Bucket {
+    pub start: usize,
+    pub len: usize,
+    pub upsets: usize,
+    pub votes: usize,
+}
+
+/// Groups all votes by their position in the vote session, `size` positions
+/// per bucket. An upset is a vote for the entry that is rated lower now, so
+/// this only says something once the ratings have settled somewhat.
+pub async fn upsets_by_position(
+    conn: &mut SqliteConnection,
+    items: &[File],
+    size: usize,
+) -> Result<Vec<Bucket>> {
+    let ratings: HashMap<&str, f64> = items
+        .iter()
+        .map(|f| (path_str(&f.path), f.rating.rating))
+        .collect();
+
+    let votes = query!(
+        r#"
+            SELECT
+                left_path AS "left_path!: String",
+                right_path AS "right_path!: String",
+                vote AS "vote!: i64",
+                at AS "at!: i64"
+            FROM resolved_votes
+            ORDER BY at ASC, id ASC
+        "#
+    )
+    .fetch_all(conn)
+    .await?;
+
+    let mut buckets: Vec<Bucket> = vec![];
+    let mut position = 0;
+    let mut last_at = None;
+    for v in votes {
+        if last_at.is_some_and(|at| v.at - at > SESSION_GAP) {
+            position = 0;
+        }
+        last_at = Some(v.at);
+
+        let (Some(left), Some(right)) = (
+            ratings.get(v.left_path.as_str()),
+            ratings.get(v.right_path.as_str()),
+        ) else {
+            continue;
+        };
+        let i = position / size;
+        position += 1;
+        if v.vote == 0 {
+            continue;
+        }
+
+        while buckets.len() <= i {
+            buckets.push(Bucket {
+                start: buckets.len() * size,
+                len: size,
+                upsets: 0,
+                votes: 0,
+            });
+        }
+        let (winner, loser) = if v.vote > 0 {
+            (left, right)
+        } else {
+            (right, left)
+        };
+        buckets[i].votes += 1;
+        if winner < loser {
+            buckets[i].upsets += 1;
+        }
+    }
+    Ok(buckets)
+}
+
+/// Upsets and votes with a confidence.
+#[derive(Debug, Clone, Default)]
+pub struct Upsets {
+    pub upsets: usize,
+    pub votes: usize,
+}
+
+/// Groups all votes that aren't draws by their confidence, None being votes
+/// cast without asking. If sure votes are reliable, they should have fewer
+/// upsets than leaning ones.
+pub async fn upsets_by_confidence(
+    conn: &mut SqliteConnection,
+    items: &[File],
+) -> Result<Vec<(Option<Confidence>, Upsets)>> {
+    let ratings: HashMap<&str, f64> = items
+        .iter()
+        .map(|f| (path_str(&f.path), f.rating.rating))
+        .collect();
+
+    let votes = query!(
+        r#"
+            SELECT
+                left_path AS "left_path!: String",
+                right_path AS "right_path!: String",
+                vote AS "vote!: i64",
+                confidence
+            FROM resolved_votes
+            WHERE vote != 0
+        "#
+    )
+    .fetch_all(conn)
+    .await?;
+
+    let mut by_confidence: BTreeMap<Option<&str>, Upsets> = BTreeMap::new();
+    for v in &votes {
+        let (Some(left), Some(right)) = (
+            ratings.get(v.left_path.as_str()),
+            ratings.get(v.right_path.as_str()),
+        ) else {
+            continue;
+        };
+        let (winner, loser) = if v.vote > 0 {
+            (left, right)
+        } else {
+            (right, left)
+        };
+        let tally = by_confidence.entry(v.confidence.as_deref()).or_default();
+        tally.votes += 1;
+        if winner < loser {
+            tally.upsets += 1;
+        }
+    }
+
+    by_confidence
+        .into_iter()
+        .map(|(confidence, tally)| Ok((confidence.map(str::parse).transpose()?, tally)))
+        .collect()
+}
@@ -0,0 +1,80 @@
+use std::borrow::BorrowMut;
+
+use anyhow::Result;
+use sqlx::{query, SqliteConnection};
+
+use crate::audit::{self, Change};
+
+/// The value of a custom field as stored by `prio set`, front matter not
+/// included.
+pub async fn stored(conn: &mut SqliteConnection, path: &str, name: &str) -> Result<Option<String>> {
+    let value = query!(
+        "SELECT value FROM entry_fields WHERE path = ?1 AND name = ?2",
+        path,
+        name
+    )
+    .fetch_optional(conn)
+    .await?
+    .map(|r| r.value);
+    Ok(value)
+}
+
+/// Stores `value`, which must already be in canonical form, or removes the
+/// field if it is None. Doesn't record anything in the audit log.
+pub async fn store(
+    conn: &mut SqliteConnection,
+    path: &str,
+    name: &str,
+    value: Option<&str>,
+) -> Result<()> {
+    match value {
+        Some(value) => {
+            query!(
+                r#"
+                    INSERT INTO entry_fields (path, name, value)
+                    VALUES (?1, ?2, ?3)
+                    ON CONFLICT (path, name) DO UPDATE SET value = excluded.value
+                "#,
+                path,
+                name,
+                value
+            )
+            .execute(conn)
+            .await?;
+        }
+        None => {
+            query!(
+                "DELETE FROM entry_fields WHERE path = ?1 AND name = ?2",
+                path,
+                name
+            )
+            .execute(conn)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Sets or removes a field and records it in the audit log.
+pub async fn set(
+    conn: &mut SqliteConnection,
+    path: &str,
+    name: &str,
+    value: Option<&str>,
+    actor: &str,
+) -> Result<()> {
+    let from = stored(conn, path, name).await?;
+    if from.as_deref() == value {
+        return Ok(());
+    }
+    store(conn, path, name, value).await?;
+
+    let change = Change::Field {
+        path: path.to_string(),
+        name: name.to_string(),
+        from,
+        to: value.map(str::to_string),
+    };
+    audit::record(conn.borrow_mut(), &change, actor).await?;
+    Ok(())
+}
@@ -0,0 +1,163 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::config::Config;
+use crate::File;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Lt,
+    Le,
+    Eq,
+    Ne,
+    Ge,
+    Gt,
+}
+
+impl Op {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Op::Lt => ordering.is_lt(),
+            Op::Le => ordering.is_le(),
+            Op::Eq => ordering.is_eq(),
+            Op::Ne => ordering.is_ne(),
+            Op::Ge => ordering.is_ge(),
+            Op::Gt => ordering.is_gt(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Field {
+    Words,
+    Minutes,
+    Rating,
+    Deviation,
+    Lang,
+    Root,
+    /// One of Config::fields, checked by Filter::check as the config isn't
+    /// known while parsing.
+    Custom(String),
+}
+
+impl FromStr for Field {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "words" => Field::Words,
+            "minutes" => Field::Minutes,
+            "rating" => Field::Rating,
+            "deviation" => Field::Deviation,
+            "lang" => Field::Lang,
+            "root" => Field::Root,
+            "" => bail!("filter has no field"),
+            _ => Field::Custom(s.to_string()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(f64),
+    Text(String),
+}
+
+/// A condition like `words>500`, `minutes<=2`, `lang=nld` or `root=work` on
+/// the metadata of an entry. The main entries directory is `root=`. Custom
+/// fields can be compared with any operator, as numbers if both sides are
+/// and as text otherwise, which also works for dates.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    field: Field,
+    op: Op,
+    value: Value,
+}
+
+impl FromStr for Filter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // Two character operators first, so `<=` isn't read as `<`.
+        const OPS: [(&str, Op); 7] = [
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("!=", Op::Ne),
+            ("==", Op::Eq),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+            ("=", Op::Eq),
+        ];
+
+        let (i, op, len) = OPS
+            .iter()
+            .filter_map(|(token, op)| s.find(token).map(|i| (i, *op, token.len())))
+            .min_by_key(|(i, _, _)| *i)
+            .ok_or_else(|| anyhow!("filter {:?} has no operator", s))?;
+
+        let field = s[..i].trim().parse()?;
+        let value = s[i + len..].trim();
+        let value = match field {
+            Field::Custom(_) => Value::Text(value.to_string()),
+            Field::Lang | Field::Root if matches!(op, Op::Eq | Op::Ne) => {
+                Value::Text(value.to_string())
+            }
+            Field::Lang | Field::Root => bail!("text can only be compared with = or != in {:?}", s),
+            _ => Value::Number(
+                value
+                    .parse()
+                    .map_err(|_| anyhow!("invalid value {:?} in filter {:?}", value, s))?,
+            ),
+        };
+
+        Ok(Filter { field, op, value })
+    }
+}
+
+impl Filter {
+    /// Fails if the filter is on a custom field that isn't in the config.
+    pub fn check(&self, config: &Config) -> Result<()> {
+        if let Field::Custom(name) = &self.field {
+            if !config.fields.contains_key(name) {
+                bail!(
+                    "unknown filter field {:?}, expected words, minutes, rating, deviation, lang, root or a field from the config",
+                    name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn matches(&self, item: &File) -> bool {
+        let value = match &self.field {
+            Field::Words => Value::Number(item.words() as f64),
+            Field::Minutes => Value::Number(item.reading_minutes() as f64),
+            Field::Rating => Value::Number(item.rating.rating),
+            Field::Deviation => Value::Number(item.rating.deviation),
+            // Entries without a detected language never equal anything.
+            Field::Lang => match &item.lang {
+                Some(lang) => Value::Text(lang.clone()),
+                None => return self.op == Op::Ne,
+            },
+            Field::Root => Value::Text(item.root.clone()),
+            Field::Custom(name) => match item.fields.get(name) {
+                Some(value) => Value::Text(value.clone()),
+                None => return self.op == Op::Ne,
+            },
+        };
+
+        let ordering = match (&value, &self.value) {
+            (Value::Number(value), Value::Number(expected)) => value.partial_cmp(expected),
+            (Value::Text(value), Value::Text(expected)) => {
+                match (value.parse::<f64>(), expected.parse::<f64>()) {
+                    (Ok(value), Ok(expected)) => value.partial_cmp(&expected),
+                    _ => Some(value.cmp(expected)),
+                }
+            }
+            _ => None,
+        };
+        ordering.is_some_and(|ordering| self.op.holds(ordering))
+    }
+}
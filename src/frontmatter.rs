@@ -0,0 +1,72 @@
+use serde_yaml::{Mapping, Value};
+
+/// Splits an optional YAML front matter block, delimited by `---` lines at the
+/// very start of the file, from the rest of the content. Front matter that
+/// isn't a valid YAML mapping is treated as part of the body.
+pub fn split(content: &str) -> (Option<Mapping>, &str) {
+    let Some(rest) = content.strip_prefix("---\n") else {
+        return (None, content);
+    };
+
+    let (yaml, body) = if let Some(yaml) = rest.strip_prefix("---\n") {
+        ("", yaml)
+    } else if let Some(end) = rest.find("\n---\n") {
+        (&rest[..end], &rest[end + 5..])
+    } else if let Some(yaml) = rest.strip_suffix("\n---") {
+        (yaml, "")
+    } else {
+        return (None, content);
+    };
+
+    match serde_yaml::from_str::<Value>(yaml) {
+        Ok(Value::Mapping(m)) => (Some(m), body),
+        Ok(Value::Null) => (Some(Mapping::new()), body),
+        _ => (None, content),
+    }
+}
+
+/// Puts `front_matter` in front of `body`, the inverse of split. Comments and
+/// formatting in the original YAML are lost.
+pub fn join(front_matter: &Mapping, body: &str) -> Result<String, serde_yaml::Error> {
+    Ok(format!(
+        "---\n{}---\n{}",
+        serde_yaml::to_string(front_matter)?,
+        body
+    ))
+}
+
+/// Returns the value of `key` if it is a string, number or boolean.
+pub fn scalar(front_matter: &Mapping, key: &str) -> Option<String> {
+    match front_matter.get(key)? {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// The `tags` field, see list.
+pub fn tags(front_matter: &Mapping) -> Vec<String> {
+    list(front_matter, "tags")
+}
+
+/// The value of `key` as either a list or a string of comma or whitespace
+/// separated values.
+pub fn list(front_matter: &Mapping, key: &str) -> Vec<String> {
+    match front_matter.get(key) {
+        Some(Value::Sequence(tags)) => tags
+            .iter()
+            .filter_map(|tag| match tag {
+                Value::String(s) => Some(s.clone()),
+                Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            })
+            .collect(),
+        Some(Value::String(s)) => s
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect(),
+        _ => vec![],
+    }
+}
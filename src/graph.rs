@@ -0,0 +1,85 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use sqlx::{query, SqliteConnection};
+
+use crate::util::path_str;
+use crate::File;
+
+/// Number of votes between two entries, `winner` and `loser` being
+/// interchangeable for draws.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Edge {
+    pub winner: String,
+    pub loser: String,
+    pub draw: bool,
+}
+
+pub async fn vote_edges(conn: &mut SqliteConnection) -> Result<BTreeMap<Edge, i64>> {
+    let rows = query!(
+        r#"
+            SELECT
+                left_path AS "left_path!: String",
+                right_path AS "right_path!: String",
+                vote AS "vote!: i64"
+            FROM resolved_votes
+        "#
+    )
+    .fetch_all(conn)
+    .await?;
+
+    let mut edges = BTreeMap::new();
+    for r in rows {
+        let (winner, loser) = if r.vote < 0 {
+            (r.right_path, r.left_path)
+        } else {
+            (r.left_path, r.right_path)
+        };
+        let edge = Edge {
+            winner,
+            loser,
+            draw: r.vote == 0,
+        };
+        *edges.entry(edge).or_insert(0) += 1;
+    }
+    Ok(edges)
+}
+
+fn find(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// Groups the items into the connected components of the vote graph, largest
+/// first. Votes involving other entries connect the items as well.
+pub fn components<'a>(items: &[&'a File], edges: &BTreeMap<Edge, i64>) -> Vec<Vec<&'a File>> {
+    // items come first, so their index in items is their node
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    let voted = edges
+        .keys()
+        .flat_map(|e| [e.winner.as_str(), e.loser.as_str()]);
+    for path in items.iter().map(|f| path_str(&f.path)).chain(voted) {
+        let len = index.len();
+        index.entry(path).or_insert(len);
+    }
+
+    let mut parents: Vec<usize> = (0..index.len()).collect();
+    for edge in edges.keys() {
+        let a = find(&mut parents, index[edge.winner.as_str()]);
+        let b = find(&mut parents, index[edge.loser.as_str()]);
+        parents[a] = b;
+    }
+
+    let mut components: HashMap<usize, Vec<&File>> = HashMap::new();
+    for (i, item) in items.iter().enumerate() {
+        let root = find(&mut parents, i);
+        components.entry(root).or_default().push(item);
+    }
+
+    let mut res: Vec<_> = components.into_values().collect();
+    res.sort_by_key(|c| std::cmp::Reverse(c.len()));
+    res
+}
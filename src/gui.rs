@@ -0,0 +1,288 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use eframe::egui;
+use sqlx::SqliteConnection;
+use tokio::runtime::Runtime;
+
+use crate::config::Config;
+use crate::exclude::{self, Exclusions};
+use crate::images;
+use crate::render::{self, Block, Span};
+use crate::sample::take_n;
+use crate::{competition, describe_move, get_db_files, ranking, File};
+
+#[derive(PartialEq)]
+enum View {
+    Ranking,
+    Vote,
+}
+
+/// The database is only touched from the UI thread, blocking on the runtime
+/// for every query. That's fine for the handful of queries a click causes.
+struct App<'a> {
+    rt: &'a Runtime,
+    conn: &'a mut SqliteConnection,
+    config: &'a Config,
+
+    items: Vec<File>,
+    view: View,
+    selected: Option<PathBuf>,
+    pair: Vec<File>,
+    /// How the entries of the last vote moved, see describe_move.
+    moves: Vec<String>,
+    error: Option<String>,
+}
+
+impl App<'_> {
+    fn reload(&mut self) -> Result<()> {
+        self.items = self.rt.block_on(get_db_files(self.conn, self.config))?;
+        Ok(())
+    }
+
+    fn next_pair(&mut self) {
+        let active: VecDeque<_> = self
+            .items
+            .iter()
+            .filter(|f| f.is_active() && !exclude::never_compared(self.config, f))
+            .cloned()
+            .collect();
+        let exclusions = Exclusions::new(self.config, &self.items);
+        self.pair = if active.len() < 2 {
+            vec![]
+        } else {
+            // Nothing to vote on if there's no pair left to compare.
+            exclusions
+                .draw(|| Ok(take_n(active.clone(), 2, &self.config.recent_edits)))
+                .unwrap_or_default()
+        };
+    }
+
+    /// Where the entry at `path` is in the ranking, 1 being the highest.
+    fn rank(&self, path: &Path) -> Option<usize> {
+        ranking(&self.items)
+            .iter()
+            .position(|f| f.path == path)
+            .map(|i| i + 1)
+    }
+
+    fn vote(&mut self, selection: usize) -> Result<()> {
+        let paths = [
+            self.pair[selection].path.clone(),
+            self.pair[1 - selection].path.clone(),
+        ];
+        let before = paths.clone().map(|path| self.rank(&path));
+        self.rt
+            .block_on(competition(self.conn, &paths[0], &paths[1], None))?;
+        self.reload()?;
+
+        self.moves = paths
+            .iter()
+            .zip(before)
+            .filter_map(|(path, before)| {
+                let item = self.items.iter().find(|f| &f.path == path)?;
+                describe_move(item, before?, self.rank(path)?)
+            })
+            .collect();
+        self.next_pair();
+        Ok(())
+    }
+
+    fn ranking(&mut self, ui: &mut egui::Ui) {
+        egui::Panel::left("ranking").resizable(true).show(ui, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for (i, item) in ranking(&self.items).into_iter().enumerate() {
+                    let selected = self.selected.as_ref() == Some(&item.path);
+                    let label = format!("{:>3}. {}", i + 1, item.title);
+                    if ui.selectable_label(selected, label).clicked() {
+                        self.selected = Some(item.path.clone());
+                    }
+                }
+            });
+        });
+
+        egui::CentralPanel::default_margins().show(ui, |ui| {
+            let selected = self
+                .selected
+                .as_ref()
+                .and_then(|path| self.items.iter().find(|f| &f.path == path));
+            match selected {
+                Some(item) => card(ui, self.config, item),
+                None => {
+                    ui.label("Select an entry to preview it.");
+                }
+            }
+        });
+    }
+
+    fn voting(&mut self, ui: &mut egui::Ui) -> Result<()> {
+        if self.pair.len() < 2 {
+            ui.label("Need at least two active entries to vote.");
+            return Ok(());
+        }
+
+        ui.label("Which one is more important?");
+        let mut selection = None;
+        ui.columns(2, |columns| {
+            for (i, column) in columns.iter_mut().enumerate() {
+                column.push_id(i, |ui| {
+                    if ui.button("This one").clicked() {
+                        selection = Some(i);
+                    }
+                    card(ui, self.config, &self.pair[i]);
+                });
+            }
+        });
+        if ui.button("Skip").clicked() {
+            self.moves.clear();
+            self.next_pair();
+        }
+        for report in &self.moves {
+            ui.label(egui::RichText::new(report).weak());
+        }
+
+        match selection {
+            Some(selection) => self.vote(selection),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Title, rating and content of an entry.
+fn card(ui: &mut egui::Ui, config: &Config, item: &File) {
+    egui::Frame::group(ui.style()).show(ui, |ui| {
+        ui.heading(&item.title);
+        ui.label(format!(
+            "{:.0} ± {:.0}, {} words",
+            item.rating.rating,
+            item.rating.deviation,
+            item.words()
+        ));
+        ui.separator();
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            let content = String::from_utf8_lossy(&item.last_content().content);
+            if render::is_markdown(&item.path) {
+                markdown(ui, &content);
+            } else {
+                ui.label(content);
+            }
+            // Drawing them would take an image loader for egui, the names
+            // at least say they're there.
+            for path in images::referenced(config, item) {
+                ui.label(egui::RichText::new(format!("image: {}", path.display())).weak());
+            }
+        });
+    });
+}
+
+fn markdown(ui: &mut egui::Ui, content: &str) {
+    let text = |ui: &mut egui::Ui, text: &str| {
+        for span in render::spans(text) {
+            ui.label(match span {
+                Span::Plain(s) => egui::RichText::new(s),
+                Span::Strong(s) => egui::RichText::new(s).strong(),
+                Span::Emphasis(s) => egui::RichText::new(s).italics(),
+                Span::Code(s) => egui::RichText::new(s).code(),
+            });
+        }
+    };
+
+    for block in render::blocks(content) {
+        match block {
+            Block::FrontMatter(line) => {
+                ui.label(egui::RichText::new(line).weak().monospace());
+            }
+            Block::Heading(1, line) => {
+                ui.heading(line);
+            }
+            Block::Heading(_, line) => {
+                ui.label(egui::RichText::new(line).strong());
+            }
+            Block::Text(line) => {
+                ui.horizontal_wrapped(|ui| {
+                    ui.spacing_mut().item_spacing.x = 0.0;
+                    text(ui, line);
+                });
+            }
+            Block::Item(indent, line) => {
+                ui.horizontal_wrapped(|ui| {
+                    ui.spacing_mut().item_spacing.x = 0.0;
+                    ui.label(format!("{}• ", " ".repeat(indent)));
+                    text(ui, line);
+                });
+            }
+            Block::Quote(line) => {
+                ui.label(egui::RichText::new(line).italics().weak());
+            }
+            Block::Code(line) => {
+                ui.label(egui::RichText::new(line).code());
+            }
+            Block::Rule => {
+                ui.separator();
+            }
+            Block::Blank => ui.add_space(4.0),
+        }
+    }
+}
+
+impl eframe::App for App<'_> {
+    fn ui(&mut self, ui: &mut egui::Ui, _frame: &mut eframe::Frame) {
+        egui::Panel::top("views").show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.view, View::Ranking, "Ranking");
+                ui.selectable_value(&mut self.view, View::Vote, "Vote");
+            });
+        });
+
+        if let Some(error) = &self.error {
+            let mut dismissed = false;
+            egui::Panel::bottom("error").show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.colored_label(egui::Color32::RED, error);
+                    dismissed = ui.button("Dismiss").clicked();
+                });
+            });
+            if dismissed {
+                self.error = None;
+            }
+        }
+
+        match self.view {
+            View::Ranking => self.ranking(ui),
+            View::Vote => {
+                let res = egui::CentralPanel::default_margins()
+                    .show(ui, |ui| self.voting(ui))
+                    .inner;
+                if let Err(e) = res {
+                    self.error = Some(format!("{:#}", e));
+                }
+            }
+        }
+    }
+}
+
+/// Opens the window and blocks until it is closed. Has to run on the main
+/// thread, outside of the runtime.
+pub fn run(rt: &Runtime, conn: &mut SqliteConnection, config: &Config) -> Result<()> {
+    let mut app = App {
+        rt,
+        conn,
+        config,
+        items: vec![],
+        view: View::Ranking,
+        selected: None,
+        pair: vec![],
+        moves: vec![],
+        error: None,
+    };
+    app.reload()?;
+    app.next_pair();
+
+    eframe::run_native(
+        "prio",
+        eframe::NativeOptions::default(),
+        Box::new(|_| Ok(Box::new(app))),
+    )
+    .map_err(|e| anyhow!("gui: {}", e))
+}
@@ -0,0 +1,221 @@
+//! Local images referenced by entries, shown inline on terminals that can
+//! with the images feature, see Config::image_protocol.
+
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, ImageProtocol};
+use crate::render::{self, Block};
+use crate::util::path_str;
+use crate::{frontmatter, File};
+
+/// Targets of `![alt](target)` and `<img src="target">` in `text`, in order.
+fn targets(text: &str) -> Vec<&str> {
+    let mut targets = vec![];
+    let mut rest = text;
+    while let Some(i) = rest.find("![") {
+        rest = &rest[i + 2..];
+        let Some(target) = rest
+            .find("](")
+            .map(|j| &rest[j + 2..])
+            .and_then(|r| r.find(')').map(|k| &r[..k]))
+        else {
+            break;
+        };
+        // Drop an optional title, as in ![alt](path "title").
+        targets.push(target.split_whitespace().next().unwrap_or_default());
+    }
+
+    let mut rest = text;
+    while let Some(i) = rest.find("<img") {
+        rest = &rest[i + 4..];
+        let Some(src) = rest.find("src=").map(|j| &rest[j + 4..]) else {
+            continue;
+        };
+        let Some(quote) = src.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            continue;
+        };
+        if let Some(end) = src[1..].find(quote) {
+            targets.push(&src[1..end + 1]);
+        }
+    }
+    targets
+}
+
+/// Existing local files the latest revision of `item` shows as images,
+/// relative paths being relative to the entry's file. Images inside code
+/// blocks and on the web are left out.
+pub fn referenced(config: &Config, item: &File) -> Vec<PathBuf> {
+    let content = String::from_utf8_lossy(&item.last_content().content);
+    let body = frontmatter::split(&content).1;
+    let full_path = config.locate(path_str(&item.path));
+    let dir = full_path.parent().unwrap_or(Path::new("."));
+
+    let mut images: Vec<PathBuf> = vec![];
+    for block in render::blocks(body) {
+        let text = match block {
+            Block::Text(text) | Block::Item(_, text) | Block::Quote(text) => text,
+            _ => continue,
+        };
+        for target in targets(text) {
+            if target.is_empty() || target.contains("://") || target.starts_with("data:") {
+                continue;
+            }
+            let path = dir.join(target);
+            if path.is_file() && !images.contains(&path) {
+                images.push(path);
+            }
+        }
+    }
+    images
+}
+
+/// The protocol to show images with, from the config or else from what the
+/// terminal says it is. Sixel support can't be detected, so it has to be
+/// configured.
+pub fn protocol(config: &Config) -> Option<ImageProtocol> {
+    if let Some(protocol) = config.image_protocol {
+        return Some(protocol);
+    }
+    let term = std::env::var("TERM").unwrap_or_default();
+    let program = std::env::var("TERM_PROGRAM").unwrap_or_default();
+    if term.contains("kitty") || std::env::var_os("KITTY_WINDOW_ID").is_some() {
+        Some(ImageProtocol::Kitty)
+    } else if program == "iTerm.app" || program == "WezTerm" {
+        Some(ImageProtocol::Iterm)
+    } else {
+        None
+    }
+}
+
+#[cfg(feature = "images")]
+mod display {
+    use std::io::Write;
+    use std::path::Path;
+
+    use anyhow::{bail, Result};
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+
+    use crate::config::ImageProtocol;
+
+    /// Widest an image is drawn with sixel, in pixels.
+    const MAX_WIDTH: u32 = 640;
+
+    fn is_png(bytes: &[u8]) -> bool {
+        bytes.starts_with(b"\x89PNG\r\n\x1a\n")
+    }
+
+    /// Writes the image at `path` to `out` using `protocol`. Only PNG images
+    /// can be sent with kitty and sixel, iTerm takes any format.
+    pub fn write(out: &mut impl Write, protocol: ImageProtocol, path: &Path) -> Result<()> {
+        let bytes = std::fs::read(path)?;
+        match protocol {
+            ImageProtocol::Kitty => {
+                if !is_png(&bytes) {
+                    bail!("not a PNG image");
+                }
+                let data = STANDARD.encode(&bytes);
+                let chunks: Vec<_> = data.as_bytes().chunks(4096).collect();
+                for (i, chunk) in chunks.iter().enumerate() {
+                    let more = (i + 1 < chunks.len()) as u8;
+                    match i {
+                        0 => write!(out, "\x1b_Gf=100,a=T,m={};", more)?,
+                        _ => write!(out, "\x1b_Gm={};", more)?,
+                    }
+                    out.write_all(chunk)?;
+                    write!(out, "\x1b\\")?;
+                }
+            }
+            ImageProtocol::Iterm => {
+                write!(
+                    out,
+                    "\x1b]1337;File=inline=1;size={}:{}\x07",
+                    bytes.len(),
+                    STANDARD.encode(&bytes)
+                )?;
+            }
+            ImageProtocol::Sixel => {
+                if !is_png(&bytes) {
+                    bail!("not a PNG image");
+                }
+                let image = image::load_from_memory_with_format(&bytes, image::ImageFormat::Png)?;
+                let image = match image.width() > MAX_WIDTH {
+                    true => image.thumbnail(MAX_WIDTH, u32::MAX),
+                    false => image,
+                };
+                out.write_all(&sixel(&image.to_rgb8()))?;
+            }
+        }
+        writeln!(out)?;
+        Ok(())
+    }
+
+    /// Encodes the image as sixels with a fixed palette of 6 levels of every
+    /// primary colour, which is crude but needs no quantization.
+    fn sixel(image: &image::RgbImage) -> Vec<u8> {
+        let level = |c: u8| (c as usize * 5 + 127) / 255;
+        let index = |p: &image::Rgb<u8>| level(p[0]) * 36 + level(p[1]) * 6 + level(p[2]);
+
+        let mut out = b"\x1bPq".to_vec();
+        for i in 0..216 {
+            let (r, g, b) = (i / 36, i / 6 % 6, i % 6);
+            out.extend(format!("#{};2;{};{};{}", i, r * 20, g * 20, b * 20).bytes());
+        }
+
+        let (width, height) = image.dimensions();
+        for band in (0..height).step_by(6) {
+            let rows = (height - band).min(6);
+            let mut colours = vec![];
+            for y in band..band + rows {
+                for x in 0..width {
+                    let colour = index(image.get_pixel(x, y));
+                    if !colours.contains(&colour) {
+                        colours.push(colour);
+                    }
+                }
+            }
+            for (n, &colour) in colours.iter().enumerate() {
+                if n > 0 {
+                    // Back to the start of the band for the next colour.
+                    out.push(b'$');
+                }
+                out.extend(format!("#{}", colour).bytes());
+                for x in 0..width {
+                    let bits = (0..rows)
+                        .filter(|&dy| index(image.get_pixel(x, band + dy)) == colour)
+                        .fold(0u8, |bits, dy| bits | 1 << dy);
+                    out.push(b'?' + bits);
+                }
+            }
+            out.push(b'-');
+        }
+        out.extend(b"\x1b\\");
+        out
+    }
+}
+
+/// Shows the images `item` references below it, inline if the terminal can
+/// and prio was built with the images feature, or else lists their files.
+pub fn show(config: &Config, item: &File) -> anyhow::Result<()> {
+    let images = referenced(config, item);
+    if images.is_empty() {
+        return Ok(());
+    }
+
+    #[cfg(feature = "images")]
+    if let Some(protocol) = protocol(config) {
+        let mut out = std::io::stdout().lock();
+        for path in &images {
+            if let Err(e) = display::write(&mut out, protocol, path) {
+                println!("{}: {:#}", path.display(), e);
+            }
+        }
+        return Ok(());
+    }
+
+    println!();
+    for path in &images {
+        println!("image: {}", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,77 @@
+//! Reading pairwise judgments made in other tools, see prio import-votes.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Deserialize;
+
+use crate::util::parse_since;
+
+/// A vote as found in a CSV file with a `left,right,vote,at` header or a JSON
+/// array of objects with those fields. Like in entry_votes a positive vote
+/// means left won, negative that right won and 0 is a draw, it defaults to 1.
+/// `at` is either a unix timestamp or a local date and defaults to now.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImportedVote {
+    pub left: String,
+    pub right: String,
+    #[serde(default = "default_vote")]
+    pub vote: i64,
+    #[serde(default)]
+    at: Option<String>,
+}
+
+fn default_vote() -> i64 {
+    1
+}
+
+impl ImportedVote {
+    pub fn at(&self) -> Result<DateTime<Utc>> {
+        let Some(at) = &self.at else {
+            return Ok(Utc::now());
+        };
+        if let Ok(ts) = at.parse() {
+            return Utc
+                .timestamp_opt(ts, 0)
+                .single()
+                .ok_or_else(|| anyhow!("invalid time {}", ts));
+        }
+        parse_since(at)
+    }
+}
+
+pub fn read_votes(path: &Path) -> Result<Vec<ImportedVote>> {
+    let context = || format!("reading {}", path.display());
+
+    if path.extension().is_some_and(|ext| ext == "json") {
+        let s = std::fs::read_to_string(path).with_context(context)?;
+        return serde_json::from_str(&s).with_context(context);
+    }
+
+    csv::Reader::from_path(path)
+        .with_context(context)?
+        .deserialize()
+        .collect::<Result<_, _>>()
+        .with_context(context)
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Mapping {
+    name: String,
+    path: String,
+}
+
+/// Reads a CSV file with a `name,path` header, mapping the names used in the
+/// imported votes to paths of entries.
+pub fn read_mapping(path: &Path) -> Result<HashMap<String, String>> {
+    let context = || format!("reading {}", path.display());
+
+    csv::Reader::from_path(path)
+        .with_context(context)?
+        .deserialize()
+        .map(|r| r.map(|m: Mapping| (m.name, m.path)))
+        .collect::<Result<_, _>>()
+        .with_context(context)
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::config::Config;
+
+/// Name of the file declaring what entries in a directory and everything
+/// beneath it inherit.
+pub const FILE_NAME: &str = ".prio.toml";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct DirConfig {
+    /// Added to the rating of every entry beneath the directory when ranking.
+    offset: f64,
+    tags: Vec<String>,
+}
+
+/// What an entry inherits from the directories it is in, nested directories
+/// adding to their parents.
+#[derive(Debug, Clone, Default)]
+pub struct Inherited {
+    pub offset: f64,
+    pub tags: Vec<String>,
+}
+
+/// Reads the .prio.toml files, each only once.
+pub struct Loader<'a> {
+    config: &'a Config,
+    dirs: HashMap<PathBuf, Option<DirConfig>>,
+}
+
+impl<'a> Loader<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        Loader {
+            config,
+            dirs: HashMap::new(),
+        }
+    }
+
+    fn dir(&mut self, dir: &Path) -> Result<Option<&DirConfig>> {
+        if !self.dirs.contains_key(dir) {
+            let path = dir.join(FILE_NAME);
+            let parsed = match std::fs::read_to_string(&path) {
+                Ok(s) => Some(
+                    toml::from_str(&s).with_context(|| format!("parsing {}", path.display()))?,
+                ),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+            };
+            self.dirs.insert(dir.to_path_buf(), parsed);
+        }
+        Ok(self.dirs[dir].as_ref())
+    }
+
+    /// What the entry stored as `path` inherits, from its entries directory
+    /// down to the directory it is in.
+    pub fn inherited(&mut self, path: &str) -> Result<Inherited> {
+        let (root, relative) = self.config.split_root(path);
+        let root_dir = self
+            .config
+            .roots()
+            .into_iter()
+            .find(|(name, _)| *name == root)
+            .map(|(_, dir)| dir.to_path_buf())
+            .expect("split_root only returns configured roots");
+
+        let mut res = Inherited::default();
+        let mut dir = root_dir;
+        let parents: Vec<_> = Path::new(relative)
+            .parent()
+            .into_iter()
+            .flat_map(|p| p.iter())
+            .collect();
+        for i in 0..=parents.len() {
+            if i > 0 {
+                dir.push(parents[i - 1]);
+            }
+            if let Some(dir_config) = self.dir(&dir)? {
+                res.offset += dir_config.offset;
+                for tag in &dir_config.tags {
+                    if !res.tags.contains(tag) {
+                        res.tags.push(tag.clone());
+                    }
+                }
+            }
+        }
+        Ok(res)
+    }
+}
@@ -0,0 +1,23 @@
+use anyhow::{anyhow, Result};
+use whatlang::{Detector, Lang};
+
+use crate::config::Config;
+
+/// A detector restricted to the configured languages, if any.
+pub fn detector(config: &Config) -> Result<Detector> {
+    if config.languages.is_empty() {
+        return Ok(Detector::new());
+    }
+
+    let langs = config
+        .languages
+        .iter()
+        .map(|code| Lang::from_code(code).ok_or_else(|| anyhow!("unknown language {:?}", code)))
+        .collect::<Result<_>>()?;
+    Ok(Detector::with_allowlist(langs))
+}
+
+/// The ISO 639-3 code of the language `text` is most likely written in.
+pub fn detect(detector: &Detector, text: &str) -> Option<&'static str> {
+    detector.detect_lang(text).map(|lang| lang.code())
+}
@@ -0,0 +1,1095 @@
+//! The ranking engine behind the prio command: syncing entries directories
+//! into the database with update_files, loading entries with their ratings with
+//! get_db_files and recording votes with competition. Sampling pairs to vote on
+//! lives in sample and computing ratings in rating.
+//!
+//! Open the database with connect, which also brings its schema up to date.
+
+pub mod audit;
+pub mod backup;
+pub mod cache;
+pub mod chart;
+pub mod config;
+pub mod context;
+pub mod diff;
+#[cfg(any(feature = "export", feature = "import"))]
+pub mod dump;
+pub mod error;
+pub mod exclude;
+pub mod experiment;
+#[cfg(feature = "export")]
+pub mod export;
+pub mod fatigue;
+pub mod fields;
+pub mod filter;
+pub mod frontmatter;
+pub mod graph;
+#[cfg(feature = "gui")]
+pub mod gui;
+pub mod images;
+#[cfg(feature = "import")]
+pub mod import;
+pub mod inherit;
+pub mod lang;
+pub mod merge;
+pub mod preset;
+pub mod quarantine;
+pub mod rating;
+pub mod render;
+pub mod resurface;
+pub mod sample;
+pub mod schema;
+pub mod speak;
+pub mod stats;
+pub mod status;
+pub mod table;
+pub mod title;
+pub mod undo;
+pub mod util;
+pub mod votes;
+pub mod web;
+
+use std::borrow::BorrowMut;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use prio_core::Rating;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, Connection, SqliteConnection};
+use tokio::fs;
+use walkdir::WalkDir;
+
+use audit::Change;
+use config::{Config, TieBreaker};
+use status::{set_status, Status, StatusChange};
+use title::Title;
+use util::{actor, content_hash, mentions, path_str, render_template};
+
+/// Records a vote for `winner` over `loser` cast now by the current user.
+pub async fn competition(
+    conn: &mut SqliteConnection,
+    winner: &Path,
+    loser: &Path,
+    criterion: Option<&str>,
+) -> Result<()> {
+    assert!(winner != loser);
+
+    let vote = Vote {
+        left_path: winner.to_path_buf(),
+        right_path: loser.to_path_buf(),
+        vote: 1,
+        at: Utc::now(),
+        criterion: criterion.map(str::to_string),
+        confidence: None,
+    };
+    insert_vote(conn, &vote, &actor()).await
+}
+
+/// Records a vote and adds it to the audit log.
+pub async fn insert_vote(conn: &mut SqliteConnection, vote: &Vote, actor: &str) -> Result<()> {
+    let (left, right) = (path_str(&vote.left_path), path_str(&vote.right_path));
+    let ts = vote.at.timestamp();
+    let criterion = vote.criterion.as_deref();
+    let confidence = vote.confidence.map(|c| c.as_str());
+
+    let vote_id = query!(
+        r#"
+            INSERT INTO entry_votes
+                (left_path, right_path, vote, at, criterion, confidence)
+            VALUES
+                (?1, ?2, ?3, ?4, ?5, ?6)
+        "#,
+        left,
+        right,
+        vote.vote,
+        ts,
+        criterion,
+        confidence
+    )
+    .execute(conn.borrow_mut())
+    .await?
+    .last_insert_rowid();
+
+    let change = Change::Vote {
+        vote_id,
+        winner: left.to_string(),
+        loser: right.to_string(),
+        vote: vote.vote,
+        at: ts,
+        criterion: vote.criterion.clone(),
+        confidence: vote.confidence,
+    };
+    audit::record(conn, &change, actor).await?;
+    Ok(())
+}
+
+/// One revision of an entry.
+#[derive(Debug, Clone)]
+pub struct FileContent {
+    pub content: Vec<u8>,
+    pub at: DateTime<Utc>,
+}
+
+/// How sure the user was of a vote, asked after each vote when
+/// Config::ask_confidence is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Confidence {
+    Sure,
+    Leaning,
+}
+
+impl Confidence {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Confidence::Sure => "sure",
+            Confidence::Leaning => "leaning",
+        }
+    }
+}
+
+impl FromStr for Confidence {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "sure" => Ok(Confidence::Sure),
+            "leaning" => Ok(Confidence::Leaning),
+            _ => Err(error::invalid(format!("unknown confidence {:?}", s))),
+        }
+    }
+}
+
+/// A vote between two entries as stored, `vote` being positive if the left
+/// one won, negative if the right one won and 0 for a draw.
+#[derive(Debug, Clone)]
+pub struct Vote {
+    pub left_path: PathBuf,
+    pub right_path: PathBuf,
+    pub vote: i64,
+    pub at: DateTime<Utc>,
+    /// What the vote was judged on, None for overall importance.
+    pub criterion: Option<String>,
+    /// None for votes cast without being asked, which count as sure.
+    pub confidence: Option<Confidence>,
+}
+
+/// An entry with its history and its rating, as loaded by get_db_files.
+#[derive(Debug, Clone)]
+pub struct File {
+    pub id: i64,
+    pub path: PathBuf,
+    /// Name of the entries directory, see Config::roots
+    pub root: String,
+    pub title: String,
+    /// The `lang` front matter field, or else the detected language.
+    pub lang: Option<String>,
+    /// Values of the custom fields in Config::fields.
+    pub fields: BTreeMap<String, String>,
+    /// From the .prio.toml files in the directories the entry is in.
+    pub inherited: inherit::Inherited,
+    pub file_contents: Vec<FileContent>,
+    pub status_history: Vec<StatusChange>,
+    pub last_vote_at: Option<DateTime<Utc>>,
+    /// Number of counted votes the entry took part in.
+    pub votes: usize,
+    pub rating: Rating,
+}
+
+/// The `display` front matter field if there is one, otherwise the
+/// configured title template or else the first line after the front matter.
+/// Templates can refer to front matter fields and to `{first_line}`, `{stem}`
+/// and `{path}`.
+pub fn make_title(config: &Config, path: &Path, content: &str) -> String {
+    let (front_matter, body) = frontmatter::split(content);
+    let first_line = body.lines().next().unwrap_or("");
+
+    if let Some(display) = front_matter
+        .as_ref()
+        .and_then(|fm| frontmatter::scalar(fm, "display"))
+    {
+        return display;
+    }
+
+    let Some(template) = &config.title_template else {
+        return first_line.to_string();
+    };
+    render_template(template, |key| match key {
+        "first_line" => Some(first_line.to_string()),
+        "stem" => Some(path.file_stem()?.to_string_lossy().into_owned()),
+        "path" => Some(path_str(path).to_string()),
+        key => frontmatter::scalar(front_matter.as_ref()?, key),
+    })
+}
+
+impl File {
+    pub fn make_title(&self, config: &Config) -> String {
+        let content = String::from_utf8_lossy(&self.last_content().content);
+        make_title(config, &self.path, &content)
+    }
+
+    pub fn last_content(&self) -> &FileContent {
+        self.file_contents
+            .last()
+            .expect("file_contents can't be empty")
+    }
+
+    pub fn detect_lang(&self, detector: &whatlang::Detector) -> Option<String> {
+        let content = String::from_utf8_lossy(&self.last_content().content);
+        let (front_matter, body) = frontmatter::split(&content);
+        front_matter
+            .as_ref()
+            .and_then(|fm| frontmatter::scalar(fm, "lang"))
+            .or_else(|| Some(lang::detect(detector, body)?.to_string()))
+    }
+
+    /// Values of the custom fields set in the front matter of the latest
+    /// revision, leaving out those that don't fit their type.
+    pub fn front_matter_fields(&self, config: &Config) -> Vec<(String, String)> {
+        let content = String::from_utf8_lossy(&self.last_content().content);
+        let Some(front_matter) = frontmatter::split(&content).0 else {
+            return vec![];
+        };
+        config
+            .fields
+            .iter()
+            .filter_map(|(name, ty)| {
+                let value = frontmatter::scalar(&front_matter, name)?;
+                Some((name.clone(), ty.parse(&value).ok()?))
+            })
+            .collect()
+    }
+
+    /// Tags of the latest revision, see frontmatter::tags, along with those
+    /// inherited from its directories.
+    pub fn tags(&self) -> Vec<String> {
+        let content = String::from_utf8_lossy(&self.last_content().content);
+        let mut tags = self.inherited.tags.clone();
+        let own = frontmatter::split(&content)
+            .0
+            .map(|fm| frontmatter::tags(&fm))
+            .unwrap_or_default();
+        for tag in own {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        tags
+    }
+
+    /// People mentioned as `@name` in the latest revision, see util::mentions.
+    pub fn mentions(&self) -> Vec<String> {
+        let content = String::from_utf8_lossy(&self.last_content().content);
+        mentions(frontmatter::split(&content).1)
+    }
+
+    /// Whether the entry mentions `name`, ignoring case.
+    pub fn waits_on(&self, name: &str) -> bool {
+        let name = name.trim_start_matches('@');
+        self.mentions().iter().any(|m| m.eq_ignore_ascii_case(name))
+    }
+
+    /// The decision the entry is one of the alternatives for, from
+    /// `alternative_for` in its front matter. show only lists the best ranked
+    /// alternative of each decision, see prio decide.
+    pub fn alternative_for(&self) -> Option<String> {
+        let content = String::from_utf8_lossy(&self.last_content().content);
+        frontmatter::scalar(&frontmatter::split(&content).0?, "alternative_for")
+    }
+
+    /// The page the entry is about, from `url` in its front matter, see
+    /// prio add-url.
+    pub fn url(&self) -> Option<String> {
+        let content = String::from_utf8_lossy(&self.last_content().content);
+        frontmatter::scalar(&frontmatter::split(&content).0?, "url")
+    }
+
+    /// Whether the page at File::url was gone when prio refresh-urls last
+    /// fetched it, from `http_status` in its front matter.
+    pub fn dead_link(&self) -> bool {
+        let content = String::from_utf8_lossy(&self.last_content().content);
+        frontmatter::split(&content)
+            .0
+            .and_then(|fm| frontmatter::scalar(&fm, "http_status"))
+            .and_then(|status| status.parse().ok())
+            .is_some_and(web::dead)
+    }
+
+    /// The rating plus the offset inherited from its directories, which is
+    /// what the ranking is ordered by.
+    pub fn score(&self) -> f64 {
+        self.rating.rating + self.inherited.offset
+    }
+
+    /// Number of words in the latest revision, excluding front matter.
+    pub fn words(&self) -> usize {
+        let content = String::from_utf8_lossy(&self.last_content().content);
+        frontmatter::split(&content).1.split_whitespace().count()
+    }
+
+    /// Estimated reading time in minutes at 200 words per minute.
+    pub fn reading_minutes(&self) -> usize {
+        self.words().div_ceil(200)
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.file_contents
+            .first()
+            .expect("file_contents can't be empty")
+            .at
+    }
+
+    /// When the entry was last edited, voted on or changed status.
+    pub fn last_activity(&self) -> DateTime<Utc> {
+        let status_at = self.status_history.last().map(|s| s.at);
+        [Some(self.last_content().at), status_at, self.last_vote_at]
+            .into_iter()
+            .flatten()
+            .max()
+            .unwrap()
+    }
+
+    /// Orders `self` after `other` if it ranks higher.
+    pub fn rank_cmp(&self, other: &Self, config: &Config) -> Ordering {
+        let mut res = self.score().total_cmp(&other.score());
+        for tie_breaker in &config.tie_breakers {
+            res = res.then_with(|| match tie_breaker {
+                TieBreaker::Activity => self.last_activity().cmp(&other.last_activity()),
+                TieBreaker::Created => other.created_at().cmp(&self.created_at()),
+                TieBreaker::Path => other.path.cmp(&self.path),
+            });
+        }
+        res.then_with(|| other.path.cmp(&self.path))
+    }
+
+    pub fn status(&self) -> Status {
+        self.status_history
+            .last()
+            .map(|s| s.status)
+            .unwrap_or(Status::Active)
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.status() == Status::Deleted
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.status() == Status::Active
+    }
+}
+
+impl Display for File {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.status() {
+            Status::Active => write!(f, "{} ({})", self.title, path_str(&self.path)),
+            status => write!(f, "{} ({}, {})", self.title, path_str(&self.path), status),
+        }
+    }
+}
+
+// REVIEW
+impl PartialEq for File {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
+impl Eq for File {}
+
+// REVIEW
+impl Hash for File {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.path.hash(state)
+    }
+}
+
+/// All votes that count towards the ratings with this config, in the order
+/// they were cast.
+pub async fn counted_votes(conn: &mut SqliteConnection, config: &Config) -> Result<Vec<Vote>> {
+    let votes = query!(
+        r#"
+            SELECT
+                left_path AS "left_path!: String",
+                right_path AS "right_path!: String",
+                vote AS "vote!: i64",
+                at AS "at!: i64",
+                criterion,
+                confidence
+            FROM resolved_votes
+        "#
+    )
+    .fetch_all(conn)
+    .await?
+    .into_iter()
+    .filter(|r| config.counts_criterion(r.criterion.as_deref()))
+    .map(|r| {
+        Ok(Vote {
+            left_path: PathBuf::from(r.left_path),
+            right_path: PathBuf::from(r.right_path),
+            vote: r.vote,
+            at: Utc.timestamp_opt(r.at, 0).unwrap(),
+            criterion: r.criterion,
+            confidence: r.confidence.map(|c| c.parse()).transpose()?,
+        })
+    })
+    .collect::<Result<_>>()?;
+    Ok(votes)
+}
+
+/// The votes in terms of indices into `items`, with the time they were cast
+/// and how much they count, see Config::vote_weight. Votes on paths that
+/// aren't in `items` are skipped.
+pub fn index_votes(
+    config: &Config,
+    items: &[File],
+    votes: &[Vote],
+) -> Vec<(prio_core::Vote, DateTime<Utc>, f64)> {
+    let index: HashMap<&Path, usize> = items
+        .iter()
+        .enumerate()
+        .map(|(i, f)| (f.path.as_path(), i))
+        .collect();
+    votes
+        .iter()
+        .filter_map(|o| {
+            let vote = prio_core::Vote {
+                left: *index.get(o.left_path.as_path())?,
+                right: *index.get(o.right_path.as_path())?,
+                vote: o.vote,
+            };
+            Some((vote, o.at, config.vote_weight(o.confidence)))
+        })
+        .collect()
+}
+
+/// The deviation bumps for the revisions of `items` that changed enough from
+/// the one before, sorted by time. None without Config::edit_bump.
+pub fn edit_bumps(config: &Config, items: &[File]) -> Vec<rating::Bump> {
+    let Some(policy) = &config.edit_bump else {
+        return vec![];
+    };
+    let mut bumps = vec![];
+    for (i, item) in items.iter().enumerate() {
+        for pair in item.file_contents.windows(2) {
+            let old = String::from_utf8_lossy(&pair[0].content);
+            let new = String::from_utf8_lossy(&pair[1].content);
+            let lines = diff::lines(&old, &new);
+            let changed = lines
+                .iter()
+                .filter(|l| !matches!(l, diff::Line::Same(_)))
+                .count();
+            if !lines.is_empty() && changed as f64 / lines.len() as f64 >= policy.changed {
+                bumps.push(rating::Bump {
+                    item: i,
+                    at: pair[1].at,
+                    deviation: policy.deviation,
+                });
+            }
+        }
+    }
+    bumps.sort_by_key(|b| b.at);
+    bumps
+}
+
+/// Loads all entries, including ones that are no longer active, with their
+/// ratings. Lowest ranked first, see File::rank_cmp, and ranking for the
+/// active entries highest first.
+pub async fn get_db_files(conn: &mut SqliteConnection, config: &Config) -> Result<Vec<File>> {
+    let items = query!(
+        r#"
+            SELECT id AS "id!", path, root
+            FROM entries
+        "#
+    )
+    .map(|r| File {
+        id: r.id,
+        path: PathBuf::from(r.path),
+        root: r.root,
+        title: String::new(),
+        lang: None,
+        fields: BTreeMap::new(),
+        inherited: inherit::Inherited::default(),
+        file_contents: vec![],
+        status_history: vec![],
+        last_vote_at: None,
+        votes: 0,
+        rating: Rating::new(),
+    })
+    .fetch_all(conn.borrow_mut())
+    .await?;
+
+    let detector = lang::detector(config)?;
+    let mut inherit = inherit::Loader::new(config);
+    let mut m = HashMap::with_capacity(items.len());
+    for mut item in items {
+        let item_path = item.path.to_str().unwrap();
+        let contents = query!(
+            r#"
+                SELECT content AS "content!", at
+                FROM file_contents
+                WHERE path = ?1 AND content IS NOT NULL
+                ORDER BY at ASC
+            "#,
+            item_path,
+        )
+        .map(|r| FileContent {
+            content: r.content,
+            at: Utc.timestamp_opt(r.at, 0).unwrap(),
+        })
+        .fetch_all(conn.borrow_mut())
+        .await?;
+
+        let status_history = query!(
+            r#"
+                SELECT status, actor, reason, at
+                FROM status_history
+                WHERE path = ?1
+                ORDER BY at ASC, rowid ASC
+            "#,
+            item_path,
+        )
+        .fetch_all(conn.borrow_mut())
+        .await?
+        .into_iter()
+        .map(|r| {
+            Ok(StatusChange {
+                status: r.status.parse()?,
+                actor: r.actor,
+                reason: r.reason,
+                at: Utc.timestamp_opt(r.at, 0).unwrap(),
+            })
+        })
+        .collect::<Result<_>>()?;
+
+        let stored_fields = query!(
+            r#"
+                SELECT name, value
+                FROM entry_fields
+                WHERE path = ?1
+            "#,
+            item_path,
+        )
+        .fetch_all(conn.borrow_mut())
+        .await?;
+
+        item.file_contents = contents;
+        item.status_history = status_history;
+        item.title = item.make_title(config);
+        item.lang = item.detect_lang(&detector);
+        item.fields = stored_fields
+            .into_iter()
+            .filter(|r| config.fields.contains_key(&r.name))
+            .map(|r| (r.name, r.value))
+            .collect();
+        item.fields.extend(item.front_matter_fields(config));
+        item.inherited = inherit.inherited(item_path)?;
+
+        m.insert(item.path.clone(), item);
+    }
+
+    let mut res: Vec<_> = m.into_values().collect();
+    let votes = index_votes(config, &res, &counted_votes(conn, config).await?);
+
+    for (vote, at, _) in &votes {
+        for i in [vote.left, vote.right] {
+            res[i].last_vote_at = res[i].last_vote_at.max(Some(*at));
+            res[i].votes += 1;
+        }
+    }
+    let params = rating::live(conn).await?;
+    let bumps = edit_bumps(config, &res);
+    let ratings = params.rate(res.len(), &votes, &bumps, Utc::now(), |_| {});
+    for (item, rating) in res.iter_mut().zip(ratings) {
+        item.rating = rating;
+    }
+
+    // paths are unique, so this is a total order and indices stay the same as
+    // long as nothing is changed.
+    res.sort_by(|a, b| a.rank_cmp(b, config));
+    Ok(res)
+}
+
+/// The active entries in `items`, highest rated first.
+pub fn ranking(items: &[File]) -> Vec<&File> {
+    items.iter().rev().filter(|f| f.is_active()).collect()
+}
+
+/// Says how many places `item` moved by a vote, from `before` to `after` in a
+/// ranking where 1 is the highest. None if it stayed where it was.
+pub fn describe_move(item: &File, before: usize, after: usize) -> Option<String> {
+    let places = |n: usize| match n {
+        1 => "1 place".to_string(),
+        n => format!("{} places", n),
+    };
+    match after.cmp(&before) {
+        Ordering::Less => Some(format!(
+            "{} moved up {} to {}",
+            item,
+            places(before - after),
+            after
+        )),
+        Ordering::Greater => Some(format!(
+            "{} moved down {} to {}",
+            item,
+            places(after - before),
+            after
+        )),
+        Ordering::Equal => None,
+    }
+}
+
+/// Prefix of the deep links printed by `prio link`, followed by the id of an
+/// entry.
+pub const LINK_PREFIX: &str = "prio://entry/";
+
+/// Finds an entry either by its index in the ranking as printed by `show`
+/// (1 being the highest rated active entry), by its id prefixed with `#` or
+/// as a deep link, or by its path, either as stored or on disk.
+pub fn find_entry<'a>(items: &'a [File], config: &Config, entry: &str) -> Result<&'a File> {
+    if let Ok(n) = entry.parse::<usize>() {
+        return ranking(items)
+            .get(n.wrapping_sub(1))
+            .copied()
+            .ok_or_else(|| error::not_found(format!("no entry with index {}", n)));
+    }
+
+    if let Some(id) = entry
+        .strip_prefix('#')
+        .or_else(|| entry.strip_prefix(LINK_PREFIX))
+    {
+        let id: i64 = id
+            .parse()
+            .map_err(|_| error::invalid(format!("invalid id {:?}", entry)))?;
+        return items
+            .iter()
+            .find(|f| f.id == id)
+            .ok_or_else(|| error::not_found(format!("no entry with id {}", entry)));
+    }
+
+    let path = config.resolve(entry);
+    items
+        .iter()
+        .find(|f| f.path == path)
+        .ok_or_else(|| error::not_found(format!("no entry with path {}", entry)))
+}
+
+/// Adds new files and revisions to the database from a single file found by
+/// the walker, `path` being how it is stored.
+async fn sync_file(
+    conn: &mut SqliteConnection,
+    db_file: Option<&File>,
+    root: &str,
+    path: &Path,
+    full_path: &Path,
+) -> Result<()> {
+    let modified: DateTime<Utc> = fs::metadata(full_path).await?.modified()?.into();
+    let path_str = path_str(path);
+
+    match db_file {
+        Some(db_file) if db_file.is_deleted() => {
+            return Err(anyhow!(
+                "already in the database as deleted, use activate to track it again"
+            ));
+        }
+        Some(db_file) if modified <= db_file.last_content().at => return Ok(()),
+        _ => {}
+    }
+
+    // Read before anything is written, so a file that can't be read leaves
+    // nothing behind.
+    let bytes = fs::read(full_path).await?;
+    if db_file.is_some_and(|f| f.last_content().content == bytes) {
+        return Ok(());
+    }
+
+    let mut tx = conn.begin().await?;
+    if db_file.is_none() {
+        let id = schema::next_entry_id(&mut tx).await?;
+        query!(
+            "INSERT INTO entries (path, id, root) VALUES (?1, ?2, ?3)",
+            path_str,
+            id,
+            root,
+        )
+        .execute(&mut *tx)
+        .await?;
+        set_status(&mut tx, path_str, Status::Active, "sync", None).await?;
+
+        let change = Change::Add {
+            path: path_str.to_string(),
+        };
+        audit::record(&mut tx, &change, "sync").await?;
+    }
+
+    let ts = modified.timestamp();
+    query!(
+        r#"
+        INSERT INTO file_contents
+            (path, content, at)
+        VALUES
+            (?1, ?2, ?3)
+        "#,
+        path_str,
+        bytes,
+        ts
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let change = Change::Content {
+        path: path_str.to_string(),
+        at: ts,
+    };
+    audit::record(&mut tx, &change, "sync").await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Whether `path` is the database or a file next to it sharing its name, like
+/// its journal or a backup copy. Those are never synced.
+fn is_artifact(db: &Path, path: &Path) -> bool {
+    let db_name = db.file_name().unwrap().to_string_lossy();
+    path.parent() == db.parent()
+        && path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with(&*db_name))
+}
+
+/// Moves entries whose file is gone to a new file with the same content, so
+/// renaming or moving a file keeps its history and votes. Only unambiguous
+/// matches are moved, where one entry and one new file have that content.
+/// Returns the number of entries moved.
+async fn sync_renames(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    db_files: &[File],
+    files: &[(&str, PathBuf, PathBuf)],
+    skipped: &[(PathBuf, anyhow::Error)],
+) -> Result<usize> {
+    let known: HashSet<&Path> = db_files.iter().map(|f| f.path.as_path()).collect();
+    let present: HashSet<&Path> = files.iter().map(|(_, path, _)| path.as_path()).collect();
+
+    let mut gone: HashMap<u64, Vec<&File>> = HashMap::new();
+    for f in db_files.iter().filter(|f| !f.is_deleted()) {
+        let full_path = config.locate(path_str(&f.path));
+        if present.contains(f.path.as_path())
+            || skipped.iter().any(|(p, _)| full_path.starts_with(p))
+            || full_path.exists()
+        {
+            continue;
+        }
+        let hash = content_hash(&f.last_content().content);
+        gone.entry(hash).or_default().push(f);
+    }
+    if gone.is_empty() {
+        return Ok(0);
+    }
+
+    // New files by the hash of their content, as indices into `files`.
+    let mut new: HashMap<u64, Vec<(usize, Vec<u8>)>> = HashMap::new();
+    for (i, (_, path, full_path)) in files.iter().enumerate() {
+        if known.contains(path.as_path()) {
+            continue;
+        }
+        // Unreadable files are reported when they're synced.
+        let Ok(bytes) = fs::read(full_path).await else {
+            continue;
+        };
+        new.entry(content_hash(&bytes))
+            .or_default()
+            .push((i, bytes));
+    }
+
+    let mut renamed = 0;
+    for (hash, entries) in &gone {
+        let ([from], Some([(i, bytes)])) = (&entries[..], new.get(hash).map(|v| &v[..])) else {
+            continue;
+        };
+        if from.last_content().content != *bytes {
+            continue;
+        }
+
+        let (root, to, _) = &files[*i];
+        let (from, to) = (path_str(&from.path), path_str(to));
+        let mut tx = conn.begin().await?;
+        schema::move_entry(&mut tx, from, to, false).await?;
+        query!("UPDATE entries SET root = ?2 WHERE path = ?1", to, root)
+            .execute(&mut *tx)
+            .await?;
+        let change = Change::Rename {
+            from: from.to_string(),
+            to: to.to_string(),
+        };
+        audit::record(&mut tx, &change, "sync").await?;
+        tx.commit().await?;
+        renamed += 1;
+    }
+    Ok(renamed)
+}
+
+/// Brings the database up to date with the entries directories. Files that
+/// can't be synced are skipped and listed afterwards, unless
+/// `Config::strict_sync` is set.
+pub async fn update_files(conn: &mut SqliteConnection, config: &Config) -> Result<()> {
+    let mut skipped: Vec<(PathBuf, anyhow::Error)> = vec![];
+    let db = config.db();
+
+    let roots = config.roots();
+    for &(_, dir) in &roots {
+        // Hidden files are never synced, so only warn if the database isn't.
+        let Ok(relative) = db.strip_prefix(dir) else {
+            continue;
+        };
+        let hidden = relative
+            .iter()
+            .any(|part| part.to_string_lossy().starts_with('.'));
+        if !hidden {
+            eprintln!(
+                "warning: the database is inside entries directory {}, only the database itself and files named like it are kept out of the ranking",
+                dir.display()
+            );
+        }
+    }
+    let mut entries = vec![];
+    for &(root, dir) in &roots {
+        // Roots inside other roots are only walked on their own.
+        let nested: Vec<_> = roots
+            .iter()
+            .filter(|(_, other)| *other != dir && other.starts_with(dir))
+            .map(|(_, other)| other)
+            .collect();
+
+        let walker = WalkDir::new(dir).into_iter().filter_entry(|entry| {
+            entry.depth() == 0
+                || (!nested.contains(&&entry.path())
+                    && !entry.file_name().to_string_lossy().starts_with('.'))
+        });
+        for entry in walker {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) if config.strict_sync => return Err(e.into()),
+                Err(e) => {
+                    let path = e.path().unwrap_or(dir).to_path_buf();
+                    skipped.push((path, e.into()));
+                    continue;
+                }
+            };
+            if entry.file_type().is_file() && entry.path().to_str().is_none() {
+                let e = anyhow!("file name is not valid UTF-8");
+                if config.strict_sync {
+                    return Err(e.context(format!("syncing {}", entry.path().display())));
+                }
+                skipped.push((entry.into_path(), e));
+            } else if entry.file_type().is_file() && !is_artifact(&db, entry.path()) {
+                let relative = entry.path().strip_prefix(dir).unwrap();
+                let size = entry.metadata().map_or(0, |m| m.len());
+                if config.walk.includes(relative, entry.depth(), size) {
+                    let path = Config::entry_path(root, relative);
+                    entries.push((root, path, entry.into_path()));
+                }
+            }
+        }
+    }
+
+    let title = Title::new();
+    title.set(&format!("syncing {} files", entries.len()));
+
+    let mut db_files = get_db_files(conn, config).await?;
+    if sync_renames(conn, config, &db_files, &entries, &skipped).await? > 0 {
+        db_files = get_db_files(conn, config).await?;
+    }
+    let mut left: HashSet<&File> = db_files.iter().filter(|f| !f.is_deleted()).collect();
+
+    for (root, path, full_path) in entries {
+        let db_file = db_files.iter().find(|f| f.path == path);
+        if let Some(db_file) = db_file {
+            left.remove(db_file);
+        }
+
+        match sync_file(conn, db_file, root, &path, &full_path).await {
+            Ok(()) => {}
+            Err(e) if config.strict_sync => {
+                return Err(e.context(format!("syncing {}", full_path.display())))
+            }
+            Err(e) => skipped.push((full_path, e)),
+        }
+    }
+
+    for db_file in left {
+        // Whatever was in a directory that couldn't be read might still be
+        // there.
+        let path = path_str(&db_file.path);
+        let full_path = config.locate(path);
+        if skipped.iter().any(|(p, _)| full_path.starts_with(p)) {
+            continue;
+        }
+        // Excluded by the walk options, which might only be for this run.
+        if full_path.exists() {
+            continue;
+        }
+
+        // REVIEW: is there a way to get the time of deletion?
+        set_status(conn, path, Status::Deleted, "sync", None).await?;
+
+        let change = Change::Status {
+            path: path.to_string(),
+            from: db_file.status(),
+            to: Status::Deleted,
+            reason: None,
+        };
+        audit::record(conn, &change, "sync").await?;
+    }
+
+    if !skipped.is_empty() {
+        eprintln!("skipped {} files while syncing:", skipped.len());
+        for (path, e) in &skipped {
+            eprintln!("  {}: {:#}", path.display(), e);
+        }
+    }
+    Ok(())
+}
+
+/// Syncs the file of the entry at `path` right away, instead of waiting for
+/// the next sync. `db_file` is the entry as loaded, None for a new file, in
+/// which case the entry and its first content are added together.
+pub async fn track(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    db_file: Option<&File>,
+    path: &str,
+) -> Result<()> {
+    let (root, _) = config.split_root(path);
+    let mut tx = conn.begin().await?;
+    sync_file(
+        &mut tx,
+        db_file,
+        root,
+        Path::new(path),
+        &config.locate(path),
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Opens the database configured in `config`, applying any migrations it is
+/// missing.
+pub async fn connect(config: &Config) -> Result<SqliteConnection> {
+    let mut conn = open(config).await?;
+    schema::upgrade(&mut conn).await?;
+    Ok(conn)
+}
+
+/// Like connect, but leaves paths alone when the schema is up to date, for
+/// commands that have to be fast like prio status.
+pub async fn connect_quick(config: &Config) -> Result<SqliteConnection> {
+    let mut conn = open(config).await?;
+    if !schema::is_current(&mut conn).await? {
+        schema::upgrade(&mut conn).await?;
+    }
+    Ok(conn)
+}
+
+async fn open(config: &Config) -> Result<SqliteConnection> {
+    let db = config.db();
+    if !db.exists() {
+        return Err(error::not_found(format!(
+            "{} doesn't exist, create it with prio init",
+            db.display()
+        )));
+    }
+    SqliteConnection::connect(&format!("sqlite:{}", db.display()))
+        .await
+        .with_context(|| format!("opening {}", db.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn unreadable_new_file_leaves_nothing_behind() {
+        let mut conn = schema::memory().await;
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            dir: dir.path().to_path_buf(),
+            ..Config::default()
+        };
+        // Its metadata can be read, but reading it as a file fails.
+        let full_path = dir.path().join("a.md");
+        std::fs::create_dir(&full_path).unwrap();
+
+        let res = sync_file(&mut conn, None, "", Path::new("a.md"), &full_path).await;
+        assert!(res.is_err());
+
+        let entries = query!(r#"SELECT COUNT(*) AS "n!: i64" FROM entries"#)
+            .fetch_one(&mut conn)
+            .await
+            .unwrap()
+            .n;
+        assert_eq!(entries, 0);
+        assert!(get_db_files(&mut conn, &config).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn new_file_is_added_with_its_content() {
+        let mut conn = schema::memory().await;
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            dir: dir.path().to_path_buf(),
+            ..Config::default()
+        };
+        let full_path = dir.path().join("a.md");
+        std::fs::write(&full_path, "# A\n").unwrap();
+
+        sync_file(&mut conn, None, "", Path::new("a.md"), &full_path)
+            .await
+            .unwrap();
+
+        let files = get_db_files(&mut conn, &config).await.unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].last_content().content, b"# A\n");
+        assert!(files[0].is_active());
+    }
+
+    #[tokio::test]
+    async fn votes_on_unknown_paths_are_skipped() {
+        let mut conn = schema::memory().await;
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            dir: dir.path().to_path_buf(),
+            ..Config::default()
+        };
+        for name in ["a.md", "b.md"] {
+            let full_path = dir.path().join(name);
+            std::fs::write(&full_path, name).unwrap();
+            sync_file(&mut conn, None, "", Path::new(name), &full_path)
+                .await
+                .unwrap();
+        }
+        for right in ["b.md", "gone.md"] {
+            let vote = Vote {
+                left_path: PathBuf::from("a.md"),
+                right_path: PathBuf::from(right),
+                vote: 1,
+                at: Utc::now(),
+                criterion: None,
+                confidence: None,
+            };
+            insert_vote(&mut conn, &vote, "test").await.unwrap();
+        }
+
+        let items = get_db_files(&mut conn, &config).await.unwrap();
+        let votes = counted_votes(&mut conn, &config).await.unwrap();
+        assert_eq!(votes.len(), 2);
+        let indexed = index_votes(&config, &items, &votes);
+        assert_eq!(indexed.len(), 1);
+        assert_eq!(items[indexed[0].0.right].path, Path::new("b.md"));
+    }
+}
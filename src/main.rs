@@ -1,364 +1,3995 @@
-mod sample;
-mod util;
-
 use std::borrow::BorrowMut;
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fmt::Display;
-use std::hash::{Hash, Hasher};
+use std::io::{IsTerminal, Write};
 use std::path::Path;
 use std::path::PathBuf;
+use std::process::ExitCode;
 
-use anyhow::Result;
-use chrono::{DateTime, TimeZone, Utc};
-use clap::{Parser, Subcommand};
-use dialoguer::console::Term;
-use dialoguer::{theme::ColorfulTheme, FuzzySelect};
-use skillratings::{
-    glicko2::{glicko2, Glicko2Config, Glicko2Rating},
-    Outcomes,
-};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Local, TimeZone, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use dialoguer::console::{style, Term};
+use dialoguer::{theme::ColorfulTheme, Confirm, FuzzySelect, Input, Select};
+use prio_core::Rating;
+use rand::{seq::SliceRandom, thread_rng};
+use sqlx::sqlite::SqliteConnectOptions;
 use sqlx::{query, Connection, SqliteConnection};
+use tabwriter::TabWriter;
 use tokio::fs;
 use tokio::runtime::Builder;
-use walkdir::WalkDir;
 
-use sample::take_n;
-use util::path_str;
+#[cfg(any(feature = "export", feature = "import"))]
+use prio::dump;
+#[cfg(feature = "export")]
+use prio::export;
+#[cfg(feature = "gui")]
+use prio::gui;
+#[cfg(feature = "import")]
+use prio::import;
+use prio::{
+    audit, backup, cache, chart, config, context, diff, error, exclude, experiment, fatigue,
+    fields, filter, frontmatter, graph, images, merge, preset, quarantine, rating, render,
+    resurface, sample, schema, speak, stats, status, table, title, undo, util, votes, web,
+};
+use prio::{
+    competition, connect, connect_quick, counted_votes, describe_move, edit_bumps, find_entry,
+    get_db_files, index_votes, insert_vote, make_title, ranking, track, update_files, Confidence,
+    File, Vote, LINK_PREFIX,
+};
 
-// TODO maak manier om files te moven en dat te volgen. dit moet in een transaction
-// TODO: maak manier om weight af te laten nemen van oudere tournaments
+use audit::Change;
+use config::{Config, Format};
+use exclude::Exclusions;
+use filter::Filter;
+use preset::Preset;
+use sample::{take_bridge, take_calibration, take_external, take_n};
+use status::{set_status, Status};
+use title::Title;
+use util::{actor, format_time, normalize_path, parse_since, path_str, slugify, split_words};
 
-const PATH: &'static str = "/home/lieuwe/entries";
-const DB_PATH: &'static str = "/home/lieuwe/entries/.db.db";
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+    /// Use the entries directory and database of this profile in the config.
+    /// Defaults to PRIO_PROFILE.
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    /// Entries directory to use instead of dir in the config or PRIO_DIR
+    #[arg(long, global = true)]
+    dir: Option<PathBuf>,
+    /// Database to use instead of db in the config or PRIO_DB
+    #[arg(long, global = true)]
+    db: Option<PathBuf>,
+    /// Only count votes on this criterion for the ratings, overall for votes
+    /// without one. Overrides include_criteria in the config.
+    #[arg(long, global = true)]
+    with_criterion: Vec<String>,
+    /// Don't count votes on this criterion for the ratings. Overrides
+    /// exclude_criteria in the config.
+    #[arg(long, global = true)]
+    without_criterion: Vec<String>,
+    /// Fail on the first file that can't be synced instead of skipping it
+    #[arg(long, global = true)]
+    strict: bool,
+    /// Override walk.max_depth in the config
+    #[arg(long, global = true)]
+    max_depth: Option<usize>,
+    /// Override walk.extensions in the config
+    #[arg(long = "extension", global = true)]
+    extensions: Vec<String>,
+    /// Override walk.min_size in the config, in bytes
+    #[arg(long, global = true)]
+    min_size: Option<u64>,
+    /// Override walk.max_size in the config, in bytes
+    #[arg(long, global = true)]
+    max_size: Option<u64>,
+    /// Print show, stats and votes as JSON instead of text, or export as CSV
+    /// or JSON. Overrides format in the config.
+    #[arg(long, global = true)]
+    format: Option<config::Format>,
+}
 
-async fn competition(conn: &mut SqliteConnection, winner: &Path, loser: &Path) -> Result<()> {
-    assert!(winner != loser);
+#[derive(Subcommand, Debug, Clone)]
+enum Commands {
+    /// Create an entry from the given text, or else write it in $VISUAL or
+    /// $EDITOR. Warns if its title is close to that of an active entry,
+    /// offering to edit that one instead.
+    Add(AddArgs),
+    /// Create an entry for a web page to read later, titled after the page
+    /// and with its address under `url` in the front matter
+    AddUrl {
+        url: String,
+        /// Also save the text of the page's paragraphs, so the entry can be
+        /// skimmed and its reading time estimated
+        #[arg(long)]
+        text: bool,
+    },
+    /// Fetch the pages of entries added with add-url again, recording the
+    /// status and taking over changed titles as a new version. Pages that
+    /// are gone are marked for autoprune.
+    RefreshUrls,
+    /// Rank the entries by comparing them two at a time
+    Vote(VoteArgs),
+    /// Print the ranking, or a single entry
+    Show(ShowArgs),
+    /// Show the highest ranked entry that can be done in the given situation
+    Next(context::Situation),
+    /// Mark entries as done
+    Done(StatusArgs),
+    /// Drop entries without doing them
+    Drop(StatusArgs),
+    /// Hide entries from show and vote for now
+    Snooze(StatusArgs),
+    /// Move the files of entries into the quarantine, see prio quarantine.
+    /// Their history stays in the database
+    Remove(StatusArgs),
+    /// Make done, dropped or snoozed entries active again
+    Activate(StatusArgs),
+    /// Delete an entry from the database for good, with its history, votes
+    /// and quarantined copies. Its file has to be gone, or be deleted with
+    /// --delete-file, as the next sync would add it again.
+    Purge {
+        /// Index as printed by show, id (#12) or path of the entry
+        entry: String,
+        /// Delete the file of the entry too
+        #[arg(long)]
+        delete_file: bool,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Move the file of an entry, keeping its votes and history
+    Mv {
+        /// Index as printed by show, id (#12) or path of the entry
+        entry: String,
+        /// The new path of the file
+        to: String,
+    },
+    /// Bring a deleted entry back, writing its file from the quarantine or
+    /// else its last revision
+    Restore {
+        /// Id (#12) or path of the entry, deleted entries have no index
+        entry: String,
+    },
+    /// Show the changes made to the database
+    Log {
+        /// Only show changes since this date (2024-01-31) or duration ago (7d)
+        #[arg(long)]
+        since: Option<String>,
+    },
+    /// Vote on an entry against its neighbours until its position settles
+    Nudge {
+        /// Index as printed by show, #id or path of the entry
+        entry: String,
+    },
+    /// Print the rank and neighbours of an entry
+    RankOf {
+        /// Path or #id of the entry
+        entry: String,
+    },
+    /// Break down how the rating of an entry came about from its votes
+    Explain {
+        /// Index as printed by show, #id or path of the entry
+        entry: String,
+    },
+    /// List the stored versions of an entry, oldest first
+    History {
+        /// Index as printed by show, #id or path of the entry
+        entry: String,
+        /// Print version K as numbered in the list instead
+        #[arg(long, value_name = "K")]
+        show: Option<usize>,
+    },
+    /// List the votes cast with their ids, oldest first, or change one of
+    /// them
+    #[command(args_conflicts_with_subcommands = true)]
+    Votes {
+        #[command(subcommand)]
+        command: Option<VotesCommand>,
+        /// Only list the votes on this entry, by index as printed by show,
+        /// #id or path
+        #[arg(long, value_name = "N")]
+        entry: Option<String>,
+        /// Only list the last K votes
+        #[arg(long, value_name = "K")]
+        limit: Option<usize>,
+    },
+    /// Write a stored version of an entry back to its file, storing it as the
+    /// latest version again
+    Checkout {
+        /// Index as printed by show, #id or path of the entry
+        entry: String,
+        /// Version to restore, as numbered by history
+        #[arg(long, value_name = "K")]
+        version: usize,
+    },
+    /// Print what changed between two stored versions of an entry, the last
+    /// two by default, as a unified diff
+    Diff {
+        /// Index as printed by show, #id or path of the entry
+        entry: String,
+        /// Version to diff from, as numbered by history
+        #[arg(long, value_name = "A")]
+        from: Option<usize>,
+        /// Version to diff to, as numbered by history
+        #[arg(long, value_name = "B")]
+        to: Option<usize>,
+    },
+    /// Compare the alternatives for a decision, as set with `alternative_for`
+    /// in their front matter, against each other once and print the winner.
+    /// Lists the decisions if none is given.
+    Decide { decision: Option<String> },
+    /// Set a custom field declared in the config, or unset it if no value is
+    /// given. Values in the front matter of an entry take precedence.
+    Set {
+        /// Index as printed by show, #id or path of the entry
+        entry: String,
+        field: String,
+        value: Option<String>,
+    },
+    /// Print a deep link to an entry, which every command taking an entry
+    /// accepts too
+    Link {
+        /// Index as printed by show, #id or path of the entry
+        entry: String,
+        /// Also print the link as a QR code
+        #[cfg(feature = "qr")]
+        #[arg(long)]
+        qr: bool,
+    },
+    /// Read the top of the ranking aloud using the configured speak_command,
+    /// or print it as plain sentences if there is none
+    Speak {
+        /// How many entries to mention
+        #[arg(long, default_value_t = 5)]
+        top: usize,
+        /// Print the sentences even if a speak_command is configured
+        #[arg(long)]
+        print: bool,
+    },
+    /// Revert the last vote, status or field change
+    Undo {
+        /// Revert the last vote, even if other changes were made since
+        #[arg(long)]
+        vote: bool,
+    },
+    /// Apply the last undone change again
+    Redo,
+    /// Run commands read from stdin, one per line, after syncing only once
+    Batch,
+    /// Print a one line summary without syncing, e.g. for shell prompts
+    Status,
+    /// Rewrite the paths of entries starting with a prefix after moving
+    /// their files, keeping their votes and history, without syncing first
+    Remap {
+        /// Prefix of the old paths, as stored, e.g. `work/` or `notes:`
+        #[arg(long)]
+        from: String,
+        /// What to replace the prefix by
+        #[arg(long)]
+        to: String,
+        /// Only print what would be renamed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Create the entries directory and an empty database for it
+    Init {
+        /// The entries directory, defaults to dir in the config
+        path: Option<PathBuf>,
+        /// Set up rating decay and the config for a way of using prio. The
+        /// config is written if there is none yet, or else printed.
+        #[arg(long)]
+        preset: Option<Preset>,
+    },
+    /// Open the file of an entry in $VISUAL or $EDITOR and store what was
+    /// changed as a new version right after
+    Edit {
+        /// Index as printed by show, or path of the entry
+        entry: String,
+    },
+    /// Check the database for problems
+    Doctor,
+    /// Compare the file of every entry against the content last synced for
+    /// it, without syncing first. Fails if any differ, to be used in
+    /// scheduled checks
+    Verify,
+    /// List the entries matching the autoprune policy in the config
+    Autoprune {
+        /// Snooze or drop the listed entries after asking for confirmation
+        #[arg(long)]
+        apply: bool,
+    },
+    /// Review entries that are due again, regardless of their rating, and
+    /// grade how relevant they still are to schedule the next review
+    Resurface {
+        /// Review at most this many entries
+        #[arg(short = 'n', long, default_value_t = 10)]
+        limit: usize,
+        /// Only list the entries that are due
+        #[arg(long)]
+        list: bool,
+    },
+    /// Import pairwise votes from a CSV or JSON file, see import::ImportedVote
+    /// for the format
+    #[cfg(feature = "import")]
+    ImportVotes {
+        file: PathBuf,
+        /// CSV file with a name,path header mapping names used in the votes
+        /// to entry paths
+        #[arg(long)]
+        map: Option<PathBuf>,
+        /// Create an entry for every unknown name instead of failing
+        #[arg(long)]
+        create_stubs: bool,
+    },
+    /// Merge another prio database into this one
+    Merge {
+        /// Path of the other database
+        db: PathBuf,
+        /// What to do with entries whose content history differs in both,
+        /// asked for each of them when not given
+        #[arg(long)]
+        strategy: Option<merge::Strategy>,
+    },
+    /// Snapshot the database and remove old snapshots
+    Backup {
+        /// Number of snapshots to keep, including the new one, instead of
+        /// backup_keep
+        #[arg(long)]
+        keep: Option<usize>,
+    },
+    /// Replace the database by a snapshot, or list the snapshots without one
+    RestoreBackup {
+        /// File name of the snapshot as listed, or its path
+        backup: Option<String>,
+    },
+    /// Write a read-only HTML page with the ranking of only the active entries
+    /// having all given tags, without ratings
+    #[cfg(feature = "export")]
+    Share {
+        /// Tag from the `tags` front matter field, can be repeated
+        #[arg(long = "tag", required = true)]
+        tags: Vec<String>,
+        #[arg(long, default_value = "share.html")]
+        out: PathBuf,
+    },
+    /// Open a window with the ranking, a preview and a voting view
+    #[cfg(feature = "gui")]
+    Gui,
+    /// Compute the ratings from all votes with other parameters and compare
+    /// the top of the ranking with the current one. Unless --dry-run or
+    /// --experiment is given, the parameters are used from then on.
+    Recompute {
+        #[arg(long, default_value = "glicko2")]
+        system: rating::System,
+        /// Half-life of votes, like 90d: a vote this old counts half
+        #[arg(long)]
+        decay: Option<String>,
+        /// Only let votes older than this decay, like 30d
+        #[arg(long, requires = "decay")]
+        horizon: Option<String>,
+        /// Only print the comparison
+        #[arg(long)]
+        dry_run: bool,
+        /// Store the ratings as a named experiment instead of using them
+        #[arg(long, conflicts_with = "dry_run")]
+        experiment: Option<String>,
+    },
+    /// Print the top of the ranking, entries with a date field in the coming
+    /// week, recently edited entries and today's votes
+    Dashboard,
+    /// List the entries whose rating isn't settled yet, with roughly how
+    /// many votes each needs, to tell whether a vote session is worthwhile
+    NeedsVotes {
+        /// Deviation above which an entry isn't settled, defaults to
+        /// settled_deviation in the config
+        #[arg(long)]
+        threshold: Option<f64>,
+    },
+    /// Print the number of entries and votes, votes per day over the last
+    /// month, the most and least compared entries and the average deviation
+    Stats,
+    /// List entries that moved a lot in the ranking recently, which can point
+    /// at miscast votes. Meant to run from cron with --notify.
+    Drift {
+        /// Compare with the ranking this long ago
+        #[arg(long, default_value = "7d")]
+        since: String,
+        /// Report entries that moved more than this many ranks
+        #[arg(long, default_value_t = 5)]
+        ranks: usize,
+        /// Also report entries that fell out of the top this many
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+        /// Send the report to notify_command from the config if there is
+        /// anything to report
+        #[arg(long)]
+        notify: bool,
+    },
+    /// Compare the experiments stored by recompute and promote one to be the
+    /// live ranking
+    Experiments {
+        #[command(subcommand)]
+        command: ExperimentCommand,
+    },
+    /// Go through the files prio remove moved into the quarantine
+    Quarantine {
+        #[command(subcommand)]
+        command: QuarantineCommand,
+    },
+    /// Write the database in another format to stdout: the vote graph, or
+    /// the entries with their ratings with --format csv or json
+    #[cfg(feature = "export")]
+    Export {
+        /// Export the graph of votes, entries being nodes and votes edges
+        #[arg(long)]
+        graph: Option<export::GraphFormat>,
+        /// Also export deleted entries
+        #[arg(long, conflicts_with = "graph")]
+        deleted: bool,
+        /// Export the whole database as JSON, with every revision and vote,
+        /// to be read back by prio import
+        #[arg(long, conflicts_with_all = ["graph", "deleted"])]
+        all: bool,
+    },
+    /// Restore a database written by export --all into a new collection,
+    /// writing the entries that aren't on disk
+    #[cfg(feature = "import")]
+    Import { file: PathBuf },
+}
 
-    let winner = path_str(winner);
-    let loser = path_str(loser);
-    let score = 1;
-    let ts = Utc::now().timestamp();
+#[derive(Subcommand, Debug, Clone)]
+enum ExperimentCommand {
+    List,
+    /// Print how the top of the ranking differs from the live one
+    Show {
+        name: String,
+    },
+    /// Compute the live ranking with the parameters of the experiment from
+    /// now on
+    Promote {
+        name: String,
+    },
+    Delete {
+        name: String,
+    },
+}
 
-    query!(
-        "INSERT INTO entry_votes VALUES (?1, ?2, ?3, ?4)",
-        winner,
-        loser,
-        score,
-        ts
-    )
-    .execute(conn)
-    .await?;
-    Ok(())
+#[derive(Subcommand, Debug, Clone)]
+enum QuarantineCommand {
+    List,
+    /// Decide for every quarantined file whether to delete it for good,
+    /// restore it or keep it around
+    Review,
 }
 
-#[derive(Debug, Clone)]
-pub struct FileContent {
-    content: Option<Vec<u8>>,
-    at: DateTime<Utc>,
+#[derive(Subcommand, Debug, Clone)]
+enum VotesCommand {
+    /// Change the outcome of a vote, by id as listed by prio votes
+    Edit {
+        id: i64,
+        /// The outcome for the entry listed first
+        #[arg(long)]
+        outcome: Outcome,
+    },
+    /// Delete a vote, by id as listed by prio votes
+    Delete { id: i64 },
 }
 
-#[derive(Debug, Clone)]
-pub struct Vote {
-    left_path: PathBuf,
-    right_path: PathBuf,
-    vote: i64,
-    at: DateTime<Utc>,
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum Outcome {
+    Win,
+    Loss,
+    Draw,
+}
+
+impl Outcome {
+    /// The vote as stored in entry_votes.
+    fn vote(self) -> i64 {
+        match self {
+            Outcome::Win => 1,
+            Outcome::Loss => -1,
+            Outcome::Draw => 0,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct VoteArgs {
+    /// Prefer pairs of entries that aren't connected by earlier votes, see
+    /// doctor
+    #[arg(long)]
+    bridge: bool,
+    /// Only vote on entries matching a condition, like for show
+    #[arg(short, long)]
+    filter: Vec<Filter>,
+    /// Sync before every vote, so new entries can come up without starting a
+    /// new session
+    #[arg(long)]
+    live: bool,
+    /// What the votes of this session are judged on, e.g. urgency. Skips the
+    /// calibration pairs, which compare against the overall ranking.
+    #[arg(long)]
+    criterion: Option<String>,
+    #[command(flatten)]
+    situation: context::Situation,
+}
+
+#[derive(clap::Args, Debug, Clone, Default)]
+struct ShowArgs {
+    /// Index as printed by show, #id or path of the entry to show
+    entry: Option<String>,
+    /// Instead of its content, show the entry between this many entries
+    /// ranked directly above and below it. Use nudge to vote against those.
+    #[arg(short, long, requires = "entry")]
+    context: Option<usize>,
+    /// Also print a histogram of the ratings, marking the shown entry
+    #[arg(long)]
+    chart: bool,
+    /// Only list entries matching a condition like `words>500` or `lang=nld`,
+    /// on words, minutes, rating, deviation, lang, root or a custom field. Can
+    /// be given multiple times.
+    #[arg(short, long, conflicts_with = "entry")]
+    filter: Vec<Filter>,
+    /// Columns of the list and their order, comma separated, out of rank,
+    /// title, path, score, offset, votes, age, words, minutes, lang, tags,
+    /// status and custom fields. Defaults to rank,title,score,votes,age,minutes,tags.
+    #[arg(long, value_delimiter = ',', conflicts_with = "entry")]
+    columns: Vec<table::Column>,
+    /// Only list entries mentioning @name, the ones delegated to them
+    #[arg(long, value_name = "NAME", conflicts_with = "entry")]
+    waiting_on: Option<String>,
+    /// List every alternative of a decision instead of only the best ranked
+    /// one, see decide
+    #[arg(long, conflicts_with = "entry")]
+    alternatives: bool,
+    /// Print the content of the entry as is, instead of styling Markdown or
+    /// passing it through render_command
+    #[arg(long, requires = "entry")]
+    raw: bool,
+    /// Only entries that can be done in this context, like next --context
+    #[arg(long, value_name = "CONTEXT")]
+    at: Option<String>,
+    /// Only entries that take at most this much energy, like for next
+    #[arg(long, value_enum)]
+    energy: Option<context::Energy>,
+}
+
+impl ShowArgs {
+    fn situation(&self) -> context::Situation {
+        context::Situation {
+            at: self.at.clone(),
+            energy: self.energy,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug, Clone)]
+#[command(arg_required_else_help = true)]
+struct AddArgs {
+    /// Content of the entry, e.g. "Buy birthday present for T", or `-` to
+    /// read it from stdin. Its file is named after the first line. Opens an
+    /// editor to write it in when not given.
+    text: Option<String>,
+    /// Path of the new file, in the entries directory or `name:path` for
+    /// another root
+    #[arg(short, long)]
+    path: Option<String>,
+    /// Edit the text in $VISUAL or $EDITOR before adding it
+    #[arg(short, long)]
+    edit: bool,
+    /// Start the generated file name with today's date
+    #[arg(long, conflicts_with = "path")]
+    date: bool,
+    /// Find where the entry belongs right away by comparing it against the
+    /// ranking, halving the range every time, instead of starting out in
+    /// the middle
+    #[arg(long)]
+    place: bool,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct StatusArgs {
+    /// Indices as printed by show, or paths of the entries. Changing several
+    /// at once is undone as one change.
+    #[arg(required = true)]
+    entries: Vec<String>,
+    /// Why the status changed, asked for interactively for done, drop and
+    /// remove when not given
+    #[arg(short, long)]
+    reason: Option<String>,
 }
 
-#[derive(Debug, Clone)]
-pub struct File {
-    path: PathBuf,
-    file_contents: Vec<FileContent>,
-    rating: Glicko2Rating,
+/// The entries to vote on, as selected by the arguments.
+async fn vote_candidates(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    args: &VoteArgs,
+) -> Result<VecDeque<File>> {
+    for filter in &args.filter {
+        filter.check(config)?;
+    }
+    let items = get_db_files(conn, config).await?;
+    let items: VecDeque<_> = items
+        .into_iter()
+        .filter(|f| f.is_active() && args.filter.iter().all(|filter| filter.matches(f)))
+        .filter(|f| args.situation.allows(f))
+        .filter(|f| !exclude::never_compared(config, f))
+        .collect();
+    if items.len() < 2 {
+        return Err(error::conflict("need at least two active entries to vote"));
+    }
+    Ok(items)
 }
 
-impl File {
-    fn last_content(&self) -> &FileContent {
-        self.file_contents
-            .last()
-            .expect("file_contents can't be empty")
+/// Asks about pairs with a clear expected outcome. If any of them goes the
+/// other way, a different criterion than usual might be used today, so offers
+/// to tag the session with it. Returns None if the user quit.
+async fn calibrate(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    args: &VoteArgs,
+) -> Result<Option<Option<String>>> {
+    let mut votes = vec![];
+    for _ in 0..config.calibration_pairs {
+        let items = vote_candidates(conn, config, args).await?;
+        let exclusions = Exclusions::new(config, &items);
+        let pair = exclusions.draw(|| Ok(take_calibration(items.clone()).unwrap_or_default()))?;
+        if pair.len() < 2 {
+            break;
+        }
+        let Some(selection) = pick(&pair) else {
+            return Ok(None);
+        };
+        let (winner, loser) = (&pair[selection], &pair[1 - selection]);
+        votes.push((
+            winner.path.clone(),
+            loser.path.clone(),
+            winner.rating.rating < loser.rating.rating,
+        ));
+    }
+
+    let wrong = votes.iter().filter(|(_, _, wrong)| *wrong).count();
+    let mut criterion = None;
+    if wrong > 0 {
+        println!(
+            "{} of {} calibration pairs went against the ranking, you might be judging on a different criterion today",
+            wrong,
+            votes.len()
+        );
+        let tag = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Tag this session with a criterion?")
+            .default(false)
+            .interact_on(&Term::stderr())?;
+        if tag {
+            let label: String = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("Criterion")
+                .interact_text()?;
+            criterion = Some(label.trim().to_string()).filter(|s| !s.is_empty());
+        }
     }
 
-    fn is_deleted(&self) -> bool {
-        self.last_content().content.is_none()
+    for (winner, loser, _) in votes {
+        competition(conn, &winner, &loser, criterion.as_deref()).await?;
     }
+    Ok(Some(criterion))
 }
 
-impl Display for File {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let content = &self
-            .file_contents
-            .last()
-            .expect("file_contents can't be empty")
-            .content;
+async fn vote(conn: &mut SqliteConnection, config: &Config, args: &VoteArgs) -> Result<()> {
+    let title = Title::new();
+    // Without a terminal, pairs are written to stdout as JSON and answered
+    // on stdin, and the prompts in between are skipped.
+    let piped = !std::io::stdout().is_terminal();
+    let criterion = match &args.criterion {
+        Some(criterion) => Some(criterion.clone()),
+        None if piped => None,
+        None => {
+            title.set("calibrating");
+            let Some(criterion) = calibrate(conn, config, args).await? else {
+                return Ok(());
+            };
+            criterion
+        }
+    };
+
+    // Loaded once, after that ratings are updated in place as votes are cast
+    // so there's no pause between pairs.
+    let mut candidates = vote_candidates(conn, config, args).await?;
+    let params = rating::live(conn).await?;
+    for n in 1.. {
+        if args.live {
+            update_files(conn, config).await?;
+            let fresh = vote_candidates(conn, config, args).await?;
+            let known: HashSet<_> = candidates.iter().map(|f| &f.path).collect();
+            match fresh.iter().filter(|f| !known.contains(&f.path)).count() {
+                0 => {}
+                1 => eprintln!("1 new entry joined the pool"),
+                new => eprintln!("{} new entries joined the pool", new),
+            }
+            candidates = fresh;
+        }
+        title.set(&format!("voting {}", n));
+
+        let components = if args.bridge {
+            let edges = graph::vote_edges(conn).await?;
+            let refs: Vec<_> = candidates.iter().collect();
+            let mut component_of = HashMap::new();
+            for (i, component) in graph::components(&refs, &edges).into_iter().enumerate() {
+                component_of.extend(component.into_iter().map(|f| (&f.path, i)));
+            }
+            let components: Vec<_> = candidates.iter().map(|f| component_of[&f.path]).collect();
+            Some(components)
+        } else {
+            None
+        };
+        let items = Exclusions::new(config, &candidates).draw(|| {
+            let items = candidates.clone();
+            Ok(match &components {
+                Some(components) => take_bridge(items, components, &config.recent_edits),
+                None => match &config.sampler_command {
+                    Some(command) => take_external(items, command)?,
+                    None => take_n(items, 2, &config.recent_edits),
+                },
+            })
+        })?;
+
+        let answer = if piped {
+            ask_piped(&items)?
+        } else {
+            // After a vote in this session, it can be taken back right away.
+            let mut choices: Vec<_> = items.iter().map(|f| f.to_string()).collect();
+            if n > 1 {
+                choices.push("(undo the last vote)".to_string());
+            }
+            pick(&choices).map(|selection| [Answer::Left, Answer::Right, Answer::Undo][selection])
+        };
+        let Some(answer) = answer else { break };
+        if let Answer::Undo = answer {
+            match undo::undo(conn, config, true).await? {
+                Some(change) => eprintln!("undid: {}", change),
+                None => eprintln!("no vote to undo"),
+            }
+            candidates = vote_candidates(conn, config, args).await?;
+            continue;
+        }
+        let confidence = match answer {
+            Answer::Left | Answer::Right if config.ask_confidence && !piped => {
+                let Some(confidence) = ask_confidence()? else {
+                    break;
+                };
+                Some(confidence)
+            }
+            _ => None,
+        };
 
-        match content {
-            Some(content) => {
-                let s = std::str::from_utf8(&content).unwrap();
-                let line = s.lines().nth(0).unwrap_or("");
-                write!(f, "{} ({})", line, path_str(&self.path))
+        let (left, right) = (&items[0].path, &items[1].path);
+        let (left, right, vote) = match answer {
+            Answer::Left => (left, right, 1),
+            Answer::Right => (right, left, 1),
+            Answer::Draw => (left, right, 0),
+            Answer::Undo => unreachable!("undone above"),
+        };
+        let cast = Vote {
+            left_path: left.clone(),
+            right_path: right.clone(),
+            vote,
+            at: Utc::now(),
+            criterion: criterion.clone(),
+            confidence,
+        };
+        insert_vote(conn, &cast, &actor()).await?;
+        if config.counts_criterion(criterion.as_deref()) {
+            let weight = config.vote_weight(confidence);
+            let before = [pool_rank(&candidates, left), pool_rank(&candidates, right)];
+            apply_vote(&mut candidates, &params, left, right, vote, weight)?;
+            if !piped {
+                for (path, before) in [left, right].into_iter().zip(before) {
+                    if let Some(before) = before {
+                        report_move(&candidates, path, before);
+                    }
+                }
             }
-            None => {
-                write!(f, "{} (deleted)", path_str(&self.path))
+        }
+
+        let break_after = config.break_after.filter(|&after| after > 0 && !piped);
+        if let Some(after) = break_after.filter(|after| n % after == 0) {
+            if take_break(conn, config, n, after).await? {
+                break;
             }
         }
     }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Answer {
+    Left,
+    Right,
+    Draw,
+    /// Take back the last vote instead.
+    Undo,
+}
+
+/// Asks whether the user was sure of the vote they just cast. Returns None if
+/// they quit instead.
+fn ask_confidence() -> Result<Option<Confidence>> {
+    let choice = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("How sure are you?")
+        .items(&["sure", "leaning"])
+        .default(0)
+        .interact_on_opt(&Term::stderr())?;
+    Ok(choice.map(|i| [Confidence::Sure, Confidence::Leaning][i]))
 }
 
-// REVIEW
-impl PartialEq for File {
-    fn eq(&self, other: &Self) -> bool {
-        self.path == other.path
+/// Prints the pair as a line of JSON, `{"left": {...}, "right": {...}}`, and
+/// reads `left`, `right`, `draw` or `undo` from stdin. Returns None at the
+/// end of input or on `quit`.
+fn ask_piped(pair: &[File]) -> Result<Option<Answer>> {
+    let entry = |f: &File| {
+        serde_json::json!({
+            "id": f.id,
+            "path": path_str(&f.path),
+            "title": f.title,
+        })
+    };
+    println!(
+        "{}",
+        serde_json::json!({ "left": entry(&pair[0]), "right": entry(&pair[1]) })
+    );
+
+    let mut line = String::new();
+    if std::io::stdin().read_line(&mut line)? == 0 {
+        return Ok(None);
     }
+    Ok(Some(match line.trim() {
+        "left" => Answer::Left,
+        "right" => Answer::Right,
+        "draw" => Answer::Draw,
+        "undo" => Answer::Undo,
+        "quit" => return Ok(None),
+        answer => {
+            return Err(error::invalid(format!(
+                "unknown answer {:?}, expected left, right, draw, undo or quit",
+                answer
+            )))
+        }
+    }))
 }
-impl Eq for File {}
 
-// REVIEW
-impl Hash for File {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.path.hash(state)
+/// Updates the ratings in `items` for a vote that was just cast. This only
+/// approximates reloading everything: only the two entries are updated,
+/// while a reload weighs every earlier vote by its decay as of now and
+/// applies edit bumps in between.
+fn apply_vote(
+    items: &mut VecDeque<File>,
+    params: &rating::Params,
+    left: &Path,
+    right: &Path,
+    vote: i64,
+    weight: f64,
+) -> Result<()> {
+    let index = |path: &Path| {
+        items
+            .iter()
+            .position(|f| f.path == path)
+            .ok_or_else(|| anyhow!("{} is no longer in the pool", path.display()))
+    };
+    let (left, right) = (index(left)?, index(right)?);
+
+    let mut ratings = [items[left].rating, items[right].rating];
+    let vote = prio_core::Vote {
+        left: 0,
+        right: 1,
+        vote,
+    };
+    let now = Utc::now();
+    params.apply(&mut ratings, &vote, now, now, weight);
+
+    let now = Some(now);
+    for (i, rating) in [left, right].into_iter().zip(ratings) {
+        items[i].rating = rating;
+        items[i].last_vote_at = now;
+        items[i].votes += 1;
     }
+    Ok(())
 }
 
-async fn get_db_files(conn: &mut SqliteConnection, include_deleted: bool) -> Result<Vec<File>> {
-    let items = query!(
-        r#"
-            SELECT path
-            FROM entries
-        "#
-    )
-    .map(|r| File {
-        path: PathBuf::from(r.path),
-        file_contents: vec![],
-        rating: Glicko2Rating::new(),
-    })
-    .fetch_all(conn.borrow_mut())
-    .await?;
+/// Where the entry at `path` ranks among `items` by rating, 1 being the highest.
+fn pool_rank(items: &VecDeque<File>, path: &Path) -> Option<usize> {
+    let rating = items.iter().find(|f| f.path == path)?.rating.rating;
+    Some(items.iter().filter(|f| f.rating.rating > rating).count() + 1)
+}
 
-    let mut m = HashMap::with_capacity(items.len());
-    for mut item in items {
-        let item_path = item.path.to_str().unwrap();
-        let contents = query!(
-            r#"
-                SELECT content, at
-                FROM file_contents
-                WHERE path = ?1
-                ORDER BY at ASC
-            "#,
-            item_path,
-        )
-        .map(|r| FileContent {
-            content: r.content,
-            at: Utc.timestamp(r.at, 0),
-        })
-        .fetch_all(conn.borrow_mut())
-        .await?;
+/// Tells how many places the entry at `path` moved in the pool by the last
+/// vote, if it moved.
+fn report_move(items: &VecDeque<File>, path: &Path, before: usize) {
+    let (Some(item), Some(after)) = (
+        items.iter().find(|f| f.path == path),
+        pool_rank(items, path),
+    ) else {
+        return;
+    };
+    if let Some(report) = describe_move(item, before, after) {
+        eprintln!("{}", report);
+    }
+}
 
-        item.file_contents = contents;
+/// Shows how often votes late in a session went against the current ranking
+/// and asks whether to stop.
+async fn take_break(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    n: usize,
+    size: usize,
+) -> Result<bool> {
+    let items = get_db_files(conn, config).await?;
 
-        m.insert(item.path.clone(), item);
+    println!(
+        "\n{} votes in a row, upsets by position in earlier sessions:",
+        n
+    );
+    for bucket in fatigue::upsets_by_position(conn, &items, size).await? {
+        if bucket.votes == 0 {
+            continue;
+        }
+        println!(
+            "  votes {:>3}-{:<3} {:>3}% upsets ({} votes)",
+            bucket.start + 1,
+            bucket.start + bucket.len,
+            100 * bucket.upsets / bucket.votes,
+            bucket.votes
+        );
     }
 
-    let orderings = query!(
-        r#"
-            SELECT left_path, right_path, vote, at
-            FROM entry_votes
-        "#
-    )
-    .map(|r| Vote {
-        left_path: PathBuf::from(r.left_path),
-        right_path: PathBuf::from(r.right_path),
-        vote: r.vote,
-        at: Utc.timestamp(r.at, 0),
-    })
-    .fetch_all(conn.borrow_mut())
-    .await?;
+    Ok(Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt("Take a break?")
+        .default(true)
+        .interact_on(&Term::stderr())?)
+}
+
+/// Asks which of the two items is more important, None if the user quit.
+fn pick<T: Display>(items: &[T]) -> Option<usize> {
+    FuzzySelect::with_theme(&ColorfulTheme::default())
+        .items(items)
+        .default(0)
+        .interact_on_opt(&Term::stderr())
+        .unwrap()
+}
 
-    for ordering in orderings {
-        let left = m.get(&ordering.left_path).unwrap().rating;
-        let right = m.get(&ordering.right_path).unwrap().rating;
+/// Compares the entry against its direct neighbours in the ranking until it
+/// has been compared against both entries currently above and below it.
+async fn nudge(conn: &mut SqliteConnection, config: &Config, entry: &str) -> Result<()> {
+    let path = {
+        let items = get_db_files(conn, config).await?;
+        find_entry(&items, config, entry)?.path.clone()
+    };
+    let mut compared = HashSet::new();
 
-        let outcome = match ordering.vote {
-            0 => Outcomes::DRAW,
-            ..=-1 => Outcomes::LOSS,
-            1.. => Outcomes::WIN,
+    loop {
+        let items = get_db_files(conn, config).await?;
+        let ranking = ranking(&items);
+        let Some(i) = ranking.iter().position(|f| f.path == path) else {
+            let item = items.iter().find(|f| f.path == path).unwrap();
+            return Err(not_ranked(item));
         };
 
-        let (left, right) = glicko2(&left, &right, &outcome, &Glicko2Config::new());
+        // The closest entries it may be compared with, see never_compare.
+        let exclusions = Exclusions::new(config, items.iter());
+        let allowed = |f: &&&File| exclusions.allows(ranking[i], f);
+        let above = ranking[..i].iter().rev().find(allowed).copied();
+        let below = ranking[i + 1..].iter().find(allowed).copied();
+        let Some(other) = [above, below]
+            .into_iter()
+            .flatten()
+            .find(|f| !compared.contains(&f.path))
+        else {
+            print!("settled at ");
+            print_ranked(i + 1, ranking[i]);
+            break;
+        };
 
-        m.get_mut(&ordering.left_path).unwrap().rating = left;
-        m.get_mut(&ordering.right_path).unwrap().rating = right;
+        let pair = [ranking[i], other];
+        let Some(selection) = pick(&pair) else { break };
+        competition(conn, &pair[selection].path, &pair[1 - selection].path, None).await?;
+        compared.insert(other.path.clone());
     }
 
-    let mut res: Vec<_> = m
-        .into_iter()
-        .map(|p| p.1)
-        .filter(|f| !f.is_deleted() || include_deleted)
-        .collect();
-    res.sort_by_key(|i| i.rating.rating as i64);
-    Ok(res)
+    Ok(())
 }
 
-async fn update_files(conn: &mut SqliteConnection) -> Result<()> {
-    let entries = WalkDir::new(PATH).into_iter().filter_map(|entry| {
-        let entry = entry.unwrap();
-        if !entry.file_type().is_file() {
-            return None;
-        } else if entry.file_name().to_string_lossy().starts_with('.') {
-            return None;
+fn show_one(config: &Config, item: &File, rank: Option<usize>, raw: bool) -> Result<()> {
+    match rank {
+        Some(i) => print_ranked(i + 1, item),
+        None => println!("{}", item),
+    }
+    if let Some(last) = item.status_history.last().filter(|_| !item.is_active()) {
+        print!(
+            "{} since {} by {}",
+            last.status,
+            format_time(last.at),
+            last.actor
+        );
+        match &last.reason {
+            Some(reason) => println!(": {}", reason),
+            None => println!(),
         }
+    }
+    for (name, value) in &item.fields {
+        println!("{}: {}", name, value);
+    }
+    println!();
 
-        Some(entry)
-    });
+    let content = &item.last_content().content;
+    if raw || !std::io::stdout().is_terminal() {
+        std::io::stdout().write_all(content)?;
+    } else if let Some(command) = &config.render_command {
+        std::io::stdout().write_all(&render::command(command, content)?)?;
+    } else if render::is_markdown(&item.path) {
+        print!("{}", render::terminal(&String::from_utf8_lossy(content)));
+    } else {
+        print!("{}", String::from_utf8_lossy(content));
+    }
+    if !raw {
+        images::show(config, item)?;
+    }
+    Ok(())
+}
 
-    let db_files = get_db_files(conn, true).await?;
-    let mut left: HashSet<&File> = db_files.iter().filter(|f| !f.is_deleted()).collect();
-
-    for entry in entries {
-        let metadata = entry.metadata().unwrap();
-        let modified: DateTime<Utc> = metadata.modified().unwrap().into();
-
-        let full_path = entry.path().to_path_buf();
-        let path = full_path.strip_prefix(PATH).unwrap();
-        let path_str = path.to_str().unwrap();
-
-        let db_file = db_files.iter().find(|f| f.path == path);
-
-        match db_file {
-            None => {
-                query!(
-                    r#"
-                    INSERT INTO entries
-                        (path)
-                    VALUES
-                        (?1)
-                    "#,
-                    path_str,
-                )
-                .execute(conn.borrow_mut())
-                .await?;
-            }
-            Some(db_file) if db_file.is_deleted() => {
-                // TODO: make this a warning
-                panic!("file already exists in database as deleted");
+/// The entry as printed by show --format json, times in seconds since the
+/// epoch.
+fn entry_json(item: &File, rank: Option<usize>) -> serde_json::Value {
+    serde_json::json!({
+        "rank": rank,
+        "id": item.id,
+        "path": path_str(&item.path),
+        "title": item.title,
+        "status": item.status().to_string(),
+        "rating": item.rating.rating,
+        "deviation": item.rating.deviation,
+        "score": item.score(),
+        "votes": item.votes,
+        "tags": item.tags(),
+        "created_at": item.created_at().timestamp(),
+        "edited_at": item.last_content().at.timestamp(),
+        "last_vote_at": item.last_vote_at.map(|at| at.timestamp()),
+    })
+}
+
+/// Keeps only the best ranked alternative of every decision in `rows`,
+/// noting how many others there are in its title.
+fn collapse_alternatives(rows: &[(usize, &File)]) -> Vec<(usize, File)> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for (_, item) in rows {
+        if let Some(decision) = item.alternative_for() {
+            *counts.entry(decision).or_default() += 1;
+        }
+    }
+
+    let mut seen = HashSet::new();
+    rows.iter()
+        .filter_map(|&(i, item)| match item.alternative_for() {
+            None => Some((i, item.clone())),
+            Some(decision) if seen.insert(decision.clone()) => {
+                let title = match counts[&decision] {
+                    1 => format!("{} ({})", item.title, decision),
+                    n => format!("{} ({}, best of {})", item.title, decision, n),
+                };
+                Some((
+                    i,
+                    File {
+                        title,
+                        ..item.clone()
+                    },
+                ))
             }
-            Some(db_file) => {
-                left.remove(&db_file);
+            Some(_) => None,
+        })
+        .collect()
+}
 
-                let outdated = modified > db_file.last_content().at;
-                if !outdated {
-                    continue;
-                }
+fn print_chart(config: &Config, ranking: &[&File], marked: Option<&File>) {
+    let ratings: Vec<_> = ranking.iter().map(|f| f.rating.rating).collect();
+    let (low, high) = config.clamp.bounds(&ratings);
+    let ratings: Vec<_> = ratings.iter().map(|r| r.clamp(low, high)).collect();
+    let marked = marked.map(|f| f.rating.rating.clamp(low, high));
+    for line in chart::histogram(&ratings, marked) {
+        println!("{}", line);
+    }
+}
+
+async fn show(conn: &mut SqliteConnection, config: &Config, args: &ShowArgs) -> Result<()> {
+    for filter in &args.filter {
+        filter.check(config)?;
+    }
+    let items = get_db_files(conn, config).await?;
+    let ranking = ranking(&items);
+    let situation = args.situation();
+
+    let Some(entry) = &args.entry else {
+        let rows: Vec<_> = ranking
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| args.filter.iter().all(|filter| filter.matches(item)))
+            .filter(|(_, item)| {
+                args.waiting_on
+                    .as_ref()
+                    .is_none_or(|name| item.waits_on(name))
+            })
+            .filter(|(_, item)| situation.allows(item))
+            .map(|(i, item)| (i + 1, *item))
+            .collect();
+        let collapsed = if args.alternatives {
+            None
+        } else {
+            Some(collapse_alternatives(&rows))
+        };
+        let rows: Vec<_> = match &collapsed {
+            Some(collapsed) => collapsed.iter().map(|(i, item)| (*i, item)).collect(),
+            None => rows,
+        };
+        if config.format == Format::Json {
+            let rows: Vec<_> = rows
+                .iter()
+                .map(|(i, item)| entry_json(item, Some(*i)))
+                .collect();
+            println!("{}", serde_json::Value::Array(rows));
+            return Ok(());
+        }
+        let scores: Vec<_> = ranking.iter().map(|f| f.score()).collect();
+        table::print(config, &rows, &args.columns, config.clamp.bounds(&scores))?;
+        if args.chart {
+            print_chart(config, &ranking, None);
+        }
+        return Ok(());
+    };
+
+    let item = find_entry(&items, config, entry)?;
+    let rank = ranking.iter().position(|f| f.path == item.path);
+    if config.format == Format::Json {
+        let json = match (args.context, rank) {
+            (None, _) => {
+                let mut json = entry_json(item, rank.map(|i| i + 1));
+                json["fields"] = serde_json::json!(item.fields);
+                json["content"] = String::from_utf8_lossy(&item.last_content().content).into();
+                json
+            }
+            (Some(_), None) => return Err(not_ranked(item)),
+            (Some(context), Some(i)) => {
+                let end = (i + context + 1).min(ranking.len());
+                let start = i.saturating_sub(context);
+                (start..end)
+                    .map(|j| entry_json(ranking[j], Some(j + 1)))
+                    .collect()
             }
+        };
+        println!("{}", json);
+        return Ok(());
+    }
+    if args.chart {
+        print_chart(config, &ranking, Some(item));
+        println!();
+    }
+    match (args.context, rank) {
+        (None, _) => show_one(config, item, rank, args.raw)?,
+        (Some(_), None) => return Err(not_ranked(item)),
+        (Some(context), Some(i)) => {
+            let end = (i + context + 1).min(ranking.len());
+            for (j, item) in ranking
+                .iter()
+                .enumerate()
+                .take(end)
+                .skip(i.saturating_sub(context))
+            {
+                print!("{}", if i == j { "> " } else { "  " });
+                print_ranked(j + 1, item);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn next(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    situation: &context::Situation,
+) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    let ranking = ranking(&items);
+    match ranking.iter().position(|item| situation.allows(item)) {
+        Some(i) => show_one(config, ranking[i], Some(i), false)?,
+        None => println!("nothing to do in this situation"),
+    }
+    Ok(())
+}
+
+/// Asks for an optional reason when running interactively.
+fn ask_reason() -> Result<Option<String>> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(None);
+    }
+
+    let reason: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Reason (optional)")
+        .allow_empty(true)
+        .interact_text()?;
+    let reason = reason.trim();
+    Ok((!reason.is_empty()).then(|| reason.to_string()))
+}
+
+async fn change_status(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    args: &StatusArgs,
+    status: Status,
+) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    let mut changed: Vec<&File> = vec![];
+    for entry in &args.entries {
+        let item = find_entry(&items, config, entry)?;
+        if changed.iter().any(|f| f.path == item.path) {
+            continue;
         }
 
-        let bytes = fs::read(&full_path).await?;
-
-        match db_file {
-            Some(f) if f.last_content().content.as_ref() == Some(&bytes) => continue,
-            None | Some(_) => {
-                let ts = modified.timestamp();
-
-                query!(
-                    r#"
-                    INSERT INTO file_contents
-                        (path, content, at)
-                    VALUES
-                        (?1, ?2, ?3)
-                    "#,
-                    path_str,
-                    bytes,
-                    ts
-                )
-                .execute(conn.borrow_mut())
-                .await?;
+        // A deleted entry can only come back if its file did.
+        let restored = status == Status::Active && config.locate(path_str(&item.path)).exists();
+        if let Some(last) = item.status_history.last() {
+            if (last.status == Status::Deleted && !restored) || last.status == status {
+                let reason = last.reason.as_deref().unwrap_or("no reason given");
+                return Err(error::conflict(format!(
+                    "{} is already {} (since {} by {}: {})",
+                    path_str(&item.path),
+                    last.status,
+                    last.at.format("%Y-%m-%d"),
+                    last.actor,
+                    reason
+                )));
             }
         }
+        changed.push(item);
     }
 
-    for db_file in left {
-        let path = path_str(&db_file.path);
-        let ts = Utc::now().timestamp(); // REVIEW: is there a way to get the time of deletion?
+    let reason = match &args.reason {
+        Some(reason) => Some(reason.clone()),
+        None if matches!(status, Status::Done | Status::Dropped | Status::Deleted) => ask_reason()?,
+        None => None,
+    };
 
-        query!(
-            r#"
-            INSERT INTO file_contents
-                (path, content, at)
-            VALUES
-                (?1, NULL, ?2)
-            "#,
-            path,
-            ts
-        )
-        .execute(conn.borrow_mut())
-        .await?;
+    let actor = actor();
+    let mut tx = conn.begin().await?;
+    let mut changes = vec![];
+    for item in &changed {
+        let path = path_str(&item.path);
+        if status == Status::Deleted {
+            quarantine::put(config, path).await?;
+        }
+        set_status(&mut tx, path, status, &actor, reason.as_deref()).await?;
+        changes.push(Change::Status {
+            path: path.to_string(),
+            from: item.status(),
+            to: status,
+            reason: reason.clone(),
+        });
     }
+    let change = match changes.len() {
+        1 => changes.pop().unwrap(),
+        _ => Change::Bulk { changes },
+    };
+    audit::record(&mut tx, &change, &actor).await?;
+    tx.commit().await?;
 
+    for item in &changed {
+        println!("{} is now {}", path_str(&item.path), status);
+    }
     Ok(())
 }
 
-#[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
-struct Cli {
-    #[command(subcommand)]
-    command: Option<Commands>,
+async fn restore_entry(conn: &mut SqliteConnection, config: &Config, entry: &str) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    let item = find_entry(&items, config, entry)?;
+    let path = path_str(&item.path);
+    if !item.is_deleted() {
+        return Err(error::conflict(format!("{} isn't deleted", path)));
+    }
+    let full_path = config.locate(path);
+    if full_path.exists() {
+        return Err(error::conflict(format!(
+            "{} exists again, make it active with prio activate",
+            full_path.display()
+        )));
+    }
+
+    // The quarantined file can be newer than what the database has.
+    match quarantine::find(config, path).await? {
+        Some(quarantined) => quarantine::restore(config, &quarantined).await?,
+        None => {
+            if let Some(parent) = full_path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            fs::write(&full_path, &item.last_content().content).await?;
+        }
+    }
+
+    let actor = actor();
+    let reason = Some("restored".to_string());
+    set_status(conn, path, Status::Active, &actor, reason.as_deref()).await?;
+    let change = Change::Status {
+        path: path.to_string(),
+        from: Status::Deleted,
+        to: Status::Active,
+        reason,
+    };
+    audit::record(conn, &change, &actor).await?;
+
+    println!("restored {}", full_path.display());
+    Ok(())
 }
 
-#[derive(Subcommand, Debug, Clone)]
-enum Commands {
-    Vote,
-    Show,
+async fn purge(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    entry: &str,
+    delete_file: bool,
+    yes: bool,
+) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    let item = find_entry(&items, config, entry)?;
+    let path = path_str(&item.path).to_string();
+    let full_path = config.locate(&path);
+    // Otherwise the next sync would add it again.
+    let file = full_path.exists();
+    if file && !delete_file {
+        return Err(error::conflict(format!(
+            "{} still has its file {}, remove it first or use --delete-file",
+            item,
+            full_path.display()
+        )));
+    }
+
+    if !yes {
+        if !std::io::stdin().is_terminal() {
+            return Err(error::invalid(
+                "purge can't ask for confirmation, use --yes",
+            ));
+        }
+        let mut prompt = format!(
+            "Delete {} with {} revisions and {} votes for good",
+            item,
+            item.file_contents.len(),
+            item.votes
+        );
+        if file {
+            prompt.push_str(&format!(", and delete {}", full_path.display()));
+        }
+        let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("{}?", prompt))
+            .interact_on(&Term::stderr())?;
+        if !confirmed {
+            return Ok(());
+        }
+    }
+
+    let mut tx = conn.begin().await?;
+    let (revisions, votes) = schema::purge_entry(&mut tx, &path).await?;
+    let change = Change::Purge {
+        path: path.clone(),
+        revisions,
+        votes,
+    };
+    audit::record(&mut tx, &change, &actor()).await?;
+    tx.commit().await?;
+
+    if file {
+        match fs::remove_file(&full_path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    while let Some(quarantined) = quarantine::find(config, &path).await? {
+        quarantine::purge(config, &quarantined).await?;
+    }
+
+    println!("{}", change);
+    Ok(())
 }
 
-async fn vote(conn: &mut SqliteConnection) -> Result<()> {
-    loop {
-        let items = get_db_files(conn, false).await?;
-        let items = VecDeque::from(items);
-        let items = take_n(items, 2);
+/// Renames the file of an entry and the entry with it. The file is moved back
+/// if updating the database fails.
+async fn move_entry(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    entry: &str,
+    to: &str,
+) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    let item = find_entry(&items, config, entry)?;
+    if item.is_deleted() {
+        return Err(error::conflict(format!("{} is deleted", item)));
+    }
+    let from = path_str(&item.path).to_string();
+    let to = path_str(&config.resolve(to)).to_string();
+    if from == to {
+        return Err(error::invalid(format!("{} is already at {}", item, to)));
+    }
+    if items.iter().any(|f| path_str(&f.path) == to) {
+        return Err(error::conflict(format!("{} is already an entry", to)));
+    }
 
-        let selection = FuzzySelect::with_theme(&ColorfulTheme::default())
-            .items(&items)
-            .default(0)
-            .interact_on_opt(&Term::stderr())
-            .unwrap();
-        let Some(selection) = selection else { break };
+    let (old, new) = (config.locate(&from), config.locate(&to));
+    if fs::try_exists(&new).await? {
+        return Err(error::conflict(format!("{} already exists", new.display())));
+    }
+    if let Some(parent) = new.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::rename(&old, &new).await?;
 
-        let other = [1, 0][selection];
-        competition(conn, &items[selection].path, &items[other].path).await?;
+    let change = Change::Rename {
+        from: from.clone(),
+        to: to.clone(),
+    };
+    let res: Result<()> = async {
+        let mut tx = conn.begin().await?;
+        schema::move_entry(&mut tx, &from, &to, false).await?;
+        let root = config.split_root(&to).0;
+        query!("UPDATE entries SET root = ?2 WHERE path = ?1", to, root)
+            .execute(&mut *tx)
+            .await?;
+        audit::record(&mut tx, &change, &actor()).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+    .await;
+    if let Err(e) = res {
+        // Otherwise the next sync would see the entry as deleted.
+        if let Err(back) = fs::rename(&new, &old).await {
+            return Err(e.context(format!(
+                "moving {} back to {} failed too: {}",
+                new.display(),
+                old.display(),
+                back
+            )));
+        }
+        return Err(e);
     }
 
+    println!("{}", change);
     Ok(())
 }
 
-async fn show(conn: &mut SqliteConnection) -> Result<()> {
-    let items = get_db_files(conn, false).await?;
-    for (i, item) in items.into_iter().rev().enumerate() {
-        println!(
-            "{}. {} (score: {}, deviation: {})",
-            i + 1,
-            item,
-            item.rating.rating as i64,
-            item.rating.deviation as i64
-        );
+async fn edit_entry(conn: &mut SqliteConnection, config: &Config, entry: &str) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    let item = find_entry(&items, config, entry)?;
+    if item.is_deleted() {
+        return Err(error::conflict(format!("{} is deleted", item)));
+    }
+    let path = path_str(&item.path);
+    edit(&config.locate(path))?;
+
+    track(conn, config, Some(item), path).await?;
+    let versions = get_db_files(conn, config)
+        .await?
+        .into_iter()
+        .find(|f| f.path == item.path)
+        .map_or(0, |f| f.file_contents.len());
+    if versions > item.file_contents.len() {
+        println!("stored version {} of {}", versions, item);
+    } else {
+        println!("{} is unchanged", item);
     }
     Ok(())
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
-    let command = cli.command.unwrap_or(Commands::Show);
+/// Lets the user write a new entry, starting from `text`, in a temporary
+/// file so nothing is left behind if they give up. Returns what they wrote.
+async fn edit_new(config: &Config, text: &str) -> Result<String> {
+    let extension = config.walk.extensions.first().map_or("md", |e| e.as_str());
+    let path = std::env::temp_dir().join(format!("prio-add-{}.{}", std::process::id(), extension));
+    fs::write(&path, text).await?;
+    let res = edit(&path);
+    let content = fs::read_to_string(&path).await;
+    fs::remove_file(&path).await?;
+    res?;
+    Ok(content?)
+}
 
-    Builder::new_current_thread().build()?.block_on(async {
-        //let mut rng = thread_rng();
-        let mut conn = SqliteConnection::connect(DB_PATH).await?;
+/// Opens `path` in $VISUAL, $EDITOR or else vi and waits for it to exit.
+fn edit(path: &Path) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let words = split_words(&editor)?;
+    let (program, args) = words
+        .split_first()
+        .ok_or_else(|| anyhow!("editor is empty"))?;
+
+    let status = std::process::Command::new(program)
+        .args(args)
+        .arg(path)
+        .status()
+        .map_err(|e| anyhow!("starting {}: {}", program, e))?;
+    if !status.success() {
+        return Err(anyhow!("{} failed with {}", program, status));
+    }
+    Ok(())
+}
+
+/// Active entries with a title that is nearly the same as `title`, ignoring
+/// case and whitespace.
+fn similar_titles<'a>(items: &'a [File], title: &str) -> Vec<&'a File> {
+    let normalize = |s: &str| {
+        s.split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase()
+    };
+    let title = normalize(title);
+    if title.is_empty() {
+        return vec![];
+    }
+    ranking(items)
+        .into_iter()
+        .filter(|f| strsim::normalized_damerau_levenshtein(&normalize(&f.title), &title) >= 0.85)
+        .collect()
+}
 
-        update_files(&mut conn).await?;
+/// A path in the entries directory for a new entry with `text`, that isn't
+/// taken yet.
+fn new_entry_path(config: &Config, text: &str, date: bool) -> String {
+    let extension = config.walk.extensions.first().map_or("md", |e| e.as_str());
+    let mut stem = slugify(text);
+    if date {
+        stem = format!("{}-{}", Local::now().format("%Y-%m-%d"), stem);
+    }
 
-        match command {
-            Commands::Vote => vote(&mut conn).await?,
-            Commands::Show => show(&mut conn).await?,
+    let mut path = format!("{}.{}", stem, extension);
+    for n in 2.. {
+        if !config.locate(&path).exists() {
+            break;
         }
+        path = format!("{}-{}.{}", stem, n, extension);
+    }
+    path
+}
 
-        Ok(())
+async fn add(conn: &mut SqliteConnection, config: &Config, args: &AddArgs) -> Result<()> {
+    let text = match args.text.as_deref() {
+        Some("-") => Some(std::io::read_to_string(std::io::stdin())?),
+        Some(text) => Some(format!("{}\n", text.trim_end())),
+        None => None,
+    };
+    let content = match text {
+        Some(text) if !args.edit => text,
+        text => edit_new(config, text.as_deref().unwrap_or("")).await?,
+    };
+    if content.trim().is_empty() {
+        println!("nothing added");
+        return Ok(());
+    }
+
+    let path = match &args.path {
+        Some(path) => normalize_path(path),
+        None => {
+            let first_line = content.lines().find(|l| !l.trim().is_empty());
+            new_entry_path(config, first_line.unwrap_or_default(), args.date)
+        }
+    };
+    let full_path = config.locate(&path);
+    if full_path.exists() {
+        return Err(error::conflict(format!(
+            "{} already exists",
+            full_path.display()
+        )));
+    }
+
+    let items = get_db_files(conn, config).await?;
+    let title = make_title(config, Path::new(&path), &content);
+    let similar = similar_titles(&items, &title);
+    if !similar.is_empty() {
+        eprintln!("{:?} looks like an existing entry:", title);
+        for item in &similar {
+            eprintln!("  {}", item);
+        }
+        let open_existing = std::io::stdin().is_terminal()
+            && Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt("Edit the existing entry instead?")
+                .default(false)
+                .interact_on(&Term::stderr())?;
+        if open_existing {
+            let existing = match similar.len() {
+                1 => 0,
+                _ => match pick(&similar) {
+                    Some(i) => i,
+                    None => return Ok(()),
+                },
+            };
+            return edit(&config.locate(path_str(&similar[existing].path)));
+        }
+    }
+
+    if let Some(parent) = full_path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::write(&full_path, &content).await?;
+    if let Err(e) = track(conn, config, None, &path).await {
+        fs::remove_file(&full_path).await?;
+        return Err(e);
+    }
+    if args.place && std::io::stdin().is_terminal() {
+        let items = get_db_files(conn, config).await?;
+        let item = find_entry(&items, config, &path)?;
+        place(conn, config, item, &items).await?;
+    }
+
+    let items = get_db_files(conn, config).await?;
+    let item = find_entry(&items, config, &path)?;
+    match ranking(&items).iter().position(|f| f.path == item.path) {
+        Some(i) => println!("added #{} at rank {}: {}", item.id, i + 1, item),
+        None => println!("added #{}: {}", item.id, item),
+    }
+    Ok(())
+}
+
+async fn add_url(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    url: &str,
+    text: bool,
+) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    if let Some(item) = items.iter().find(|f| f.url().as_deref() == Some(url)) {
+        return Err(error::conflict(format!(
+            "{} was added already as #{}: {}",
+            url, item.id, item
+        )));
+    }
+
+    let page = web::fetch(url)?;
+    if !page.ok() {
+        return Err(anyhow!("fetching {}: status {}", url, page.status));
+    }
+    let title = web::title(&page.body).unwrap_or_else(|| url.to_string());
+
+    let mut body = format!("{}\n", title);
+    if text {
+        let text = web::text(&page.body);
+        if !text.is_empty() {
+            body = format!("{}\n{}\n", body, text);
+        }
+    }
+    let mut front_matter = serde_yaml::Mapping::new();
+    front_matter.insert("url".into(), url.into());
+    let content = frontmatter::join(&front_matter, &body)?;
+
+    let path = new_entry_path(config, &title, false);
+    let full_path = config.locate(&path);
+    if let Some(parent) = full_path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::write(&full_path, &content).await?;
+    if let Err(e) = track(conn, config, None, &path).await {
+        fs::remove_file(&full_path).await?;
+        return Err(e);
+    }
+
+    let items = get_db_files(conn, config).await?;
+    let item = find_entry(&items, config, &path)?;
+    println!("added #{}: {}", item.id, item);
+    Ok(())
+}
+
+/// Fetches the pages of the active entries added with add-url again, storing
+/// the status and the time in their front matter and taking over changed
+/// titles, unless the title is pinned with `display`. Pages that are gone
+/// are left for autoprune.
+async fn refresh_urls(conn: &mut SqliteConnection, config: &Config) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    let mut dead = 0;
+    for item in ranking(&items) {
+        let Some(url) = item.url() else {
+            continue;
+        };
+        let page = match web::fetch(&url) {
+            Ok(page) => page,
+            Err(e) => {
+                eprintln!("{}: {:#}", item, e);
+                continue;
+            }
+        };
+
+        let path = path_str(&item.path);
+        let content = String::from_utf8_lossy(&item.last_content().content);
+        let (Some(mut front_matter), body) = frontmatter::split(&content) else {
+            continue;
+        };
+        front_matter.insert("http_status".into(), page.status.into());
+        front_matter.insert(
+            "fetched".into(),
+            Local::now().format("%Y-%m-%d %H:%M").to_string().into(),
+        );
+
+        let mut body = body.to_string();
+        let title = web::title(&page.body).filter(|_| page.ok());
+        if let Some(title) = title.filter(|_| !front_matter.contains_key("display")) {
+            let old = body
+                .lines()
+                .find(|l| !l.trim().is_empty())
+                .unwrap_or_default();
+            if old.trim() != title {
+                println!("{}: now titled {:?}", item, title);
+                body = match body.find(old).filter(|_| !old.is_empty()) {
+                    Some(i) => format!("{}{}{}", &body[..i], title, &body[i + old.len()..]),
+                    None => format!("{}\n{}", title, body),
+                };
+            }
+        }
+
+        fs::write(
+            config.locate(path),
+            frontmatter::join(&front_matter, &body)?,
+        )
+        .await?;
+        track(conn, config, Some(item), path).await?;
+        if page.dead() {
+            println!("{}: {} is gone ({})", item, url, page.status);
+            dead += 1;
+        } else if !page.ok() {
+            println!("{}: {} returned {}", item, url, page.status);
+        }
+    }
+    if dead > 0 {
+        println!(
+            "{} dead links, prio autoprune will offer to prune them",
+            dead
+        );
+    }
+    Ok(())
+}
+
+/// Votes on the new entry against the middle of the part of the ranking it
+/// could still end up in, like inserting into a sorted list, so it gets a
+/// meaningful rating after a handful of votes. Stops early if the user quits.
+async fn place(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    item: &File,
+    items: &[File],
+) -> Result<()> {
+    let exclusions = Exclusions::new(config, items);
+    let others: Vec<_> = ranking(items)
+        .into_iter()
+        .filter(|f| f.path != item.path && exclusions.allows(item, f))
+        .collect();
+    let (mut lo, mut hi) = (0, others.len());
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        let other = others[mid];
+        let Some(selection) = pick(&[item, other]) else {
+            break;
+        };
+        if selection == 0 {
+            competition(conn, &item.path, &other.path, None).await?;
+            hi = mid;
+        } else {
+            competition(conn, &other.path, &item.path, None).await?;
+            lo = mid + 1;
+        }
+    }
+    Ok(())
+}
+
+/// The active alternatives for every decision, best ranked first.
+fn alternatives_by_decision<'a>(ranking: &[&'a File]) -> BTreeMap<String, Vec<&'a File>> {
+    let mut decisions: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for item in ranking {
+        if let Some(decision) = item.alternative_for() {
+            decisions.entry(decision).or_default().push(*item);
+        }
+    }
+    decisions
+}
+
+async fn decide(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    decision: Option<&str>,
+) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    let decisions = alternatives_by_decision(&ranking(&items));
+    let Some(decision) = decision else {
+        for (decision, alternatives) in &decisions {
+            println!(
+                "{}: {} alternatives, best: {}",
+                decision,
+                alternatives.len(),
+                alternatives[0]
+            );
+        }
+        return Ok(());
+    };
+    let Some(alternatives) = decisions.get(decision) else {
+        return Err(error::not_found(format!(
+            "no active entry is an alternative for {:?}",
+            decision
+        )));
+    };
+    if alternatives.len() < 2 {
+        return Err(error::conflict(format!(
+            "{} is the only alternative for {:?}",
+            alternatives[0], decision
+        )));
+    }
+
+    // Every pair once, a round robin is small enough for a single decision.
+    let exclusions = Exclusions::new(config, &items);
+    let mut pairs: Vec<_> = alternatives
+        .iter()
+        .enumerate()
+        .flat_map(|(i, a)| alternatives[i + 1..].iter().map(move |b| [*a, *b]))
+        .filter(|[a, b]| exclusions.allows(a, b))
+        .collect();
+    pairs.shuffle(&mut thread_rng());
+    for mut pair in pairs {
+        pair.shuffle(&mut thread_rng());
+        let Some(selection) = pick(&pair) else {
+            break;
+        };
+        let (winner, loser) = (pair[selection], pair[1 - selection]);
+        competition(conn, &winner.path, &loser.path, None).await?;
+    }
+
+    let items = get_db_files(conn, config).await?;
+    let ranking = ranking(&items);
+    let best = alternatives_by_decision(&ranking)[decision][0];
+    let rank = ranking.iter().position(|f| f.path == best.path).unwrap();
+    print!("best alternative for {}: ", decision);
+    print_ranked(rank + 1, best);
+    Ok(())
+}
+
+async fn history(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    entry: &str,
+    show: Option<usize>,
+) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    let item = find_entry(&items, config, entry)?;
+    let versions = &item.file_contents;
+
+    if let Some(k) = show {
+        let Some(version) = k.checked_sub(1).and_then(|i| versions.get(i)) else {
+            return Err(error::not_found(format!(
+                "{} has versions 1 to {}, not {}",
+                item,
+                versions.len(),
+                k
+            )));
+        };
+        print!("{}", String::from_utf8_lossy(&version.content));
+        return Ok(());
+    }
+
+    let mut tw = TabWriter::new(std::io::stdout().lock()).padding(2);
+    for (i, version) in versions.iter().enumerate() {
+        let content = String::from_utf8_lossy(&version.content);
+        let first_line = content.lines().find(|l| !l.trim().is_empty());
+        writeln!(
+            tw,
+            "{}\t{}\t{} bytes\t{}",
+            i + 1,
+            format_time(version.at),
+            version.content.len(),
+            first_line.unwrap_or_default().replace('\t', " ")
+        )?;
+    }
+    tw.flush()?;
+    Ok(())
+}
+
+async fn list_votes(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    entry: Option<&str>,
+    limit: Option<usize>,
+) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    let path = match entry {
+        Some(entry) => Some(path_str(&find_entry(&items, config, entry)?.path).to_string()),
+        None => None,
+    };
+    let by_path: HashMap<&str, &File> = items.iter().map(|f| (path_str(&f.path), f)).collect();
+    let name = |path: &str| match by_path.get(path) {
+        Some(item) => item.to_string(),
+        None => path.to_string(),
+    };
+
+    let mut votes = votes::list(conn, path.as_deref()).await?;
+    if let Some(limit) = limit {
+        votes.drain(..votes.len().saturating_sub(limit));
+    }
+    if config.format == Format::Json {
+        let votes: Vec<_> = votes
+            .into_iter()
+            .map(|vote| {
+                serde_json::json!({
+                    "id": vote.id,
+                    "at": vote.at,
+                    "left": vote.left_path,
+                    "right": vote.right_path,
+                    "vote": vote.vote,
+                    "criterion": vote.criterion,
+                    "confidence": vote.confidence,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::Value::Array(votes));
+        return Ok(());
+    }
+
+    let mut tw = TabWriter::new(std::io::stdout().lock()).padding(2);
+    for vote in votes {
+        let outcome = match vote.vote {
+            0 => "drew with",
+            ..=-1 => "lost to",
+            1.. => "beat",
+        };
+        write!(
+            tw,
+            "{}\t{}\t{}\t{}\t{}",
+            vote.id,
+            format_time(Utc.timestamp_opt(vote.at, 0).unwrap()),
+            name(&vote.left_path),
+            outcome,
+            name(&vote.right_path)
+        )?;
+        let notes: Vec<_> = vote.criterion.into_iter().chain(vote.confidence).collect();
+        if !notes.is_empty() {
+            write!(tw, "\t{}", notes.join(", "))?;
+        }
+        writeln!(tw)?;
+    }
+    tw.flush()?;
+    Ok(())
+}
+
+/// Applies `command` to a vote and prints how the ratings of the two entries
+/// changed because of it.
+async fn change_vote(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    command: VotesCommand,
+) -> Result<()> {
+    let id = match command {
+        VotesCommand::Edit { id, .. } | VotesCommand::Delete { id } => id,
+    };
+    let Some(stored) = votes::get(conn, id).await? else {
+        return Err(error::not_found(format!(
+            "there is no vote {}, see prio votes",
+            id
+        )));
+    };
+    let before = get_db_files(conn, config).await?;
+
+    let change = match command {
+        VotesCommand::Edit { outcome, .. } => {
+            let vote = outcome.vote();
+            if vote == stored.vote {
+                println!("vote {} has that outcome already", id);
+                return Ok(());
+            }
+            votes::set_outcome(conn, id, vote).await?;
+            Change::VoteEdit {
+                vote_id: id,
+                from: stored.vote,
+                to: vote,
+            }
+        }
+        VotesCommand::Delete { .. } => {
+            votes::delete(conn, id).await?;
+            Change::VoteDelete {
+                vote_id: id,
+                winner: stored.left_path.clone(),
+                loser: stored.right_path.clone(),
+                vote: stored.vote,
+                at: stored.at,
+                criterion: stored.criterion,
+                confidence: stored.confidence.map(|c| c.parse()).transpose()?,
+            }
+        }
+    };
+    audit::record(conn, &change, &util::actor()).await?;
+    println!("{}", change);
+
+    let after = get_db_files(conn, config).await?;
+    for path in [&stored.left_path, &stored.right_path] {
+        let find = |items: &[File]| items.iter().find(|f| path_str(&f.path) == path).cloned();
+        if let (Some(old), Some(new)) = (find(&before), find(&after)) {
+            println!("{}: {:.0} -> {:.0}", new, old.score(), new.score());
+        }
+    }
+    Ok(())
+}
+
+async fn checkout(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    entry: &str,
+    version: usize,
+) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    let item = find_entry(&items, config, entry)?;
+    if item.is_deleted() {
+        return Err(error::conflict(format!(
+            "{} is deleted, undo or prio quarantine review can bring it back",
+            item
+        )));
+    }
+    let versions = &item.file_contents;
+    let Some(stored) = version.checked_sub(1).and_then(|i| versions.get(i)) else {
+        return Err(error::not_found(format!(
+            "{} has versions 1 to {}, not {}",
+            item,
+            versions.len(),
+            version
+        )));
+    };
+    if stored.content == item.last_content().content {
+        println!("{} has the content of version {} already", item, version);
+        return Ok(());
+    }
+
+    // Syncing before every command means the file has no unstored changes.
+    let path = path_str(&item.path);
+    fs::write(config.locate(path), &stored.content).await?;
+    track(conn, config, Some(item), path).await?;
+    println!(
+        "restored version {} of {} as version {}",
+        version,
+        item,
+        versions.len() + 1
+    );
+    Ok(())
+}
+
+async fn diff_versions(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    entry: &str,
+    from: Option<usize>,
+    to: Option<usize>,
+) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    let item = find_entry(&items, config, entry)?;
+    let versions = &item.file_contents;
+
+    let to = to.unwrap_or(versions.len());
+    let from = from.unwrap_or(to.saturating_sub(1));
+    let version = |k: usize| {
+        k.checked_sub(1)
+            .and_then(|i| versions.get(i))
+            .ok_or_else(|| {
+                error::not_found(format!(
+                    "{} has versions 1 to {}, not {}",
+                    item,
+                    versions.len(),
+                    k
+                ))
+            })
+    };
+    let (old, new) = (version(from)?, version(to)?);
+
+    let old_text = String::from_utf8_lossy(&old.content);
+    let new_text = String::from_utf8_lossy(&new.content);
+    let lines = diff::unified(&old_text, &new_text, 3);
+    if lines.is_empty() {
+        println!("versions {} and {} are the same", from, to);
+        return Ok(());
+    }
+
+    let path = path_str(&item.path);
+    let header = |sign: &str, k: usize, at: DateTime<Utc>| {
+        format!("{} {} (version {}, {})", sign, path, k, format_time(at))
+    };
+    println!("{}", style(header("---", from, old.at)).bold());
+    println!("{}", style(header("+++", to, new.at)).bold());
+    for line in lines {
+        let styled = match line.chars().next() {
+            Some('@') => style(line).cyan(),
+            Some('-') => style(line).red(),
+            Some('+') => style(line).green(),
+            _ => style(line),
+        };
+        println!("{}", styled);
+    }
+    Ok(())
+}
+
+async fn set_field(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    entry: &str,
+    field: &str,
+    value: Option<&str>,
+) -> Result<()> {
+    let ty = config.field(field)?;
+    let value = value.map(|v| ty.parse(v)).transpose()?;
+
+    let items = get_db_files(conn, config).await?;
+    let item = find_entry(&items, config, entry)?;
+    fields::set(
+        conn,
+        path_str(&item.path),
+        field,
+        value.as_deref(),
+        &actor(),
+    )
+    .await?;
+
+    if item
+        .front_matter_fields(config)
+        .iter()
+        .any(|(name, _)| name == field)
+    {
+        eprintln!(
+            "warning: the front matter of {} sets {}, which takes precedence",
+            item, field
+        );
+    }
+    Ok(())
+}
+
+fn not_ranked(item: &File) -> anyhow::Error {
+    error::conflict(format!(
+        "{} is {}, only active entries are ranked",
+        path_str(&item.path),
+        item.status()
+    ))
+}
+
+fn print_ranked(rank: usize, item: &File) {
+    println!(
+        "{}. {} (score: {}, deviation: {}, {} words, {} min)",
+        rank,
+        item,
+        item.score() as i64,
+        item.rating.deviation as i64,
+        item.words(),
+        item.reading_minutes()
+    );
+}
+
+async fn rank_of(conn: &mut SqliteConnection, config: &Config, entry: &str) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    let item = find_entry(&items, config, entry)?;
+    if !item.is_active() {
+        return Err(not_ranked(item));
+    }
+
+    let ranking = ranking(&items);
+    let i = ranking.iter().position(|f| f.path == item.path).unwrap();
+    let below = ranking.len() - i - 1;
+
+    println!("id: #{}", item.id);
+    println!("rank: {} of {}", i + 1, ranking.len());
+    println!("score: {}", item.score() as i64);
+    println!("deviation: {}", item.rating.deviation as i64);
+    println!("percentile: {}", below * 100 / ranking.len());
+    if i > 0 {
+        print!("above: ");
+        print_ranked(i, ranking[i - 1]);
+    }
+    if below > 0 {
+        print!("below: ");
+        print_ranked(i + 2, ranking[i + 1]);
+    }
+    Ok(())
+}
+
+/// How a single vote changed the rating of the explained entry.
+struct Step<'a> {
+    opponent: &'a File,
+    /// 1 for a win, 0 for a draw and -1 for a loss.
+    result: i64,
+    before: f64,
+    after: f64,
+    at: DateTime<Utc>,
+}
+
+async fn explain(conn: &mut SqliteConnection, config: &Config, entry: &str) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    let item = find_entry(&items, config, entry)?;
+    let me = items.iter().position(|f| f.path == item.path).unwrap();
+    let votes = index_votes(config, &items, &counted_votes(conn, config).await?);
+    let params = rating::live(conn).await?;
+    let now = Utc::now();
+
+    // Replays all votes, as every vote moves the ratings the later ones are
+    // computed from.
+    let bumps = edit_bumps(config, &items);
+    let mut bumps = bumps.as_slice();
+    let mut ratings = vec![Rating::new(); items.len()];
+    let mut steps = vec![];
+    let mut weights = vec![];
+    for (vote, at, weight) in &votes {
+        rating::apply_bumps(&mut ratings, &mut bumps, Some(*at));
+        let before = ratings[me].rating;
+        params.apply(&mut ratings, vote, *at, now, *weight);
+        let (opponent, result) = match (vote.left == me, vote.right == me) {
+            (true, _) => (vote.right, vote.vote.signum()),
+            (_, true) => (vote.left, -vote.vote.signum()),
+            _ => continue,
+        };
+        steps.push(Step {
+            opponent: &items[opponent],
+            result,
+            before,
+            after: ratings[me].rating,
+            at: *at,
+        });
+        weights.push((*weight, params.decay(*at, now)));
+    }
+
+    let ranking = ranking(&items);
+    match ranking.iter().position(|f| f.path == item.path) {
+        Some(i) => println!("{}: rank {} of {}", item, i + 1, ranking.len()),
+        None => println!("{}: not ranked", item),
+    }
+    println!("score: {:.0} ± {:.0}", item.score(), item.rating.deviation);
+    if item.inherited.offset != 0.0 {
+        println!(
+            "  of which {:+.0} is an offset from .prio.toml",
+            item.inherited.offset
+        );
+    }
+
+    let count = |result| steps.iter().filter(|s| s.result == result).count();
+    println!(
+        "votes: {} wins, {} losses, {} draws",
+        count(1),
+        count(-1),
+        count(0)
+    );
+    if steps.is_empty() {
+        println!("it was never voted on, so it has the initial rating");
+        return Ok(());
+    }
+
+    let mut beaten: Vec<_> = steps.iter().filter(|s| s.result > 0).collect();
+    beaten.sort_by(|a, b| b.opponent.score().total_cmp(&a.opponent.score()));
+    if !beaten.is_empty() {
+        println!("strongest opponents beaten:");
+        for step in beaten.iter().take(3) {
+            println!("  {:>5.0}  {}", step.opponent.score(), step.opponent);
+        }
+    }
+
+    let mut losses: Vec<_> = steps.iter().filter(|s| s.result < 0).collect();
+    losses.sort_by(|a, b| (a.after - a.before).total_cmp(&(b.after - b.before)));
+    if !losses.is_empty() {
+        println!("most damaging losses:");
+        for step in losses.iter().take(3) {
+            println!("  {:>+5.0}  {}", step.after - step.before, step.opponent);
+        }
+    }
+
+    if params.half_life.is_some() {
+        let average = weights.iter().map(|(_, decay)| decay).sum::<f64>() / weights.len() as f64;
+        let oldest = weights.iter().map(|(_, decay)| *decay).fold(1.0, f64::min);
+        println!(
+            "decay: its votes count for {:.0}% on average, the oldest for {:.0}%",
+            average * 100.0,
+            oldest * 100.0
+        );
+    }
+    let leaning = weights.iter().filter(|(weight, _)| *weight < 1.0).count();
+    if leaning > 0 {
+        println!(
+            "{} of its votes were leaning and counted for {:.0}%",
+            leaning,
+            config.leaning_weight * 100.0
+        );
+    }
+
+    println!("last rating changes:");
+    for step in steps.iter().rev().take(5) {
+        let result = match step.result {
+            1 => "won against",
+            0 => "drew with",
+            _ => "lost to",
+        };
+        println!(
+            "  {}  {:.0} -> {:.0}  {} {}",
+            format_time(step.at),
+            step.before,
+            step.after,
+            result,
+            step.opponent
+        );
+    }
+    Ok(())
+}
+
+async fn log(conn: &mut SqliteConnection, since: Option<&str>) -> Result<()> {
+    let since = match since {
+        Some(since) => parse_since(since)?,
+        None => DateTime::<Utc>::MIN_UTC,
+    };
+
+    for entry in audit::entries_since(conn, since).await? {
+        println!(
+            "{:>5}  {}  {:<8}  {}{}",
+            entry.id,
+            format_time(entry.at),
+            entry.actor,
+            entry.change,
+            if entry.undone { " (undone)" } else { "" }
+        );
+    }
+    Ok(())
+}
+
+async fn run(conn: &mut SqliteConnection, config: &Config, command: Commands) -> Result<()> {
+    match command {
+        Commands::Add(args) => add(conn, config, &args).await?,
+        Commands::AddUrl { url, text } => add_url(conn, config, &url, text).await?,
+        Commands::RefreshUrls => refresh_urls(conn, config).await?,
+        Commands::Vote(args) => vote(conn, config, &args).await?,
+        Commands::Next(situation) => next(conn, config, &situation).await?,
+        Commands::Show(args) => show(conn, config, &args).await?,
+        Commands::Done(args) => change_status(conn, config, &args, Status::Done).await?,
+        Commands::Drop(args) => change_status(conn, config, &args, Status::Dropped).await?,
+        Commands::Snooze(args) => change_status(conn, config, &args, Status::Snoozed).await?,
+        Commands::Remove(args) => change_status(conn, config, &args, Status::Deleted).await?,
+        Commands::Activate(args) => change_status(conn, config, &args, Status::Active).await?,
+        Commands::Restore { entry } => restore_entry(conn, config, &entry).await?,
+        Commands::Purge {
+            entry,
+            delete_file,
+            yes,
+        } => purge(conn, config, &entry, delete_file, yes).await?,
+        Commands::Mv { entry, to } => move_entry(conn, config, &entry, &to).await?,
+        Commands::Log { since } => log(conn, since.as_deref()).await?,
+        Commands::Nudge { entry } => nudge(conn, config, &entry).await?,
+        Commands::RankOf { entry } => rank_of(conn, config, &entry).await?,
+        Commands::Explain { entry } => explain(conn, config, &entry).await?,
+        Commands::Decide { decision } => decide(conn, config, decision.as_deref()).await?,
+        Commands::History { entry, show } => history(conn, config, &entry, show).await?,
+        Commands::Votes {
+            command: Some(command),
+            ..
+        } => change_vote(conn, config, command).await?,
+        Commands::Votes {
+            command: None,
+            entry,
+            limit,
+        } => list_votes(conn, config, entry.as_deref(), limit).await?,
+        Commands::Checkout { entry, version } => checkout(conn, config, &entry, version).await?,
+        Commands::Diff { entry, from, to } => diff_versions(conn, config, &entry, from, to).await?,
+        Commands::Set {
+            entry,
+            field,
+            value,
+        } => set_field(conn, config, &entry, &field, value.as_deref()).await?,
+        Commands::Link {
+            entry,
+            #[cfg(feature = "qr")]
+            qr,
+        } => {
+            let items = get_db_files(conn, config).await?;
+            let link = format!("{}{}", LINK_PREFIX, find_entry(&items, config, &entry)?.id);
+            println!("{}", link);
+            #[cfg(feature = "qr")]
+            if qr {
+                let code = qrcode::QrCode::new(&link)?;
+                println!(
+                    "{}",
+                    code.render::<qrcode::render::unicode::Dense1x2>().build()
+                );
+            }
+        }
+        Commands::Speak { top, print } => {
+            let items = get_db_files(conn, config).await?;
+            let text = speak::digest(&ranking(&items), top);
+            match &config.speak_command {
+                Some(command) if !print => speak::say(command, &text)?,
+                _ => print!("{}", text),
+            }
+        }
+        Commands::Undo { vote } => match undo::undo(conn, config, vote).await? {
+            Some(change) => println!("undid: {}", change),
+            None => println!("nothing to undo"),
+        },
+        Commands::Redo => match undo::redo(conn, config).await? {
+            Some(change) => println!("redid: {}", change),
+            None => println!("nothing to redo"),
+        },
+        Commands::Batch => batch(conn, config).await?,
+        Commands::Status => status(conn, config).await?,
+        Commands::Init { .. } => return Err(error::invalid("init has to be run on its own")),
+        Commands::Remap { .. } => return Err(error::invalid("remap has to be run on its own")),
+        Commands::Edit { entry } => edit_entry(conn, config, &entry).await?,
+        Commands::Doctor => doctor(conn, config).await?,
+        Commands::Verify => verify(conn, config).await?,
+        Commands::Autoprune { apply } => autoprune(conn, config, apply).await?,
+        Commands::Resurface { limit, list } => resurface(conn, config, limit, list).await?,
+        Commands::Merge { db, strategy } => merge_db(conn, config, &db, strategy).await?,
+        Commands::Backup { keep } => {
+            backup(conn, config, keep.unwrap_or(config.backup_keep)).await?
+        }
+        Commands::RestoreBackup { .. } => {
+            return Err(error::invalid("restore-backup has to be run on its own"))
+        }
+        Commands::Dashboard => dashboard(conn, config).await?,
+        Commands::Stats => stats(conn, config).await?,
+        Commands::NeedsVotes { threshold } => needs_votes(conn, config, threshold).await?,
+        Commands::Drift {
+            since,
+            ranks,
+            top,
+            notify,
+        } => {
+            let since = util::parse_duration(&since)?;
+            drift(conn, config, since, ranks, top, notify).await?
+        }
+        Commands::Experiments { command } => experiments(conn, config, command).await?,
+        Commands::Quarantine { command } => quarantine_files(conn, config, command).await?,
+        Commands::Recompute {
+            system,
+            decay,
+            horizon,
+            dry_run,
+            experiment,
+        } => {
+            let seconds = |d: Option<String>| -> Result<Option<i64>> {
+                Ok(d.map(|d| util::parse_duration(&d))
+                    .transpose()?
+                    .map(|d| d.num_seconds()))
+            };
+            let params = rating::Params {
+                system,
+                half_life: seconds(decay)?,
+                horizon: seconds(horizon)?,
+            };
+            recompute(conn, config, &params, dry_run, experiment.as_deref()).await?
+        }
+        #[cfg(feature = "export")]
+        Commands::Share { tags, out } => share(conn, config, &tags, &out).await?,
+        #[cfg(feature = "gui")]
+        Commands::Gui => return Err(error::invalid("gui has to be started on its own")),
+        #[cfg(feature = "export")]
+        Commands::Export { all: true, .. } => export_all(conn, config).await?,
+        #[cfg(feature = "export")]
+        Commands::Export {
+            graph: Some(graph), ..
+        } => export_graph(conn, config, graph).await?,
+        #[cfg(feature = "export")]
+        Commands::Export {
+            graph: None,
+            deleted,
+            ..
+        } => export_ranking(conn, config, deleted).await?,
+        #[cfg(feature = "import")]
+        Commands::Import { .. } => return Err(error::invalid("import has to be run on its own")),
+        #[cfg(feature = "import")]
+        Commands::ImportVotes {
+            file,
+            map,
+            create_stubs,
+        } => import_votes(conn, config, &file, map.as_deref(), create_stubs).await?,
+    }
+    Ok(())
+}
+
+async fn autoprune(conn: &mut SqliteConnection, config: &Config, apply: bool) -> Result<()> {
+    let Some(policy) = &config.autoprune else {
+        return Err(error::invalid("no autoprune policy in the config"));
+    };
+    if !matches!(policy.status, Status::Snoozed | Status::Dropped) {
+        return Err(error::invalid("autoprune can only snooze or drop entries"));
+    }
+
+    let items = get_db_files(conn, config).await?;
+    let ranking = ranking(&items);
+    let now = Utc::now();
+    let cutoff = now - chrono::Duration::days(30 * policy.months as i64);
+
+    // Being in the bottom is checked now and at the end of every month
+    // before, with the ranking as it was then. Dead links are pruned
+    // wherever they are, see prio refresh-urls.
+    let mut bottom = bottom_tenth(&items, now);
+    for month in 1..=policy.months as i64 {
+        let then = now - chrono::Duration::days(30 * month);
+        let rated = rated_at(conn, config, &items, then).await?;
+        let was_bottom = bottom_tenth(&rated, then);
+        bottom.retain(|path| was_bottom.contains(path));
+    }
+    let candidates: Vec<_> = ranking
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| {
+            item.dead_link() || (bottom.contains(&item.path) && item.last_activity() < cutoff)
+        })
+        .collect();
+
+    if candidates.is_empty() {
+        println!("nothing to prune");
+        return Ok(());
+    }
+    for (i, item) in &candidates {
+        if item.dead_link() {
+            println!("{}. {} (dead link)", i + 1, item);
+        } else {
+            println!(
+                "{}. {} (last activity {})",
+                i + 1,
+                item,
+                format_time(item.last_activity())
+            );
+        }
+    }
+    if !apply {
+        return Ok(());
+    }
+
+    let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!(
+            "Mark these {} entries as {}?",
+            candidates.len(),
+            policy.status
+        ))
+        .interact_on(&Term::stderr())?;
+    if !confirmed {
+        return Ok(());
+    }
+
+    let stale = format!(
+        "autoprune: bottom tenth without activity for {} months",
+        policy.months
+    );
+    for (_, item) in candidates {
+        let reason = match item.dead_link() {
+            true => "autoprune: dead link".to_string(),
+            false => stale.clone(),
+        };
+        let path = path_str(&item.path);
+        set_status(conn, path, policy.status, "autoprune", Some(&reason)).await?;
+
+        let change = Change::Status {
+            path: path.to_string(),
+            from: item.status(),
+            to: policy.status,
+            reason: Some(reason),
+        };
+        audit::record(conn, &change, "autoprune").await?;
+    }
+    Ok(())
+}
+
+async fn resurface(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    limit: usize,
+    list: bool,
+) -> Result<()> {
+    const GRADES: [&str; 6] = [
+        "0: not relevant at all",
+        "1: hardly relevant",
+        "2: barely relevant",
+        "3: somewhat relevant",
+        "4: relevant",
+        "5: very relevant",
+    ];
+
+    let items = get_db_files(conn, config).await?;
+    let ranking = ranking(&items);
+    let schedules = resurface::schedules(conn).await?;
+    let now = Utc::now();
+
+    // Entries that were never resurfaced are due since they were created.
+    let mut due: Vec<_> = ranking
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let schedule = schedules
+                .get(&item.path)
+                .cloned()
+                .unwrap_or_else(resurface::Schedule::new);
+            let due_at = schedule.due_at.max(item.created_at());
+            (i, *item, schedule, due_at)
+        })
+        .filter(|(_, _, _, due_at)| *due_at <= now)
+        .collect();
+    due.sort_by_key(|(_, _, _, due_at)| *due_at);
+
+    if due.is_empty() {
+        println!("nothing to resurface");
+        return Ok(());
+    }
+    if list {
+        for (i, item, _, due_at) in &due {
+            println!("{}. {} (due since {})", i + 1, item, format_time(*due_at));
+        }
+        return Ok(());
+    }
+
+    for (i, item, schedule, _) in due.into_iter().take(limit) {
+        show_one(config, item, Some(i), false)?;
+        println!();
+
+        let Some(grade) = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("How relevant is this still?")
+            .items(&GRADES)
+            .default(3)
+            .interact_on_opt(&Term::stderr())?
+        else {
+            break;
+        };
+
+        let schedule = schedule.review(grade as u8, Utc::now());
+        resurface::save(conn, path_str(&item.path), &schedule).await?;
+        let days = schedule.interval_days;
+        println!(
+            "next resurfacing in {} day{}\n",
+            days,
+            if days == 1 { "" } else { "s" }
+        );
+    }
+    Ok(())
+}
+
+#[cfg(feature = "import")]
+async fn import_votes(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    file: &Path,
+    map: Option<&Path>,
+    create_stubs: bool,
+) -> Result<()> {
+    let votes = import::read_votes(file)?;
+    let mapping = map
+        .map(import::read_mapping)
+        .transpose()?
+        .unwrap_or_default();
+    let resolve = |name: &str| normalize_path(mapping.get(name).map_or(name, |path| path));
+
+    let items = get_db_files(conn, config).await?;
+    let known: HashSet<&str> = items
+        .iter()
+        .filter(|f| !f.is_deleted())
+        .map(|f| path_str(&f.path))
+        .collect();
+    let mut missing: Vec<String> = votes
+        .iter()
+        .flat_map(|v| [resolve(&v.left), resolve(&v.right)])
+        .filter(|path| !known.contains(path.as_str()))
+        .collect();
+    missing.sort();
+    missing.dedup();
+
+    if !missing.is_empty() {
+        if !create_stubs {
+            return Err(error::not_found(format!(
+                "unknown entries, map them with --map or use --create-stubs: {}",
+                missing.join(", ")
+            )));
+        }
+
+        if let Some(path) = missing.iter().find(|path| !config.is_inside(path)) {
+            return Err(error::invalid(format!(
+                "{} is outside the entries directory, can't create a stub for it",
+                path
+            )));
+        }
+
+        // Stubs are regular files, so they are picked up by a sync like any
+        // other new entry.
+        for path in &missing {
+            let full_path = config.locate(path);
+            if let Some(parent) = full_path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            fs::write(&full_path, format!("{}\n", path)).await?;
+            println!("created {}", path);
+        }
+        update_files(conn, config).await?;
+
+        // The walk filters can leave a stub out, and a vote on a path that
+        // isn't an entry breaks the ranking.
+        let items = get_db_files(conn, config).await?;
+        let known: HashSet<&str> = items
+            .iter()
+            .filter(|f| !f.is_deleted())
+            .map(|f| path_str(&f.path))
+            .collect();
+        let skipped: Vec<&str> = missing
+            .iter()
+            .map(|path| path.as_str())
+            .filter(|path| !known.contains(path))
+            .collect();
+        if !skipped.is_empty() {
+            return Err(error::invalid(format!(
+                "stubs weren't synced as entries, check the walk settings: {}",
+                skipped.join(", ")
+            )));
+        }
+    }
+
+    let mut tx = conn.begin().await?;
+    for (i, vote) in votes.iter().enumerate() {
+        let (left, right) = (resolve(&vote.left), resolve(&vote.right));
+        if left == right {
+            return Err(error::invalid(format!(
+                "vote {}: {} can't be compared to itself",
+                i + 1,
+                left
+            )));
+        }
+        let at = vote
+            .at()
+            .map_err(|e| error::invalid(format!("vote {}: {}", i + 1, e)))?;
+        let vote = Vote {
+            left_path: PathBuf::from(left),
+            right_path: PathBuf::from(right),
+            vote: vote.vote,
+            at,
+            criterion: None,
+            confidence: None,
+        };
+        insert_vote(&mut tx, &vote, "import").await?;
+    }
+    tx.commit().await?;
+
+    println!("imported {} votes", votes.len());
+    Ok(())
+}
+
+async fn merge_db(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    db: &Path,
+    strategy: Option<merge::Strategy>,
+) -> Result<()> {
+    let mut remote = SqliteConnection::connect(&format!("sqlite:{}", db.display())).await?;
+
+    let choose = |path: &str| {
+        if !std::io::stdin().is_terminal() {
+            return Err(error::conflict(format!(
+                "{} has a different history in both databases, use --strategy",
+                path
+            )));
+        }
+        let choice = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!(
+                "{} has a different history in both databases",
+                path
+            ))
+            .items(&["keep local", "keep remote", "keep both as revisions"])
+            .default(2)
+            .interact_on(&Term::stderr())?;
+        Ok([
+            merge::Strategy::Local,
+            merge::Strategy::Remote,
+            merge::Strategy::Both,
+        ][choice])
+    };
+    let report = merge::merge(conn, &mut remote, strategy, choose).await?;
+
+    for (path, content) in &report.files {
+        let full_path = config.locate(path);
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(&full_path, content).await?;
+    }
+
+    println!(
+        "added {} entries, updated {} ({} conflicts), copied {} votes",
+        report.added, report.updated, report.conflicts, report.votes
+    );
+    Ok(())
+}
+
+async fn backup(conn: &mut SqliteConnection, config: &Config, keep: usize) -> Result<()> {
+    let path = backup::create(conn, config).await?;
+    println!("wrote {}", path.display());
+    for path in backup::prune(config, keep.max(1)).await? {
+        println!("removed {}", path.display());
+    }
+    Ok(())
+}
+
+/// Whether `command` can remove things from the database, so it's snapshot
+/// first with Config::auto_backup. A batch can run any of them.
+fn is_destructive(command: &Commands) -> bool {
+    matches!(
+        command,
+        Commands::Remove(_)
+            | Commands::Purge { .. }
+            | Commands::Autoprune { apply: true }
+            | Commands::Votes {
+                command: Some(VotesCommand::Delete { .. }),
+                ..
+            }
+            | Commands::Merge { .. }
+            | Commands::Remap { dry_run: false, .. }
+            | Commands::Batch
+    )
+}
+
+async fn restore_backup(config: &Config, name: Option<&str>) -> Result<()> {
+    let Some(name) = name else {
+        let backups = backup::list(config).await?;
+        if backups.is_empty() {
+            println!("no backups in {}", backup::dir(config).display());
+        }
+        for path in backups.iter().rev() {
+            println!("{}", path.file_name().unwrap().to_string_lossy());
+        }
+        return Ok(());
+    };
+    let path = backup::find(config, name).await?;
+
+    // Restoring can be a mistake too.
+    let mut conn = connect(config).await?;
+    let current = backup::create(&mut conn, config).await?;
+    conn.close().await?;
+    println!("wrote {}", current.display());
+
+    backup::restore(config, &path).await?;
+    println!("restored {}", path.display());
+
+    // Entries removed since the backup are back, and need their files back.
+    let mut conn = connect(config).await?;
+    let items = get_db_files(&mut conn, config).await?;
+    let files = items
+        .iter()
+        .filter(|f| !f.is_deleted())
+        .map(|f| (path_str(&f.path), f.last_content().content.as_slice()));
+    let written = write_missing(config, files).await?;
+    if written > 0 {
+        println!("wrote {} files of restored entries", written);
+    }
+    Ok(())
+}
+
+/// Replays all votes with `params` on a worker thread, printing progress.
+fn rate_in_background(
+    params: &rating::Params,
+    items: usize,
+    votes: &[(prio_core::Vote, DateTime<Utc>, f64)],
+    bumps: &[rating::Bump],
+) -> Vec<Rating> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let now = Utc::now();
+    let ratings = std::thread::scope(|s| {
+        let worker = s.spawn(move || {
+            params.rate(items, votes, bumps, now, |done| {
+                let _ = tx.send(done);
+            })
+        });
+        for done in rx {
+            eprint!("\rreplaying votes: {}/{}", done, votes.len());
+        }
+        worker.join().unwrap()
+    });
+    if !votes.is_empty() {
+        eprintln!();
+    }
+    ratings
+}
+
+/// Prints how the top 20 of the ranking changes when going from `before` to
+/// `after`.
+fn print_ranking_diff(before: &[&File], after: &[&File]) {
+    for (i, item) in after.iter().take(20).enumerate() {
+        let old = before.iter().position(|f| f.path == item.path);
+        let old_item = old.map(|j| before[j]);
+        let change = match old {
+            Some(j) if j == i => "=".to_string(),
+            Some(j) => format!("{:+}", j as i64 - i as i64),
+            None => "new".to_string(),
+        };
+        println!(
+            "{:>3}. {:>4}  {} -> {}  {}",
+            i + 1,
+            change,
+            old_item.map_or(0, |f| f.score() as i64),
+            item.score() as i64,
+            item
+        );
+    }
+}
+
+async fn recompute(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    params: &rating::Params,
+    dry_run: bool,
+    experiment: Option<&str>,
+) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    let votes = index_votes(config, &items, &counted_votes(conn, config).await?);
+    let bumps = edit_bumps(config, &items);
+    let ratings = rate_in_background(params, items.len(), &votes, &bumps);
+
+    let mut recomputed = items.clone();
+    for (item, rating) in recomputed.iter_mut().zip(ratings) {
+        item.rating = rating;
+    }
+    recomputed.sort_by(|a, b| a.rank_cmp(b, config));
+
+    println!(
+        "ranking with {} compared to {}:",
+        params,
+        rating::live(conn).await?
+    );
+    print_ranking_diff(&ranking(&items), &ranking(&recomputed));
+
+    match experiment {
+        _ if dry_run => {}
+        Some(name) => {
+            experiment::save(conn, name, params, &recomputed).await?;
+            println!("stored as experiment {}", name);
+        }
+        None => rating::set_live(conn, params).await?,
+    }
+    Ok(())
+}
+
+/// `items` with the ratings they had at `then`, from the votes and edits up
+/// to then, sorted like get_db_files does.
+async fn rated_at(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    items: &[File],
+    then: DateTime<Utc>,
+) -> Result<Vec<File>> {
+    let votes: Vec<_> = counted_votes(conn, config)
+        .await?
+        .into_iter()
+        .filter(|v| v.at <= then)
+        .collect();
+    let votes = index_votes(config, items, &votes);
+    let mut bumps = edit_bumps(config, items);
+    bumps.retain(|b| b.at <= then);
+    let ratings = rating::live(conn)
+        .await?
+        .rate(items.len(), &votes, &bumps, then, |_| {});
+
+    let mut rated = items.to_vec();
+    for (item, rating) in rated.iter_mut().zip(ratings) {
+        item.rating = rating;
+    }
+    rated.sort_by(|a, b| a.rank_cmp(b, config));
+    Ok(rated)
+}
+
+/// The paths of the entries in the bottom tenth of the ranking of `items`,
+/// counting only the entries that existed at `at`.
+fn bottom_tenth(items: &[File], at: DateTime<Utc>) -> HashSet<PathBuf> {
+    let ranking: Vec<_> = ranking(items)
+        .into_iter()
+        .filter(|f| f.created_at() <= at)
+        .collect();
+    let bottom = ranking.len() / 10;
+    ranking[ranking.len() - bottom..]
+        .iter()
+        .map(|f| f.path.clone())
+        .collect()
+}
+
+async fn drift(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    since: Duration,
+    ranks: usize,
+    top: usize,
+    notify: bool,
+) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    let then = Utc::now() - since;
+    let before = rated_at(conn, config, &items, then).await?;
+
+    // Only compare entries that existed back then, entries added since would
+    // push everything below them down.
+    let existed = |f: &&File| f.created_at() <= then;
+    let before: Vec<_> = ranking(&before).into_iter().filter(existed).collect();
+    let after: Vec<_> = ranking(&items).into_iter().filter(existed).collect();
+
+    let mut alerts = vec![];
+    for (i, item) in after.iter().enumerate() {
+        let Some(j) = before.iter().position(|f| f.path == item.path) else {
+            continue;
+        };
+        if i.abs_diff(j) > ranks {
+            alerts.push(format!("{} moved from {} to {}", item, j + 1, i + 1));
+        } else if j < top && i >= top {
+            alerts.push(format!(
+                "{} fell out of the top {}, from {} to {}",
+                item,
+                top,
+                j + 1,
+                i + 1
+            ));
+        }
+    }
+
+    for alert in &alerts {
+        println!("{}", alert);
+    }
+    if notify && !alerts.is_empty() {
+        let default = ["notify-send".to_string(), "prio".to_string()];
+        let command = config.notify_command.as_deref().unwrap_or(&default);
+        let (program, args) = command
+            .split_first()
+            .ok_or_else(|| anyhow!("notify_command is empty"))?;
+        let status = std::process::Command::new(program)
+            .args(args)
+            .arg(alerts.join("\n"))
+            .status()
+            .map_err(|e| anyhow!("starting {}: {}", program, e))?;
+        if !status.success() {
+            return Err(anyhow!("{} failed with {}", program, status));
+        }
+    }
+    Ok(())
+}
+
+async fn needs_votes(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    threshold: Option<f64>,
+) -> Result<()> {
+    let threshold = threshold.unwrap_or(config.settled_deviation);
+    let items = get_db_files(conn, config).await?;
+    let ranking = ranking(&items);
+    let params = rating::live(conn).await?;
+    let deviations: Vec<_> = ranking.iter().map(|f| f.rating.deviation).collect();
+    let budget = stats::budget(&params, &deviations, threshold);
+
+    let unsettled = ranking.iter().filter(|f| f.rating.deviation >= threshold);
+    let mut rows: Vec<_> = unsettled.zip(&budget.unsettled).collect();
+    rows.sort_by_key(|(_, n)| std::cmp::Reverse(n.unwrap_or(usize::MAX)));
+    for (item, n) in rows {
+        match n {
+            Some(n) => println!("{:>4}  {}", n, item),
+            None => println!("{:>4}  {}", "100+", item),
+        }
+    }
+    println!("{}", budget_summary(&budget, threshold));
+    Ok(())
+}
+
+fn budget_summary(budget: &stats::Budget, threshold: f64) -> String {
+    match budget.unsettled.len() {
+        0 => format!("every entry has a deviation below {:.0}", threshold),
+        n => format!(
+            "{} entries have a deviation above {:.0}, roughly {} votes would settle them",
+            n, threshold, budget.votes
+        ),
+    }
+}
+
+async fn stats(conn: &mut SqliteConnection, config: &Config) -> Result<()> {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    let items = get_db_files(conn, config).await?;
+    let stats = stats::compute(conn, &items).await?;
+    if config.format == Format::Json {
+        let entries = |items: &[&File]| -> Vec<_> {
+            items
+                .iter()
+                .map(|f| serde_json::json!({ "path": path_str(&f.path), "votes": f.votes }))
+                .collect()
+        };
+        let per_day: serde_json::Map<_, _> = stats
+            .votes_per_day
+            .iter()
+            .map(|(day, n)| (day.clone(), (*n).into()))
+            .collect();
+        let json = serde_json::json!({
+            "entries": stats.entries,
+            "active": stats.active,
+            "deleted": stats.deleted,
+            "votes": stats.votes,
+            "votes_per_day": per_day,
+            "most_compared": entries(&stats.most_compared),
+            "least_compared": entries(&stats.least_compared),
+            "average_deviation": stats.average_deviation,
+        });
+        println!("{}", json);
+        return Ok(());
+    }
+
+    println!(
+        "entries: {}, of which {} active and {} deleted",
+        stats.entries, stats.active, stats.deleted
+    );
+    println!("votes: {}", stats.votes);
+    let recent = stats.votes_last_days();
+    println!(
+        "votes in the last {} days: {}, {:.1} a day",
+        stats::DAYS,
+        recent,
+        recent as f64 / stats::DAYS as f64
+    );
+    if let Some((day, max)) = stats.votes_per_day.iter().max_by_key(|(_, n)| *n) {
+        if *max > 0 {
+            let bars: String = stats
+                .votes_per_day
+                .iter()
+                .map(|(_, n)| match n {
+                    0 => ' ',
+                    n => BARS[(n * BARS.len()).div_ceil(*max) - 1],
+                })
+                .collect();
+            println!("  {}  (most: {} on {})", bars, max, day);
+        }
+    }
+    if let Some(deviation) = stats.average_deviation {
+        println!("average deviation: {:.0}", deviation);
+    }
+
+    for (name, items) in [
+        ("most compared", &stats.most_compared),
+        ("least compared", &stats.least_compared),
+    ] {
+        if items.is_empty() {
+            continue;
+        }
+        println!("{}:", name);
+        for item in items {
+            println!("  {:>4}  {}", item.votes, item);
+        }
+    }
+    Ok(())
+}
+
+async fn dashboard(conn: &mut SqliteConnection, config: &Config) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    let ranking = ranking(&items);
+    let now = Utc::now();
+
+    println!("top 5:");
+    for (i, item) in ranking.iter().take(5).enumerate() {
+        println!("  {}. {}", i + 1, item);
+    }
+
+    // Dates are stored as YYYY-MM-DD, so they compare as text.
+    let soon = (Local::now() + Duration::days(7))
+        .format("%Y-%m-%d")
+        .to_string();
+    let date_fields: Vec<_> = config
+        .fields
+        .iter()
+        .filter(|(_, ty)| matches!(ty, config::FieldType::Date))
+        .map(|(name, _)| name)
+        .collect();
+    let mut due: Vec<_> = ranking
+        .iter()
+        .flat_map(|item| {
+            date_fields.iter().filter_map(|name| {
+                let date = item.fields.get(*name)?;
+                (date.as_str() <= soon.as_str()).then_some((date, name, *item))
+            })
+        })
+        .collect();
+    due.sort_by_key(|(date, _, _)| *date);
+    if !due.is_empty() {
+        println!("\ndue within a week:");
+        for (date, name, item) in due {
+            println!("  {} {}  {}", date, name, item);
+        }
+    }
+
+    let mut waiting_on: BTreeMap<String, Vec<&File>> = BTreeMap::new();
+    for item in &ranking {
+        for name in item.mentions() {
+            waiting_on
+                .entry(name.to_lowercase())
+                .or_default()
+                .push(item);
+        }
+    }
+    if !waiting_on.is_empty() {
+        println!("\nwaiting on:");
+        for (name, items) in waiting_on {
+            println!("  @{}", name);
+            for item in items {
+                println!("    {}", item);
+            }
+        }
+    }
+
+    let week_ago = now - Duration::days(7);
+    let edited: Vec<_> = ranking
+        .iter()
+        .filter(|item| item.file_contents.len() > 1 && item.last_content().at >= week_ago)
+        .collect();
+    if !edited.is_empty() {
+        println!("\nedited this week:");
+        for item in edited {
+            println!("  {}", item);
+        }
+    }
+
+    let midnight = Local::now()
+        .date_naive()
+        .and_hms_opt(0, 0, 0)
+        .and_then(|t| Local.from_local_datetime(&t).earliest())
+        .map_or(now, |t| t.with_timezone(&Utc))
+        .timestamp();
+    let votes_today = votes::count_since(conn, midnight).await?;
+    println!("\nvotes today: {}", votes_today);
+
+    let params = rating::live(conn).await?;
+    let deviations: Vec<_> = ranking.iter().map(|f| f.rating.deviation).collect();
+    let budget = stats::budget(&params, &deviations, config.settled_deviation);
+    println!("{}", budget_summary(&budget, config.settled_deviation));
+    Ok(())
+}
+
+async fn quarantine_files(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    command: QuarantineCommand,
+) -> Result<()> {
+    let items = quarantine::list(config).await?;
+    if items.is_empty() {
+        println!("nothing in quarantine");
+        return Ok(());
+    }
+    if let QuarantineCommand::List = command {
+        for item in &items {
+            println!("{}  {}", format_time(item.at()), item.path);
+        }
+        return Ok(());
+    }
+
+    for item in &items {
+        let status = status::current_status(conn, &item.path).await?;
+        let choice = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!(
+                "{} ({}, removed {})",
+                item.path,
+                status,
+                format_time(item.at())
+            ))
+            .items(&["keep in quarantine", "delete for good", "restore"])
+            .default(0)
+            .interact_on_opt(&Term::stderr())?;
+        match choice {
+            None => break,
+            Some(0) => {}
+            Some(1) => quarantine::purge(config, item).await?,
+            Some(_) => {
+                quarantine::restore(config, item).await?;
+                // Syncing won't pick up a deleted entry again by itself.
+                if status == Status::Deleted {
+                    let actor = actor();
+                    let reason = Some("restored from quarantine".to_string());
+                    set_status(conn, &item.path, Status::Active, &actor, reason.as_deref()).await?;
+                    let change = Change::Status {
+                        path: item.path.clone(),
+                        from: status,
+                        to: Status::Active,
+                        reason,
+                    };
+                    audit::record(conn, &change, &actor).await?;
+                }
+                println!("restored {}", item.path);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn experiments(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    command: ExperimentCommand,
+) -> Result<()> {
+    match command {
+        ExperimentCommand::List => {
+            let live = rating::live(conn).await?;
+            for experiment in experiment::list(conn).await? {
+                println!(
+                    "{}  {}  {}{}",
+                    format_time(experiment.created_at),
+                    experiment.name,
+                    experiment.params,
+                    if experiment.params == live {
+                        " (live)"
+                    } else {
+                        ""
+                    }
+                );
+            }
+        }
+        ExperimentCommand::Show { name } => {
+            let experiment = experiment::get(conn, &name).await?;
+            let scores = experiment::scores(conn, &name).await?;
+            let items = get_db_files(conn, config).await?;
+
+            // Entries added after the experiment have no score in it.
+            let mut stored: Vec<_> = items
+                .iter()
+                .filter_map(|item| {
+                    let rating = *scores.get(path_str(&item.path))?;
+                    Some(File {
+                        rating,
+                        ..item.clone()
+                    })
+                })
+                .collect();
+            stored.sort_by(|a, b| a.rank_cmp(b, config));
+
+            println!(
+                "{} ({}, {}) compared to the live ranking:",
+                experiment.name,
+                experiment.params,
+                format_time(experiment.created_at)
+            );
+            print_ranking_diff(&ranking(&items), &ranking(&stored));
+        }
+        ExperimentCommand::Promote { name } => {
+            let experiment = experiment::get(conn, &name).await?;
+            rating::set_live(conn, &experiment.params).await?;
+            println!("ranking with {} from now on", experiment.params);
+        }
+        ExperimentCommand::Delete { name } => experiment::delete(conn, &name).await?,
+    }
+    Ok(())
+}
+
+/// Reports entries whose file doesn't hash to the content last stored for
+/// it. Those with a newer modification time were edited since the last sync,
+/// the others changed without sync being able to notice.
+async fn verify(conn: &mut SqliteConnection, config: &Config) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    let mut mismatches = 0;
+    for item in items.iter().filter(|f| !f.is_deleted()) {
+        let path = path_str(&item.path);
+        let full_path = config.locate(path);
+        let stored = item.last_content();
+        let bytes = match fs::read(&full_path).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                println!("{}: missing", path);
+                mismatches += 1;
+                continue;
+            }
+            Err(e) => return Err(anyhow!(e).context(format!("reading {}", full_path.display()))),
+        };
+        if util::content_hash(&bytes) == util::content_hash(&stored.content) {
+            continue;
+        }
+
+        mismatches += 1;
+        // Sync stores whole seconds.
+        let modified: DateTime<Utc> = fs::metadata(&full_path).await?.modified()?.into();
+        if modified.timestamp() > stored.at.timestamp() {
+            println!("{}: edited since the last sync", path);
+        } else {
+            println!(
+                "{}: differs from the content synced at {}, without being modified since",
+                path,
+                format_time(stored.at)
+            );
+        }
+    }
+
+    if mismatches > 0 {
+        return Err(error::corruption(format!(
+            "{} files don't match the database",
+            mismatches
+        )));
+    }
+    println!("all files match the database");
+    Ok(())
+}
+
+async fn doctor(conn: &mut SqliteConnection, config: &Config) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    let ranking = ranking(&items);
+
+    let by_confidence = fatigue::upsets_by_confidence(conn, &items).await?;
+    if by_confidence.iter().any(|(c, _)| c.is_some()) {
+        println!("votes against the current ranking by confidence:");
+        for (confidence, bucket) in &by_confidence {
+            println!(
+                "  {:<10} {:>3}% of {}",
+                confidence.map_or("not asked", |c| c.as_str()),
+                100 * bucket.upsets / bucket.votes.max(1),
+                bucket.votes
+            );
+        }
+        println!();
+    }
+    let ratings: Vec<_> = ranking.iter().map(|f| f.rating.rating).collect();
+    if let Some((low, high)) = rating::fences(&ratings) {
+        let outliers: Vec<_> = ranking
+            .iter()
+            .filter(|f| f.rating.rating < low || f.rating.rating > high)
+            .collect();
+        if !outliers.is_empty() {
+            println!(
+                "{} entries have ratings far outside {:.0} to {:.0}, set clamp in the config to keep them from dominating show:",
+                outliers.len(),
+                low,
+                high
+            );
+            for item in outliers {
+                println!("  {:.0}  {}", item.rating.rating, item);
+            }
+            println!();
+        }
+    }
+
+    let edges = graph::vote_edges(conn).await?;
+    let problems = exclude::problems(config, &items, &edges);
+    if !problems.is_empty() {
+        for problem in &problems {
+            println!("{}", problem);
+        }
+        println!();
+    }
+
+    // Ratings are only comparable within a component, so cross-component
+    // rankings are mostly noise.
+    let components = graph::components(&ranking, &edges);
+    if components.len() <= 1 {
+        println!(
+            "vote graph: all {} active entries are connected",
+            ranking.len()
+        );
+        return Ok(());
+    }
+
+    println!(
+        "vote graph: {} active entries are split into {} components, use vote --bridge to connect them",
+        ranking.len(),
+        components.len()
+    );
+    for (i, component) in components.iter().enumerate() {
+        println!("component {}: {} entries", i + 1, component.len());
+        if i > 0 {
+            for item in component {
+                println!("\t{}", item);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "export")]
+async fn export_ranking(conn: &mut SqliteConnection, config: &Config, deleted: bool) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    let ranking = ranking(&items);
+    let rows: Vec<_> = items
+        .iter()
+        .rev()
+        .filter(|f| deleted || !f.is_deleted())
+        .map(|f| {
+            (
+                ranking.iter().position(|r| r.path == f.path).map(|i| i + 1),
+                f,
+            )
+        })
+        .collect();
+
+    match config.format {
+        Format::Csv => print!("{}", export::ranking_csv(&rows)?),
+        Format::Json => {
+            let rows: Vec<_> = rows.iter().map(|(rank, f)| entry_json(f, *rank)).collect();
+            println!("{}", serde_json::Value::Array(rows));
+        }
+        Format::Text => {
+            return Err(error::invalid(
+                "export needs --graph, or --format csv or json for the entries",
+            ))
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "export")]
+async fn export_all(conn: &mut SqliteConnection, config: &Config) -> Result<()> {
+    if config.format == Format::Csv {
+        return Err(error::invalid("export --all only writes JSON"));
+    }
+    let dump = dump::dump(conn).await?;
+    println!("{}", serde_json::to_string_pretty(&dump)?);
+    Ok(())
+}
+
+/// Writes the files of entries that aren't on disk, returning how many.
+/// Otherwise the next sync would see these entries as deleted. Fails on a
+/// path outside the entries directory.
+async fn write_missing<'a>(
+    config: &Config,
+    files: impl IntoIterator<Item = (&'a str, &'a [u8])>,
+) -> Result<usize> {
+    let mut written = 0;
+    for (path, content) in files {
+        if !config.is_inside(path) {
+            return Err(error::invalid(format!(
+                "{} is outside the entries directory",
+                path
+            )));
+        }
+        let full_path = config.locate(path);
+        if full_path.exists() {
+            continue;
+        }
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(&full_path, content).await?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+#[cfg(feature = "import")]
+async fn import_dump(conn: &mut SqliteConnection, config: &Config, file: &Path) -> Result<()> {
+    let data = fs::read(file).await?;
+    let dump: dump::Dump = serde_json::from_slice(&data)
+        .map_err(|e| error::invalid(format!("{} isn't a dump: {}", file.display(), e)))?;
+    if let Some(entry) = dump.entries.iter().find(|e| !config.is_inside(&e.path)) {
+        return Err(error::invalid(format!(
+            "{} is outside the entries directory",
+            entry.path
+        )));
+    }
+    dump::load(conn, &dump).await?;
+
+    let files = dump
+        .entries
+        .iter()
+        .filter_map(|e| Some((e.path.as_str(), e.file()?.as_bytes())));
+    let written = write_missing(config, files).await?;
+
+    println!(
+        "imported {} entries and {} votes, wrote {} files",
+        dump.entries.len(),
+        dump.votes.len(),
+        written
+    );
+    Ok(())
+}
+
+#[cfg(feature = "export")]
+async fn export_graph(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    format: export::GraphFormat,
+) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    let edges = graph::vote_edges(conn).await?;
+
+    let voted: HashSet<&str> = edges
+        .keys()
+        .flat_map(|e| [e.winner.as_str(), e.loser.as_str()])
+        .collect();
+    let nodes: Vec<_> = items
+        .iter()
+        .rev()
+        .filter(|f| !f.is_deleted() || voted.contains(path_str(&f.path)))
+        .collect();
+
+    let ratings: Vec<_> = nodes.iter().map(|f| f.rating.rating).collect();
+    let bounds = config.clamp.bounds(&ratings);
+    print!("{}", export::graph(format, &nodes, &edges, bounds));
+    Ok(())
+}
+
+#[cfg(feature = "export")]
+async fn share(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    tags: &[String],
+    out: &Path,
+) -> Result<()> {
+    let items = get_db_files(conn, config).await?;
+    let shared: Vec<_> = ranking(&items)
+        .into_iter()
+        .filter(|f| {
+            let own = f.tags();
+            tags.iter().all(|tag| own.contains(tag))
+        })
+        .collect();
+
+    fs::write(out, export::share(&tags.join(", "), &shared)).await?;
+    println!("wrote {} entries to {}", shared.len(), out.display());
+    Ok(())
+}
+
+async fn init(config: &Config, configured: &Config, preset: Option<Preset>) -> Result<()> {
+    let db = config.db();
+    if db.exists() {
+        return Err(error::conflict(format!("{} already exists", db.display())));
+    }
+    fs::create_dir_all(&config.dir).await?;
+    if let Some(parent) = db.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    let options = SqliteConnectOptions::new()
+        .filename(&db)
+        .create_if_missing(true);
+    let mut conn = SqliteConnection::connect_with(&options).await?;
+    schema::create(&mut conn).await?;
+    println!(
+        "created {} for the entries in {}",
+        db.display(),
+        config.dir.display()
+    );
+    let mut dir_set = config.dir == configured.dir;
+
+    if let Some(preset) = preset {
+        rating::set_live(&mut conn, &preset.params()).await?;
+        let mut snippet = preset.config().to_string();
+        if !dir_set {
+            snippet = format!("dir = {:?}\n{}", config.dir.display().to_string(), snippet);
+        }
+        let name = preset.to_possible_value().unwrap().get_name().to_string();
+        match prio::config::path() {
+            Some(path) if !path.exists() => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).await?;
+                }
+                fs::write(&path, &snippet).await?;
+                println!("wrote the {} preset to {}", name, path.display());
+                dir_set = true;
+            }
+            Some(path) => {
+                println!(
+                    "add the {} preset to {}:\n\n{}",
+                    name,
+                    path.display(),
+                    snippet
+                );
+                dir_set = true;
+            }
+            None => println!(
+                "no config file location, the {} preset is:\n\n{}",
+                name, snippet
+            ),
+        }
+    }
+
+    if !dir_set {
+        println!(
+            "set dir = {:?} in the config or PRIO_DIR to use it",
+            config.dir.display().to_string()
+        );
+    }
+    Ok(())
+}
+
+/// Renames the entries whose path starts with `from` to start with `to`
+/// instead, all at once. Refuses if a renamed entry isn't deleted but its
+/// file isn't at the new path, or if the new path is taken.
+async fn remap(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    from: &str,
+    to: &str,
+    dry_run: bool,
+) -> Result<()> {
+    let paths: Vec<String> = sqlx::query_scalar("SELECT path FROM entries ORDER BY path")
+        .fetch_all(conn.borrow_mut())
+        .await?;
+    let existing: HashSet<&str> = paths.iter().map(|p| p.as_str()).collect();
+    let renames: Vec<(&str, String)> = paths
+        .iter()
+        .filter_map(|path| {
+            let rest = path.strip_prefix(from)?;
+            Some((path.as_str(), normalize_path(&format!("{}{}", to, rest))))
+        })
+        .filter(|(path, new)| path != new)
+        .collect();
+    if renames.is_empty() {
+        println!("no entries start with {}", from);
+        return Ok(());
+    }
+
+    let mut problems = vec![];
+    for (path, new) in &renames {
+        if existing.contains(new.as_str()) {
+            problems.push(format!("{} -> {}: already an entry", path, new));
+        } else if status::current_status(conn, path).await? != Status::Deleted
+            && !config.locate(new).exists()
+        {
+            problems.push(format!(
+                "{} -> {}: no file at {}",
+                path,
+                new,
+                config.locate(new).display()
+            ));
+        }
+    }
+    if !problems.is_empty() {
+        for problem in &problems {
+            eprintln!("{}", problem);
+        }
+        return Err(error::conflict(format!(
+            "not remapping, {} problems",
+            problems.len()
+        )));
+    }
+
+    if dry_run {
+        for (path, new) in &renames {
+            println!("{} -> {}", path, new);
+        }
+        return Ok(());
+    }
+
+    let mut tx = conn.begin().await?;
+    for (path, new) in &renames {
+        schema::move_entry(&mut tx, path, new, false).await?;
+        let root = config.split_root(new).0;
+        query!("UPDATE entries SET root = ?2 WHERE path = ?1", new, root)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    println!("remapped {} entries", renames.len());
+    Ok(())
+}
+
+async fn status(conn: &mut SqliteConnection, config: &Config) -> Result<()> {
+    let summary = cache::summary(conn).await?;
+    let params = rating::live(conn).await?;
+    let budget = stats::budget(
+        &params,
+        &cache::deviations(conn).await?,
+        config.settled_deviation,
+    );
+    match summary.top {
+        Some(top) => print!("{} active, top: {}", summary.active, top),
+        None => print!("{} active", summary.active),
+    }
+    match budget.unsettled.len() {
+        0 => println!(),
+        n => println!(", {} unsettled (~{} votes)", n, budget.votes),
+    }
+    Ok(())
+}
+
+/// Makes the ranking cache reflect the changes made by this connection, if
+/// any.
+async fn refresh_cache(conn: &mut SqliteConnection, config: &Config) -> Result<()> {
+    let changes: i64 = sqlx::query_scalar("SELECT total_changes()")
+        .fetch_one(conn.borrow_mut())
+        .await?;
+    if changes == 0 {
+        return Ok(());
+    }
+
+    let items = get_db_files(conn, config).await?;
+    cache::refresh(conn, &ranking(&items)).await
+}
+
+/// Runs the commands read from stdin, one per line, in a single transaction.
+/// Stops and rolls back everything at the first failing command.
+async fn batch(conn: &mut SqliteConnection, config: &Config) -> Result<()> {
+    let mut tx = conn.begin().await?;
+
+    for (i, line) in std::io::stdin().lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let args =
+            split_words(line).map_err(|e| error::invalid(format!("line {}: {}", i + 1, e)))?;
+        let cli = Cli::try_parse_from(std::iter::once("prio".to_string()).chain(args))
+            .map_err(|e| error::invalid(format!("line {}: {}", i + 1, e)))?;
+        let command = match cli.command {
+            Some(command) => command,
+            None => default_command(config)?,
+        };
+
+        if !batchable(&command) {
+            return Err(error::invalid(format!(
+                "line {}: command can't be used in a batch",
+                i + 1
+            )));
+        }
+
+        Box::pin(run(&mut tx, config, command))
+            .await
+            .map_err(|e| e.context(format!("line {}", i + 1)))?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Whether `command` can run in a batch: it never prompts, which would read
+/// from stdin along with the batch, doesn't have to run on its own, and only
+/// changes the database, so rolling the batch back undoes all of it. Remove,
+/// undo and redo can move files in and out of the quarantine.
+fn batchable(command: &Commands) -> bool {
+    match command {
+        Commands::Show(_)
+        | Commands::Next(_)
+        | Commands::Done(_)
+        | Commands::Drop(_)
+        | Commands::Snooze(_)
+        | Commands::Activate(_)
+        | Commands::Log { .. }
+        | Commands::RankOf { .. }
+        | Commands::Explain { .. }
+        | Commands::History { .. }
+        | Commands::Votes { .. }
+        | Commands::Diff { .. }
+        | Commands::Set { .. }
+        | Commands::Link { .. }
+        | Commands::Doctor
+        | Commands::Autoprune { apply: false }
+        | Commands::Resurface { list: true, .. }
+        | Commands::Recompute { .. }
+        | Commands::Dashboard
+        | Commands::NeedsVotes { .. }
+        | Commands::Stats
+        | Commands::Drift { .. }
+        | Commands::Experiments { .. } => true,
+        #[cfg(feature = "export")]
+        Commands::Export { .. } => true,
+        #[cfg(feature = "import")]
+        Commands::ImportVotes { create_stubs, .. } => !create_stubs,
+        _ => false,
+    }
+}
+
+/// The command to run when none is given, see Config::default_command.
+fn default_command(config: &Config) -> Result<Commands> {
+    let Some(args) = &config.default_command else {
+        return Ok(Commands::Show(ShowArgs::default()));
+    };
+    let cli = Cli::try_parse_from(std::iter::once("prio").chain(args.iter().map(|s| s.as_str())))
+        .map_err(|e| error::invalid(format!("default_command: {}", e)))?;
+    cli.command
+        .ok_or_else(|| error::invalid("default_command doesn't contain a command"))
+}
+
+/// User errors are printed on their own, other errors with their causes and a
+/// backtrace if RUST_BACKTRACE asks for one.
+fn main() -> ExitCode {
+    let Err(e) = try_main() else {
+        return ExitCode::SUCCESS;
+    };
+    match error::kind(&e) {
+        Some(kind) if kind.is_user_error() => {
+            eprintln!("Error: {:#}", e);
+            ExitCode::from(kind.exit_code())
+        }
+        kind => {
+            eprintln!("Error: {:?}", e);
+            ExitCode::from(kind.map_or(1, error::Kind::exit_code))
+        }
+    }
+}
+
+fn try_main() -> Result<()> {
+    let cli = Cli::parse();
+    // The cache always holds the ranking as configured, not as overridden
+    // for this run.
+    let profile = cli
+        .profile
+        .clone()
+        .or_else(|| std::env::var("PRIO_PROFILE").ok());
+    let mut cache_config = Config::load(profile.as_deref())?;
+    let command = match cli.command {
+        Some(command) => command,
+        None => default_command(&cache_config)?,
+    };
+    if let Some(dir) = cli.dir {
+        cache_config.dir = dir;
+    }
+    cache_config.db = cli.db.or(cache_config.db);
+    cache_config.strict_sync |= cli.strict;
+    let walk = &mut cache_config.walk;
+    walk.max_depth = cli.max_depth.or(walk.max_depth);
+    if !cli.extensions.is_empty() {
+        walk.extensions = cli.extensions;
+    }
+    walk.min_size = cli.min_size.or(walk.min_size);
+    walk.max_size = cli.max_size.or(walk.max_size);
+    cache_config.format = cli.format.unwrap_or(cache_config.format);
+    let mut config = cache_config.clone();
+    if !cli.with_criterion.is_empty() {
+        config.include_criteria = Some(cli.with_criterion);
+    }
+    if !cli.without_criterion.is_empty() {
+        config.exclude_criteria = cli.without_criterion;
+    }
+
+    #[cfg(feature = "export")]
+    let takes_csv = matches!(command, Commands::Export { .. });
+    #[cfg(not(feature = "export"))]
+    let takes_csv = false;
+    if config.format == Format::Csv && !takes_csv {
+        return Err(error::invalid("only export can print CSV"));
+    }
+
+    let rt = Builder::new_current_thread().build()?;
+
+    if let Commands::Init { path, preset } = command {
+        if let Some(path) = path {
+            config.dir = path;
+        }
+        return rt.block_on(init(&config, &cache_config, preset));
+    }
+    // Any open connection would keep writing to the replaced file.
+    if let Commands::RestoreBackup { backup } = &command {
+        return rt.block_on(restore_backup(&config, backup.as_deref()));
+    }
+
+    // Meant for shell prompts, so it skips fixing up paths, syncing and
+    // backups.
+    if let Commands::Status = command {
+        return rt.block_on(async {
+            let mut conn = connect_quick(&config).await?;
+            status(&mut conn, &config).await
+        });
+    }
+
+    // The window's event loop has to own the main thread, so the gui blocks on
+    // the runtime for each query instead of running inside it.
+    #[cfg(feature = "gui")]
+    if let Commands::Gui = command {
+        let mut conn = rt.block_on(async {
+            let mut conn = connect(&config).await?;
+            update_files(&mut conn, &config).await?;
+            anyhow::Ok(conn)
+        })?;
+        gui::run(&rt, &mut conn, &config)?;
+        return rt.block_on(refresh_cache(&mut conn, &cache_config));
+    }
+
+    rt.block_on(async {
+        //let mut rng = thread_rng();
+        let mut conn = connect(&config).await?;
+
+        if config.auto_backup && is_destructive(&command) {
+            backup::create(&mut conn, &config).await?;
+            backup::prune(&config, config.backup_keep.max(1)).await?;
+        }
+
+        // Syncing would store whatever is on disk now.
+        if let Commands::Verify = command {
+            return verify(&mut conn, &config).await;
+        }
+        // Syncing first would see the moved files as deleted and new ones.
+        if let Commands::Remap { from, to, dry_run } = &command {
+            remap(&mut conn, &config, from, to, *dry_run).await?;
+            if *dry_run {
+                return Ok(());
+            }
+            update_files(&mut conn, &config).await?;
+            return refresh_cache(&mut conn, &cache_config).await;
+        }
+        // Syncing first would add what's on disk, and import wants an empty
+        // database.
+        #[cfg(feature = "import")]
+        if let Commands::Import { file } = &command {
+            import_dump(&mut conn, &config, file).await?;
+            update_files(&mut conn, &config).await?;
+            return refresh_cache(&mut conn, &cache_config).await;
+        }
+
+        update_files(&mut conn, &config).await?;
+        run(&mut conn, &config, command).await?;
+        refresh_cache(&mut conn, &cache_config).await
     })
 }
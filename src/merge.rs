@@ -0,0 +1,264 @@
+//! Merging another prio database into this one, e.g. one kept on another
+//! machine.
+
+use std::borrow::BorrowMut;
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use chrono::{TimeZone, Utc};
+use sqlx::{query, Connection, SqliteConnection};
+
+use crate::audit::{self, Change};
+use crate::schema;
+
+/// What to do with an entry whose content history differs in both
+/// databases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Strategy {
+    /// Keep the revisions in this database.
+    Local,
+    /// Replace the revisions by those in the other database.
+    Remote,
+    /// Keep the revisions of both, ordered by time.
+    Both,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Revision {
+    content: Vec<u8>,
+    at: i64,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub added: usize,
+    pub updated: usize,
+    pub conflicts: usize,
+    pub votes: usize,
+    /// Entries whose latest content changed, to be written to the entries
+    /// directory.
+    pub files: Vec<(String, Vec<u8>)>,
+}
+
+const ACTOR: &str = "merge";
+
+async fn revisions(conn: &mut SqliteConnection, path: &str) -> Result<Vec<Revision>> {
+    Ok(query!(
+        r#"
+            SELECT content AS "content!", at
+            FROM file_contents
+            WHERE path = ?1 AND content IS NOT NULL
+            ORDER BY at ASC, rowid ASC
+        "#,
+        path
+    )
+    .map(|r| Revision {
+        content: r.content,
+        at: r.at,
+    })
+    .fetch_all(conn)
+    .await?)
+}
+
+async fn insert_revision(conn: &mut SqliteConnection, path: &str, rev: &Revision) -> Result<()> {
+    query!(
+        "INSERT INTO file_contents (path, content, at) VALUES (?1, ?2, ?3)",
+        path,
+        rev.content,
+        rev.at
+    )
+    .execute(conn.borrow_mut())
+    .await?;
+
+    let change = Change::Content {
+        path: path.to_string(),
+        at: rev.at,
+    };
+    audit::record(conn, &change, ACTOR).await?;
+    Ok(())
+}
+
+/// Copies the status changes of `path` that aren't in the local database yet,
+/// returning whether the entry is deleted in the remote database.
+async fn merge_status(
+    conn: &mut SqliteConnection,
+    remote: &mut SqliteConnection,
+    path: &str,
+) -> Result<bool> {
+    let rows = query!(
+        r#"
+            SELECT status, actor, reason, at
+            FROM status_history
+            WHERE path = ?1
+            ORDER BY at ASC, rowid ASC
+        "#,
+        path
+    )
+    .fetch_all(remote)
+    .await?;
+
+    for r in &rows {
+        query!(
+            r#"
+                INSERT INTO status_history
+                    (path, status, actor, reason, at)
+                SELECT ?1, ?2, ?3, ?4, ?5
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM status_history
+                    WHERE path = ?1 AND status = ?2 AND actor = ?3 AND at = ?5
+                )
+            "#,
+            path,
+            r.status,
+            r.actor,
+            r.reason,
+            r.at
+        )
+        .execute(conn.borrow_mut())
+        .await?;
+    }
+
+    Ok(rows.last().is_some_and(|r| r.status == "deleted"))
+}
+
+/// Merges `remote` into `conn`. Entries only in `remote` are added, content
+/// histories that only have newer revisions in `remote` get those, and for
+/// other differing histories `choose` is asked what to do unless a strategy
+/// is given. Votes and status changes missing locally are copied.
+pub async fn merge(
+    conn: &mut SqliteConnection,
+    remote: &mut SqliteConnection,
+    strategy: Option<Strategy>,
+    mut choose: impl FnMut(&str) -> Result<Strategy>,
+) -> Result<Report> {
+    schema::upgrade(remote).await?;
+
+    let mut report = Report::default();
+    let mut tx = conn.begin().await?;
+
+    let local_paths: HashSet<String> = query!("SELECT path FROM entries")
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .map(|r| r.path)
+        .collect();
+    let remote_paths = query!("SELECT path, root FROM entries ORDER BY id")
+        .fetch_all(remote.borrow_mut())
+        .await?;
+
+    for (path, root) in remote_paths.into_iter().map(|r| (r.path, r.root)) {
+        let theirs = revisions(remote, &path).await?;
+
+        if !local_paths.contains(&path) {
+            let id = schema::next_entry_id(&mut tx).await?;
+            query!(
+                "INSERT INTO entries (path, id, root) VALUES (?1, ?2, ?3)",
+                path,
+                id,
+                root
+            )
+            .execute(&mut *tx)
+            .await?;
+            let change = Change::Add { path: path.clone() };
+            audit::record(&mut tx, &change, ACTOR).await?;
+
+            for rev in &theirs {
+                insert_revision(&mut tx, &path, rev).await?;
+            }
+            let deleted = merge_status(&mut tx, remote, &path).await?;
+            if let (false, Some(last)) = (deleted, theirs.last()) {
+                report.files.push((path.clone(), last.content.clone()));
+            }
+            report.added += 1;
+            continue;
+        }
+
+        let ours = revisions(&mut tx, &path).await?;
+        merge_status(&mut tx, remote, &path).await?;
+
+        let added: Vec<&Revision> = if theirs.starts_with(&ours) {
+            theirs[ours.len()..].iter().collect()
+        } else if theirs.iter().all(|rev| ours.contains(rev)) {
+            // Also true after keeping both in an earlier merge.
+            vec![]
+        } else {
+            report.conflicts += 1;
+            let strategy = match strategy {
+                Some(strategy) => strategy,
+                None => choose(&path)?,
+            };
+            match strategy {
+                Strategy::Local => vec![],
+                Strategy::Remote => {
+                    query!("DELETE FROM file_contents WHERE path = ?1", path)
+                        .execute(&mut *tx)
+                        .await?;
+                    theirs.iter().collect()
+                }
+                Strategy::Both => theirs.iter().filter(|rev| !ours.contains(rev)).collect(),
+            }
+        };
+        if added.is_empty() {
+            continue;
+        }
+
+        for rev in &added {
+            insert_revision(&mut tx, &path, rev).await?;
+        }
+        let latest = revisions(&mut tx, &path).await?.pop().unwrap();
+        if ours.last() != Some(&latest) {
+            report.files.push((path.clone(), latest.content));
+        }
+        report.updated += 1;
+    }
+
+    let votes = query!(
+        r#"
+            SELECT
+                left_path AS "left_path!: String",
+                right_path AS "right_path!: String",
+                vote AS "vote!: i64",
+                at AS "at!: i64",
+                criterion,
+                confidence
+            FROM resolved_votes
+            ORDER BY at
+        "#
+    )
+    .fetch_all(remote)
+    .await?;
+    for v in votes {
+        let exists = query!(
+            r#"
+                SELECT COUNT(*) AS "n!: i64"
+                FROM resolved_votes
+                WHERE left_path = ?1 AND right_path = ?2 AND vote = ?3 AND at = ?4
+            "#,
+            v.left_path,
+            v.right_path,
+            v.vote,
+            v.at
+        )
+        .fetch_one(&mut *tx)
+        .await?
+        .n;
+        if exists > 0 {
+            continue;
+        }
+
+        let vote = crate::Vote {
+            left_path: PathBuf::from(&v.left_path),
+            right_path: PathBuf::from(&v.right_path),
+            vote: v.vote,
+            at: Utc.timestamp_opt(v.at, 0).unwrap(),
+            criterion: v.criterion,
+            confidence: v.confidence.map(|c| c.parse()).transpose()?,
+        };
+        crate::insert_vote(&mut tx, &vote, ACTOR).await?;
+        report.votes += 1;
+    }
+
+    tx.commit().await?;
+    Ok(report)
+}
@@ -0,0 +1,69 @@
+use crate::rating::{Params, System};
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// Starting points for common ways of using prio, see prio init --preset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Preset {
+    /// Next actions: priorities shift quickly, stale entries get snoozed and
+    /// a bare prio shows what to do next.
+    Gtd,
+    /// A long backlog groomed in sessions: slow decay, calibration pairs
+    /// at the start of a session and stale entries dropped.
+    BacklogGrooming,
+    /// Things to read: no decay, as interest doesn't wear off by itself,
+    /// and lists show the reading time.
+    ReadingList,
+}
+
+impl Preset {
+    /// How ratings are computed, stored in the new database.
+    pub fn params(&self) -> Params {
+        let half_life = match self {
+            Preset::Gtd => Some(30 * DAY),
+            Preset::BacklogGrooming => Some(180 * DAY),
+            Preset::ReadingList => None,
+        };
+        Params {
+            system: System::Glicko2,
+            half_life,
+            horizon: None,
+        }
+    }
+
+    /// The settings for the config file.
+    pub fn config(&self) -> &'static str {
+        match self {
+            Preset::Gtd => {
+                r#"default_command = ["next"]
+break_after = 20
+tie_breakers = ["activity", "created", "path"]
+
+[autoprune]
+months = 3
+status = "snoozed"
+"#
+            }
+            Preset::BacklogGrooming => {
+                r#"default_command = ["dashboard"]
+calibration_pairs = 3
+ask_confidence = true
+tie_breakers = ["created", "path"]
+
+[autoprune]
+months = 6
+status = "dropped"
+
+[clamp]
+winsorize = 0.02
+"#
+            }
+            Preset::ReadingList => {
+                r#"default_command = ["show", "--columns", "rank,title,minutes,score,tags"]
+break_after = 30
+tie_breakers = ["created", "path"]
+"#
+            }
+        }
+    }
+}
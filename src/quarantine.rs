@@ -0,0 +1,115 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::config::Config;
+
+/// A file that was removed by prio, kept until it's reviewed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Item {
+    /// The entry the file belonged to.
+    pub path: String,
+    /// Name of the file in the quarantine directory.
+    pub file: String,
+    /// When it was quarantined, in seconds since the epoch.
+    pub at: i64,
+}
+
+impl Item {
+    pub fn at(&self) -> DateTime<Utc> {
+        Utc.timestamp_opt(self.at, 0).unwrap()
+    }
+}
+
+/// Where removed files are kept. Hidden, so syncing skips it.
+pub fn dir(config: &Config) -> PathBuf {
+    config.dir.join(".quarantine")
+}
+
+fn manifest_path(config: &Config) -> PathBuf {
+    dir(config).join("manifest.json")
+}
+
+/// The quarantined files, oldest first.
+pub async fn list(config: &Config) -> Result<Vec<Item>> {
+    match fs::read(manifest_path(config)).await {
+        Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn save(config: &Config, items: &[Item]) -> Result<()> {
+    let json = serde_json::to_vec_pretty(items)?;
+    fs::write(manifest_path(config), json).await?;
+    Ok(())
+}
+
+/// Renames, or copies if `to` is on another file system.
+async fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    if fs::rename(from, to).await.is_err() {
+        fs::copy(from, to).await?;
+        fs::remove_file(from).await?;
+    }
+    Ok(())
+}
+
+/// Moves the file of the entry at `path` into quarantine, if there is one.
+pub async fn put(config: &Config, path: &str) -> Result<()> {
+    let full_path = config.locate(path);
+    if !full_path.exists() {
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    let name = full_path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let file = format!("{}-{}", now.timestamp_millis(), name);
+    move_file(&full_path, &dir(config).join(&file)).await?;
+
+    let mut items = list(config).await?;
+    items.push(Item {
+        path: path.to_string(),
+        file,
+        at: now.timestamp(),
+    });
+    save(config, &items).await
+}
+
+/// The file last quarantined for the entry at `path`.
+pub async fn find(config: &Config, path: &str) -> Result<Option<Item>> {
+    let items = list(config).await?;
+    Ok(items.into_iter().rev().find(|item| item.path == path))
+}
+
+/// Moves the quarantined file back to where it was.
+pub async fn restore(config: &Config, item: &Item) -> Result<()> {
+    let full_path = config.locate(&item.path);
+    if full_path.exists() {
+        bail!("{} exists already", full_path.display());
+    }
+    move_file(&dir(config).join(&item.file), &full_path).await?;
+
+    let mut items = list(config).await?;
+    items.retain(|i| i.file != item.file);
+    save(config, &items).await
+}
+
+/// Deletes the quarantined file for good.
+pub async fn purge(config: &Config, item: &Item) -> Result<()> {
+    match fs::remove_file(dir(config).join(&item.file)).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    let mut items = list(config).await?;
+    items.retain(|i| i.file != item.file);
+    save(config, &items).await
+}
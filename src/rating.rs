@@ -0,0 +1,209 @@
+use std::fmt::Display;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use prio_core::Rating;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, SqliteConnection};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum System {
+    #[default]
+    Glicko2,
+    Elo,
+}
+
+impl From<System> for prio_core::System {
+    fn from(system: System) -> Self {
+        match system {
+            System::Glicko2 => prio_core::System::Glicko2,
+            System::Elo => prio_core::System::Elo,
+        }
+    }
+}
+
+/// How ratings are computed from the votes. The live parameters are stored
+/// in the settings table, see `prio recompute`.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Params {
+    pub system: System,
+    /// Half-life of votes in seconds: a vote this old only counts half.
+    /// Votes never decay when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub half_life: Option<i64>,
+    /// Age in seconds before votes start to decay, votes younger than this
+    /// count fully.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub horizon: Option<i64>,
+}
+
+impl Display for Params {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.system {
+            System::Glicko2 => f.write_str("glicko2")?,
+            System::Elo => f.write_str("elo")?,
+        }
+        if let Some(half_life) = self.half_life {
+            write!(f, ", half-life {}d", half_life / (24 * 60 * 60))?;
+        }
+        if let Some(horizon) = self.horizon {
+            write!(f, " after {}d", horizon / (24 * 60 * 60))?;
+        }
+        Ok(())
+    }
+}
+
+impl Params {
+    /// How much a vote cast at `at` still counts, 1 when votes don't decay.
+    pub fn decay(&self, at: DateTime<Utc>, now: DateTime<Utc>) -> f64 {
+        match self.half_life {
+            Some(half_life) if half_life > 0 => {
+                let age = (now - at).num_seconds() - self.horizon.unwrap_or(0);
+                let age = age.max(0) as f64;
+                0.5f64.powf(age / half_life as f64)
+            }
+            _ => 1.0,
+        }
+    }
+
+    /// Applies a vote cast at `at` to the ratings, counting it for `weight`
+    /// on top of its decay.
+    pub fn apply(
+        &self,
+        ratings: &mut [Rating],
+        vote: &prio_core::Vote,
+        at: DateTime<Utc>,
+        now: DateTime<Utc>,
+        weight: f64,
+    ) {
+        let weight = weight * self.decay(at, now);
+        prio_core::apply_with(ratings, vote, self.system.into(), weight);
+    }
+
+    /// Replays the votes in order along with the bumps, calling `progress`
+    /// with the number of votes done after every chunk of them.
+    pub fn rate(
+        &self,
+        items: usize,
+        votes: &[(prio_core::Vote, DateTime<Utc>, f64)],
+        mut bumps: &[Bump],
+        now: DateTime<Utc>,
+        mut progress: impl FnMut(usize),
+    ) -> Vec<Rating> {
+        const CHUNK: usize = 1000;
+
+        let mut ratings = vec![Rating::new(); items];
+        for (i, chunk) in votes.chunks(CHUNK).enumerate() {
+            for (vote, at, weight) in chunk {
+                apply_bumps(&mut ratings, &mut bumps, Some(*at));
+                self.apply(&mut ratings, vote, *at, now, *weight);
+            }
+            progress(i * CHUNK + chunk.len());
+        }
+        apply_bumps(&mut ratings, &mut bumps, None);
+        ratings
+    }
+}
+
+/// A rise in the deviation of `items[item]` at `at`, see Config::edit_bump.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bump {
+    pub item: usize,
+    pub at: DateTime<Utc>,
+    pub deviation: f64,
+}
+
+/// Applies the bumps at the front of `bumps`, which is sorted by time, up to
+/// `until` or all of them, and drops them from it. Deviations don't get above
+/// that of a new rating.
+pub fn apply_bumps(ratings: &mut [Rating], bumps: &mut &[Bump], until: Option<DateTime<Utc>>) {
+    let max = Rating::new().deviation;
+    while let Some((bump, rest)) = bumps.split_first() {
+        if until.is_some_and(|until| bump.at > until) {
+            break;
+        }
+        let rating = &mut ratings[bump.item];
+        rating.deviation = (rating.deviation + bump.deviation)
+            .min(max)
+            .max(rating.deviation);
+        *bumps = rest;
+    }
+}
+
+/// Most votes Params::votes_to_settle simulates.
+pub const MAX_SETTLE_VOTES: usize = 100;
+
+impl Params {
+    /// Roughly how many votes an entry with `deviation` needs to get below
+    /// `target`: the number of draws against an equally rated entry with
+    /// `opponent` deviation it takes. None if that's more than a hundred.
+    pub fn votes_to_settle(&self, deviation: f64, opponent: f64, target: f64) -> Option<usize> {
+        let vote = prio_core::Vote {
+            left: 0,
+            right: 1,
+            vote: 0,
+        };
+        let mut ratings = [
+            Rating {
+                deviation,
+                ..Rating::new()
+            },
+            Rating {
+                deviation: opponent,
+                ..Rating::new()
+            },
+        ];
+        for n in 0..=MAX_SETTLE_VOTES {
+            if ratings[0].deviation < target {
+                return Some(n);
+            }
+            prio_core::apply_with(&mut ratings, &vote, self.system.into(), 1.0);
+            ratings[1].deviation = opponent;
+        }
+        None
+    }
+}
+
+/// Ratings outside the returned range are outliers: more than three times
+/// the interquartile range beyond the quartiles. None for fewer than four
+/// ratings, or if most are the same, like when few entries were voted on.
+pub fn fences(ratings: &[f64]) -> Option<(f64, f64)> {
+    if ratings.len() < 4 {
+        return None;
+    }
+    let mut sorted = ratings.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let q1 = sorted[sorted.len() / 4];
+    let q3 = sorted[sorted.len() * 3 / 4];
+    let iqr = q3 - q1;
+    if iqr <= 0.0 {
+        return None;
+    }
+    Some((q1 - 3.0 * iqr, q3 + 3.0 * iqr))
+}
+
+/// The parameters the ranking is computed with.
+pub async fn live(conn: &mut SqliteConnection) -> Result<Params> {
+    let value = query!("SELECT value FROM settings WHERE key = 'rating'")
+        .fetch_optional(conn)
+        .await?;
+    match value {
+        Some(r) => Ok(serde_json::from_str(&r.value)?),
+        None => Ok(Params::default()),
+    }
+}
+
+pub async fn set_live(conn: &mut SqliteConnection, params: &Params) -> Result<()> {
+    let value = serde_json::to_string(params)?;
+    query!(
+        r#"
+            INSERT INTO settings (key, value) VALUES ('rating', ?1)
+            ON CONFLICT (key) DO UPDATE SET value = excluded.value
+        "#,
+        value
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,200 @@
+//! Rendering entries for reading, in show and in the preview of the gui.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use anyhow::{anyhow, bail, Context, Result};
+use dialoguer::console::style;
+
+use crate::frontmatter;
+
+/// A line of a Markdown entry, by what it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Block<'a> {
+    FrontMatter(&'a str),
+    Heading(usize, &'a str),
+    Text(&'a str),
+    /// A list item, with its indentation.
+    Item(usize, &'a str),
+    Quote(&'a str),
+    /// A line of a fenced code block.
+    Code(&'a str),
+    Rule,
+    Blank,
+}
+
+/// Inline markup in a line of text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Span<'a> {
+    Plain(&'a str),
+    Strong(&'a str),
+    Emphasis(&'a str),
+    Code(&'a str),
+}
+
+pub fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("md") || e.eq_ignore_ascii_case("markdown"))
+}
+
+/// Splits Markdown into blocks, line by line. Only the parts of Markdown that
+/// show up in entries are recognized, anything else is text.
+pub fn blocks(content: &str) -> Vec<Block<'_>> {
+    let body = frontmatter::split(content).1;
+    let mut blocks: Vec<_> = content[..content.len() - body.len()]
+        .lines()
+        .map(Block::FrontMatter)
+        .collect();
+
+    let mut fence: Option<&str> = None;
+    for line in body.lines() {
+        let trimmed = line.trim_start();
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            } else {
+                blocks.push(Block::Code(line));
+            }
+            continue;
+        }
+
+        let indent = line.len() - trimmed.len();
+        let block = if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = Some(&trimmed[..3]);
+            continue;
+        } else if trimmed.is_empty() {
+            Block::Blank
+        } else if let Some(level) = heading_level(trimmed) {
+            Block::Heading(level, trimmed[level..].trim())
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            Block::Quote(quote.trim_start())
+        } else if is_rule(trimmed) {
+            Block::Rule
+        } else if let Some(item) = ["- ", "* ", "+ "]
+            .iter()
+            .find_map(|marker| trimmed.strip_prefix(marker))
+        {
+            Block::Item(indent, item)
+        } else {
+            Block::Text(line)
+        };
+        blocks.push(block);
+    }
+    blocks
+}
+
+fn heading_level(line: &str) -> Option<usize> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let rest = &line[level..];
+    ((1..=6).contains(&level) && (rest.is_empty() || rest.starts_with(' '))).then_some(level)
+}
+
+fn is_rule(line: &str) -> bool {
+    let line: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    line.len() >= 3 && ["-", "*", "_"].iter().any(|c| line == c.repeat(line.len()))
+}
+
+/// Splits a line into its inline markup. Unclosed markers are plain text, and
+/// so are underscores within words, as in snake_case.
+pub fn spans<'a>(text: &'a str) -> Vec<Span<'a>> {
+    let mut spans = vec![];
+    let mut plain = 0;
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        let previous = text[..i].chars().next_back();
+        let (marker, span): (&str, fn(&'a str) -> Span<'a>) = if rest.starts_with('`') {
+            ("`", Span::Code)
+        } else if rest.starts_with("**") || rest.starts_with("__") {
+            (&rest[..2], Span::Strong)
+        } else if rest.starts_with('*')
+            || (rest.starts_with('_') && !previous.is_some_and(char::is_alphanumeric))
+        {
+            (&rest[..1], Span::Emphasis)
+        } else {
+            i += rest.chars().next().map_or(1, char::len_utf8);
+            continue;
+        };
+
+        let inner = &rest[marker.len()..];
+        match inner.find(marker).filter(|&end| end > 0) {
+            Some(end) => {
+                if plain < i {
+                    spans.push(Span::Plain(&text[plain..i]));
+                }
+                spans.push(span(&inner[..end]));
+                i += marker.len() * 2 + end;
+                plain = i;
+            }
+            None => i += marker.len(),
+        }
+    }
+    if plain < text.len() {
+        spans.push(Span::Plain(&text[plain..]));
+    }
+    spans
+}
+
+fn styled_spans(text: &str) -> String {
+    spans(text)
+        .into_iter()
+        .map(|span| match span {
+            Span::Plain(s) => s.to_string(),
+            Span::Strong(s) => style(s).bold().to_string(),
+            Span::Emphasis(s) => style(s).italic().to_string(),
+            Span::Code(s) => style(s).cyan().to_string(),
+        })
+        .collect()
+}
+
+/// Markdown with its markup replaced by terminal styling. Code blocks are
+/// set apart but not highlighted, use Config::render_command for that.
+pub fn terminal(content: &str) -> String {
+    let mut res = String::with_capacity(content.len());
+    for block in blocks(content) {
+        let line = match block {
+            Block::FrontMatter(line) => style(line).dim().to_string(),
+            Block::Heading(1, text) => style(text).bold().underlined().to_string(),
+            Block::Heading(_, text) => style(text).bold().to_string(),
+            Block::Text(text) => styled_spans(text),
+            Block::Item(indent, text) => {
+                format!("{}• {}", " ".repeat(indent), styled_spans(text))
+            }
+            Block::Quote(text) => format!("{} {}", style("│").dim(), style(text).italic()),
+            Block::Code(line) => format!("{} {}", style("│").dim(), style(line).cyan()),
+            Block::Rule => style("─".repeat(40)).dim().to_string(),
+            Block::Blank => String::new(),
+        };
+        res.push_str(&line);
+        res.push('\n');
+    }
+    res
+}
+
+/// Pipes `content` through the configured render_command, returning what it
+/// printed.
+pub fn command(command: &[String], content: &[u8]) -> Result<Vec<u8>> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| anyhow!("render_command is empty"))?;
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("starting {}", program))?;
+    // Written from another thread, as the command can fill its stdout
+    // before it has read everything. Whether it read it all is up to the
+    // command.
+    let mut stdin = child.stdin.take().unwrap();
+    let content = content.to_vec();
+    let writer = std::thread::spawn(move || stdin.write_all(&content));
+    let output = child.wait_with_output()?;
+    let _ = writer.join();
+    if !output.status.success() {
+        bail!("{} failed with {}", program, output.status);
+    }
+    Ok(output.stdout)
+}
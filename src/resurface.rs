@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use sqlx::{query, SqliteConnection};
+
+/// When an entry is shown again by prio resurface, following SM-2.
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    repetitions: i64,
+    pub interval_days: i64,
+    ease: f64,
+    pub due_at: DateTime<Utc>,
+}
+
+impl Schedule {
+    /// The schedule of an entry that was never resurfaced.
+    pub fn new() -> Self {
+        Schedule {
+            repetitions: 0,
+            interval_days: 0,
+            ease: 2.5,
+            due_at: Utc.timestamp_opt(0, 0).unwrap(),
+        }
+    }
+
+    /// The next schedule after grading the relevance of the entry from 0 (not
+    /// at all) to 5 (very).
+    pub fn review(&self, grade: u8, now: DateTime<Utc>) -> Self {
+        let q = grade.min(5) as f64;
+
+        let (repetitions, interval_days) = if grade < 3 {
+            (0, 1)
+        } else {
+            let interval = match self.repetitions {
+                0 => 1,
+                1 => 6,
+                _ => (self.interval_days as f64 * self.ease).round() as i64,
+            };
+            (self.repetitions + 1, interval)
+        };
+        let ease = (self.ease + 0.1 - (5.0 - q) * (0.08 + (5.0 - q) * 0.02)).max(1.3);
+
+        Schedule {
+            repetitions,
+            interval_days,
+            ease,
+            due_at: now + Duration::days(interval_days),
+        }
+    }
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub async fn schedules(conn: &mut SqliteConnection) -> Result<HashMap<PathBuf, Schedule>> {
+    let rows = query!(
+        r#"
+            SELECT path, repetitions, interval_days, ease, due_at
+            FROM resurface_schedule
+        "#
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| {
+            let schedule = Schedule {
+                repetitions: r.repetitions,
+                interval_days: r.interval_days,
+                ease: r.ease,
+                due_at: Utc.timestamp_opt(r.due_at, 0).unwrap(),
+            };
+            (PathBuf::from(r.path), schedule)
+        })
+        .collect())
+}
+
+pub async fn save(conn: &mut SqliteConnection, path: &str, schedule: &Schedule) -> Result<()> {
+    let due_at = schedule.due_at.timestamp();
+    query!(
+        r#"
+            INSERT OR REPLACE INTO resurface_schedule
+                (path, repetitions, interval_days, ease, due_at)
+            VALUES
+                (?1, ?2, ?3, ?4, ?5)
+        "#,
+        path,
+        schedule.repetitions,
+        schedule.interval_days,
+        schedule.ease,
+        due_at
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
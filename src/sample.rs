@@ -1,7 +1,13 @@
 use std::collections::{HashSet, VecDeque};
+use std::io::Write;
+use std::process::{Command, Stdio};
 
+use anyhow::{anyhow, bail, Context, Result};
 use rand::prelude::*;
+use serde::Serialize;
 
+use crate::config::RecentEdits;
+use crate::util::path_str;
 use crate::File;
 
 /*
@@ -54,16 +60,18 @@ pub fn take_n(items: &mut VecDeque<File>, n: usize) -> Vec<File> {
 }
 */
 
-pub fn take_n(items: VecDeque<File>, n: usize) -> Vec<File> {
-    let mut rng = thread_rng();
-
-    // REVIEW: can we reduce collects?
-
-    let items_ref: Vec<_> = items.iter().enumerate().map(|(i, f)| (i, f)).collect();
-    let indices: HashSet<usize> = items_ref
-        .choose_multiple_weighted(&mut rng, n, |(_, f)| f.rating.deviation)
+/// Picks `n` items, preferring ones with a high deviation like
+/// prio_core::sample, and recently edited ones as much more as configured.
+pub fn take_n(items: VecDeque<File>, n: usize, recent: &RecentEdits) -> Vec<File> {
+    let weights: Vec<_> = items
+        .iter()
+        .map(|f| f.rating.deviation * recent.weight(f))
+        .collect();
+    let indices: HashSet<usize> = (0..items.len())
+        .collect::<Vec<_>>()
+        .choose_multiple_weighted(&mut thread_rng(), n, |i| weights[*i])
         .unwrap()
-        .map(|i| i.0)
+        .copied()
         .collect();
 
     items
@@ -73,3 +81,114 @@ pub fn take_n(items: VecDeque<File>, n: usize) -> Vec<File> {
         .map(|(_, f)| f)
         .collect()
 }
+
+/// Like take_n for a pair, but picks the items from different components of
+/// the vote graph if there are multiple. `components[i]` is the component of
+/// `items[i]`.
+pub fn take_bridge(items: VecDeque<File>, components: &[usize], recent: &RecentEdits) -> Vec<File> {
+    let mut rng = thread_rng();
+
+    let items_ref: Vec<_> = items.iter().enumerate().collect();
+    let Ok(&(first, _)) =
+        items_ref.choose_weighted(&mut rng, |(_, f)| f.rating.deviation * recent.weight(f))
+    else {
+        return take_n(items, 2, recent);
+    };
+
+    let others: Vec<_> = items_ref
+        .iter()
+        .filter(|(i, _)| components[*i] != components[first])
+        .collect();
+    let Ok(&&(second, _)) =
+        others.choose_weighted(&mut rng, |(_, f)| f.rating.deviation * recent.weight(f))
+    else {
+        return take_n(items, 2, recent);
+    };
+
+    let mut items: Vec<_> = items.into_iter().map(Some).collect();
+    vec![items[first].take().unwrap(), items[second].take().unwrap()]
+}
+
+/// A pair of which the outcome is all but certain, in random order. Picks a
+/// well established entry from the top quarter and one from the bottom
+/// quarter at least 400 points lower, which the higher one is expected to
+/// beat more than nine out of ten times.
+pub fn take_calibration(items: VecDeque<File>) -> Option<Vec<File>> {
+    let mut rng = thread_rng();
+
+    let mut sorted: Vec<_> = items.iter().enumerate().collect();
+    sorted.sort_by(|(_, a), (_, b)| b.rating.rating.total_cmp(&a.rating.rating));
+    let quarter = (sorted.len() / 4).max(1);
+    let settled = |f: &File| f.rating.deviation < 150.0;
+
+    let pairs: Vec<_> = sorted[..quarter]
+        .iter()
+        .filter(|(_, high)| settled(high))
+        .flat_map(|(i, high)| {
+            sorted[sorted.len() - quarter..]
+                .iter()
+                .filter(|(_, low)| settled(low) && high.rating.rating - low.rating.rating >= 400.0)
+                .map(move |(j, _)| (*i, *j))
+        })
+        .collect();
+    let &(high, low) = pairs.choose(&mut rng)?;
+
+    let mut items: Vec<_> = items.into_iter().map(Some).collect();
+    let mut pair = vec![items[high].take().unwrap(), items[low].take().unwrap()];
+    pair.shuffle(&mut rng);
+    Some(pair)
+}
+
+#[derive(Serialize)]
+struct Candidate<'a> {
+    index: usize,
+    id: i64,
+    path: &'a str,
+    title: &'a str,
+    rating: f64,
+    deviation: f64,
+    volatility: f64,
+}
+
+/// Lets the external `command` pick the pair, see `Config::sampler_command`.
+pub fn take_external(items: VecDeque<File>, command: &[String]) -> Result<Vec<File>> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| anyhow!("sampler_command is empty"))?;
+
+    let candidates: Vec<_> = items
+        .iter()
+        .enumerate()
+        .map(|(index, f)| Candidate {
+            index,
+            id: f.id,
+            path: path_str(&f.path),
+            title: &f.title,
+            rating: f.rating.rating,
+            deviation: f.rating.deviation,
+            volatility: f.rating.volatility,
+        })
+        .collect();
+    let input = serde_json::to_vec(&candidates)?;
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("starting sampler {}", program))?;
+    child.stdin.take().unwrap().write_all(&input)?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!("sampler {} failed with {}", program, output.status);
+    }
+
+    let pair: [usize; 2] = serde_json::from_slice(&output.stdout)
+        .with_context(|| format!("sampler {} didn't print a pair of indices", program))?;
+    if pair[0] == pair[1] || pair.iter().any(|i| *i >= items.len()) {
+        bail!("sampler {} picked invalid indices {:?}", program, pair);
+    }
+
+    let mut items: Vec<_> = items.into_iter().map(Some).collect();
+    Ok(pair.map(|i| items[i].take().unwrap()).into())
+}
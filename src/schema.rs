@@ -0,0 +1,610 @@
+use std::borrow::BorrowMut;
+use std::collections::HashSet;
+
+use anyhow::{bail, Result};
+use sqlx::{query, query_scalar, Connection, Executor, SqliteConnection};
+
+use crate::util::normalize_path;
+
+async fn has_column(conn: &mut SqliteConnection, table: &str, column: &str) -> Result<bool> {
+    let count: i64 = query_scalar("SELECT COUNT(*) FROM pragma_table_info(?1) WHERE name = ?2")
+        .bind(table)
+        .bind(column)
+        .fetch_one(conn)
+        .await?;
+    Ok(count > 0)
+}
+
+/// Schema changes after version 1, the first one taking a database to
+/// version 2. Never change one once released, append a new one and update
+/// schema.sql to match instead.
+const MIGRATIONS: &[&str] = &[
+    // 2: entry_aliases, and votes with an id of their own that may name an
+    // alias instead of an entry.
+    r#"
+        CREATE TABLE entry_aliases (
+            alias TEXT NOT NULL,
+            path TEXT NOT NULL,
+            last_vote INTEGER NOT NULL,
+
+            PRIMARY KEY (alias, last_vote),
+            FOREIGN KEY (path) REFERENCES entries(path)
+        );
+        CREATE INDEX entry_aliases_path_idx ON entry_aliases(path);
+
+        CREATE TABLE entry_votes_new (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            left_path TEXT NOT NULL,
+            right_path TEXT NOT NULL,
+            vote INTEGER NOT NULL,
+            criterion TEXT,
+            confidence TEXT,
+
+            at INTEGER NOT NULL
+        );
+        INSERT INTO entry_votes_new
+            (id, left_path, right_path, vote, criterion, confidence, at)
+        SELECT rowid, left_path, right_path, vote, criterion, confidence, at
+        FROM entry_votes;
+        DROP TABLE entry_votes;
+        ALTER TABLE entry_votes_new RENAME TO entry_votes;
+        CREATE INDEX entry_votes_left_path_idx ON entry_votes(left_path);
+        CREATE INDEX entry_votes_right_path_idx ON entry_votes(right_path);
+
+        CREATE VIEW resolved_votes AS
+        SELECT
+            v.id,
+            COALESCE(
+                (
+                    SELECT a.path FROM entry_aliases a
+                    WHERE a.alias = v.left_path AND v.id <= a.last_vote
+                    ORDER BY a.last_vote LIMIT 1
+                ),
+                v.left_path
+            ) AS left_path,
+            COALESCE(
+                (
+                    SELECT a.path FROM entry_aliases a
+                    WHERE a.alias = v.right_path AND v.id <= a.last_vote
+                    ORDER BY a.last_vote LIMIT 1
+                ),
+                v.right_path
+            ) AS right_path,
+            v.vote,
+            v.criterion,
+            v.confidence,
+            v.at
+        FROM entry_votes v;
+    "#,
+    // 3: the highest entry id handed out, see next_entry_id.
+    r#"
+        INSERT OR REPLACE INTO settings (key, value)
+        SELECT 'last_entry_id', IFNULL(MAX(id), 0) FROM entries;
+    "#,
+];
+
+/// The version of schema.sql.
+const LATEST: i64 = MIGRATIONS.len() as i64 + 1;
+
+async fn version(conn: &mut SqliteConnection) -> Result<i64> {
+    Ok(query_scalar("PRAGMA user_version").fetch_one(conn).await?)
+}
+
+/// Pragmas can't take parameters.
+async fn set_version(conn: &mut SqliteConnection, version: i64) -> Result<()> {
+    conn.execute(format!("PRAGMA user_version = {}", version).as_str())
+        .await?;
+    Ok(())
+}
+
+/// Creates the tables in a new, empty database.
+pub async fn create(conn: &mut SqliteConnection) -> Result<()> {
+    let mut tx = conn.begin().await?;
+    tx.execute(include_str!("../schema.sql")).await?;
+    set_version(&mut tx, LATEST).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Whether the database has the latest schema, so upgrade has no migrations
+/// to apply.
+pub async fn is_current(conn: &mut SqliteConnection) -> Result<bool> {
+    Ok(version(conn).await? == LATEST)
+}
+
+/// Applies the migrations the database doesn't have yet, as recorded in its
+/// user_version, and fixes up paths. Runs whenever a database is opened.
+pub async fn upgrade(conn: &mut SqliteConnection) -> Result<()> {
+    let current = version(conn).await?;
+    if current > LATEST {
+        bail!(
+            "the database has schema version {}, this prio only knows up to {}",
+            current,
+            LATEST
+        );
+    }
+    if current < 1 {
+        legacy(conn).await?;
+        set_version(conn, 1).await?;
+    }
+    for (version, migration) in (2..).zip(MIGRATIONS) {
+        if version <= current {
+            continue;
+        }
+        let mut tx = conn.begin().await?;
+        tx.execute(*migration).await?;
+        set_version(&mut tx, version).await?;
+        tx.commit().await?;
+    }
+
+    normalize_paths(conn).await
+}
+
+/// Brings databases from before user_version was used, created with any
+/// older schema.sql, to version 1. Every step is idempotent as it's not known
+/// which ones were applied already.
+async fn legacy(conn: &mut SqliteConnection) -> Result<()> {
+    conn.execute(
+        r#"
+            CREATE TABLE IF NOT EXISTS status_history (
+                path TEXT NOT NULL,
+                status TEXT NOT NULL,
+                actor TEXT NOT NULL,
+                reason TEXT,
+
+                at INTEGER NOT NULL,
+
+                FOREIGN KEY (path) REFERENCES entries(path)
+            );
+            CREATE INDEX IF NOT EXISTS status_history_idx ON status_history(path);
+        "#,
+    )
+    .await?;
+
+    conn.execute(
+        r#"
+            CREATE TABLE IF NOT EXISTS audit_log (
+                id INTEGER PRIMARY KEY,
+                action TEXT NOT NULL,
+                detail TEXT NOT NULL,
+                actor TEXT NOT NULL,
+
+                at INTEGER NOT NULL
+            );
+        "#,
+    )
+    .await?;
+
+    if !has_column(conn, "audit_log", "undone").await? {
+        conn.execute("ALTER TABLE audit_log ADD COLUMN undone INTEGER NOT NULL DEFAULT 0")
+            .await?;
+    }
+
+    conn.execute(
+        r#"
+            CREATE TABLE IF NOT EXISTS ranking_cache (
+                path TEXT NOT NULL PRIMARY KEY,
+                id INTEGER NOT NULL,
+                rank INTEGER NOT NULL,
+                title TEXT NOT NULL,
+                rating REAL NOT NULL,
+                deviation REAL NOT NULL,
+
+                FOREIGN KEY (path) REFERENCES entries(path)
+            );
+        "#,
+    )
+    .await?;
+
+    conn.execute(
+        r#"
+            CREATE TABLE IF NOT EXISTS resurface_schedule (
+                path TEXT NOT NULL PRIMARY KEY,
+                repetitions INTEGER NOT NULL,
+                interval_days INTEGER NOT NULL,
+                ease REAL NOT NULL,
+
+                due_at INTEGER NOT NULL,
+
+                FOREIGN KEY (path) REFERENCES entries(path)
+            );
+        "#,
+    )
+    .await?;
+
+    conn.execute(
+        r#"
+            CREATE TABLE IF NOT EXISTS entry_fields (
+                path TEXT NOT NULL,
+                name TEXT NOT NULL,
+                value TEXT NOT NULL,
+
+                PRIMARY KEY (path, name),
+                FOREIGN KEY (path) REFERENCES entries(path)
+            );
+        "#,
+    )
+    .await?;
+
+    conn.execute(
+        r#"
+            CREATE TABLE IF NOT EXISTS settings (
+                key TEXT NOT NULL PRIMARY KEY,
+                value TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS experiments (
+                name TEXT NOT NULL PRIMARY KEY,
+                params TEXT NOT NULL,
+
+                created_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS experiment_scores (
+                experiment TEXT NOT NULL,
+                path TEXT NOT NULL,
+                rating REAL NOT NULL,
+                deviation REAL NOT NULL,
+
+                PRIMARY KEY (experiment, path),
+                FOREIGN KEY (experiment) REFERENCES experiments(name),
+                FOREIGN KEY (path) REFERENCES entries(path)
+            );
+        "#,
+    )
+    .await?;
+
+    if !has_column(conn, "entry_votes", "criterion").await? {
+        conn.execute("ALTER TABLE entry_votes ADD COLUMN criterion TEXT")
+            .await?;
+    }
+
+    if !has_column(conn, "entry_votes", "confidence").await? {
+        conn.execute("ALTER TABLE entry_votes ADD COLUMN confidence TEXT")
+            .await?;
+    }
+
+    if !has_column(conn, "entries", "id").await? {
+        conn.execute(
+            r#"
+                ALTER TABLE entries ADD COLUMN id INTEGER;
+                UPDATE entries SET id = rowid;
+                CREATE UNIQUE INDEX entries_id_idx ON entries(id);
+            "#,
+        )
+        .await?;
+    }
+
+    if !has_column(conn, "entries", "root").await? {
+        conn.execute("ALTER TABLE entries ADD COLUMN root TEXT NOT NULL DEFAULT ''")
+            .await?;
+    }
+
+    // Deletions used to be recorded as a file_contents row with a NULL
+    // content, derive the initial history from those.
+    query!(
+        r#"
+            INSERT INTO status_history
+                (path, status, actor, reason, at)
+            SELECT path, status, 'migration', NULL, at
+            FROM (
+                SELECT
+                    path,
+                    at,
+                    CASE WHEN content IS NULL THEN 'deleted' ELSE 'active' END AS status,
+                    content IS NULL AS deleted,
+                    LAG(content IS NULL, 1, 1) OVER (PARTITION BY path ORDER BY at, rowid) AS was_deleted
+                FROM file_contents
+            )
+            WHERE deleted != was_deleted
+              AND NOT EXISTS (SELECT 1 FROM status_history)
+        "#
+    )
+    .execute(conn.borrow_mut())
+    .await?;
+
+    Ok(())
+}
+
+/// An id for a new entry, one higher than any that was ever handed out so
+/// an id is never reused after the entry having it is purged.
+pub async fn next_entry_id(conn: &mut SqliteConnection) -> Result<i64> {
+    let id: i64 = query_scalar(
+        r#"
+            SELECT MAX(
+                IFNULL((SELECT CAST(value AS INTEGER) FROM settings WHERE key = 'last_entry_id'), 0),
+                IFNULL((SELECT MAX(id) FROM entries), 0)
+            ) + 1
+        "#,
+    )
+    .fetch_one(conn.borrow_mut())
+    .await?;
+    query(
+        r#"
+            INSERT INTO settings (key, value) VALUES ('last_entry_id', ?1)
+            ON CONFLICT (key) DO UPDATE SET value = excluded.value
+        "#,
+    )
+    .bind(id.to_string())
+    .execute(conn)
+    .await?;
+    Ok(id)
+}
+
+/// Deletes the entry at `path` and everything stored about it, votes with
+/// other entries included. Returns the number of revisions and votes deleted.
+pub async fn purge_entry(conn: &mut SqliteConnection, path: &str) -> Result<(i64, i64)> {
+    let revisions: i64 = query_scalar("SELECT COUNT(*) FROM file_contents WHERE path = ?1")
+        .bind(path)
+        .fetch_one(conn.borrow_mut())
+        .await?;
+    let votes: i64 =
+        query_scalar("SELECT COUNT(*) FROM resolved_votes WHERE left_path = ?1 OR right_path = ?1")
+            .bind(path)
+            .fetch_one(conn.borrow_mut())
+            .await?;
+
+    query(
+        r#"
+            DELETE FROM file_contents WHERE path = ?1;
+            DELETE FROM status_history WHERE path = ?1;
+            DELETE FROM entry_votes WHERE id IN (
+                SELECT id FROM resolved_votes WHERE left_path = ?1 OR right_path = ?1
+            );
+            DELETE FROM entry_aliases WHERE path = ?1;
+            DELETE FROM ranking_cache WHERE path = ?1;
+            DELETE FROM resurface_schedule WHERE path = ?1;
+            DELETE FROM entry_fields WHERE path = ?1;
+            DELETE FROM experiment_scores WHERE path = ?1;
+            DELETE FROM entries WHERE path = ?1;
+        "#,
+    )
+    .bind(path)
+    .execute(conn)
+    .await?;
+    Ok((revisions, votes))
+}
+
+/// Renames entries stored under a path that isn't in its canonical form, see
+/// util::normalize_path. If the canonical path is an entry too, both are the
+/// same file and their histories and votes are merged.
+async fn normalize_paths(conn: &mut SqliteConnection) -> Result<()> {
+    let paths: Vec<String> = query_scalar("SELECT path FROM entries")
+        .fetch_all(conn.borrow_mut())
+        .await?;
+    let existing: HashSet<&str> = paths.iter().map(|p| p.as_str()).collect();
+    let renames: Vec<_> = paths
+        .iter()
+        .map(|path| (path, normalize_path(path)))
+        .filter(|(path, canonical)| *path != canonical)
+        .collect();
+    if renames.is_empty() {
+        return Ok(());
+    }
+
+    let mut tx = conn.begin().await?;
+    for (path, canonical) in renames {
+        let merge = existing.contains(canonical.as_str());
+        move_entry(&mut tx, path, &canonical, merge).await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Moves everything stored for the entry at `from` to `to`, merging it into
+/// the entry at `to` when `merge` is set and it exists already. Votes aren't
+/// rewritten, `from` becomes an alias of `to` for the votes cast so far
+/// instead, so an entry added at `from` later starts without them. Foreign
+/// keys require
+/// the new entry to exist before anything can be moved to it, so it is
+/// created with the root of the old one. Should run in a transaction.
+pub async fn move_entry(
+    conn: &mut SqliteConnection,
+    from: &str,
+    to: &str,
+    merge: bool,
+) -> Result<()> {
+    if !merge {
+        query("INSERT INTO entries (path, root) SELECT ?2, root FROM entries WHERE path = ?1")
+            .bind(from)
+            .bind(to)
+            .execute(conn.borrow_mut())
+            .await?;
+    }
+
+    query(
+        r#"
+            UPDATE file_contents SET path = ?2 WHERE path = ?1;
+            UPDATE status_history SET path = ?2 WHERE path = ?1;
+            UPDATE entry_aliases SET path = ?2 WHERE path = ?1;
+            INSERT OR IGNORE INTO entry_aliases (alias, path, last_vote)
+            SELECT ?1, ?2, IFNULL(MAX(id), 0) FROM entry_votes;
+            DELETE FROM ranking_cache WHERE path = ?1;
+            UPDATE OR IGNORE resurface_schedule SET path = ?2 WHERE path = ?1;
+            DELETE FROM resurface_schedule WHERE path = ?1;
+            UPDATE OR IGNORE entry_fields SET path = ?2 WHERE path = ?1;
+            DELETE FROM entry_fields WHERE path = ?1;
+            UPDATE OR IGNORE experiment_scores SET path = ?2 WHERE path = ?1;
+            DELETE FROM experiment_scores WHERE path = ?1;
+        "#,
+    )
+    .bind(from)
+    .bind(to)
+    .execute(conn.borrow_mut())
+    .await?;
+
+    let id: Option<i64> = query_scalar("SELECT id FROM entries WHERE path = ?1")
+        .bind(from)
+        .fetch_one(conn.borrow_mut())
+        .await?;
+    query("DELETE FROM entries WHERE path = ?1")
+        .bind(from)
+        .execute(conn.borrow_mut())
+        .await?;
+    if !merge {
+        query("UPDATE entries SET id = ?2 WHERE path = ?1")
+            .bind(to)
+            .bind(id)
+            .execute(conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// A new database in memory, for tests.
+#[cfg(test)]
+pub(crate) async fn memory() -> SqliteConnection {
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+    create(&mut conn).await.unwrap();
+    conn
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    /// schema.sql as it was before databases were versioned.
+    const BASELINE: &str = r#"
+        CREATE TABLE entries (
+            path TEXT NOT NULL PRIMARY KEY
+        );
+        CREATE TABLE file_contents (
+            path TEXT NOT NULL,
+            content BLOB,
+            at INTEGER NOT NULL,
+            FOREIGN KEY (path) REFERENCES entries(path)
+        );
+        CREATE TABLE entry_votes (
+            left_path TEXT NOT NULL,
+            right_path TEXT NOT NULL,
+            vote INTEGER NOT NULL,
+            at INTEGER NOT NULL,
+            FOREIGN KEY (left_path) REFERENCES entries(path),
+            FOREIGN KEY (right_path) REFERENCES entries(path)
+        );
+        INSERT INTO entries (path) VALUES ('a.md'), ('b.md');
+        INSERT INTO file_contents (path, content, at) VALUES
+            ('a.md', 'a', 10),
+            ('b.md', 'b', 10),
+            ('b.md', NULL, 30);
+        INSERT INTO entry_votes (left_path, right_path, vote, at) VALUES
+            ('a.md', 'b.md', 1, 20),
+            ('b.md', 'a.md', 0, 25);
+    "#;
+
+    async fn has_table(conn: &mut SqliteConnection, name: &str) -> bool {
+        let count: i64 = query_scalar("SELECT COUNT(*) FROM sqlite_master WHERE name = ?1")
+            .bind(name)
+            .fetch_one(conn)
+            .await
+            .unwrap();
+        count > 0
+    }
+
+    #[tokio::test]
+    async fn connect_migrates_a_baseline_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            dir: dir.path().to_path_buf(),
+            ..Config::default()
+        };
+        let url = format!("sqlite:{}?mode=rwc", config.db().display());
+        let mut conn = SqliteConnection::connect(&url).await.unwrap();
+        conn.execute(BASELINE).await.unwrap();
+        conn.close().await.unwrap();
+
+        let mut conn = crate::connect(&config).await.unwrap();
+        assert_eq!(version(&mut conn).await.unwrap(), LATEST);
+        assert!(has_table(&mut conn, "status_history").await);
+        assert!(has_table(&mut conn, "entry_aliases").await);
+
+        let votes: Vec<(i64, String, String, i64)> =
+            sqlx::query_as("SELECT id, left_path, right_path, vote FROM entry_votes ORDER BY id")
+                .fetch_all(&mut conn)
+                .await
+                .unwrap();
+        assert_eq!(
+            votes,
+            [
+                (1, "a.md".to_string(), "b.md".to_string(), 1),
+                (2, "b.md".to_string(), "a.md".to_string(), 0),
+            ]
+        );
+
+        // The deletion recorded as NULL content became a status change.
+        let statuses: Vec<(String, String)> =
+            sqlx::query_as("SELECT path, status FROM status_history ORDER BY path, at")
+                .fetch_all(&mut conn)
+                .await
+                .unwrap();
+        assert_eq!(
+            statuses,
+            [
+                ("a.md".to_string(), "active".to_string()),
+                ("b.md".to_string(), "active".to_string()),
+                ("b.md".to_string(), "deleted".to_string()),
+            ]
+        );
+
+        // Opening it again has nothing left to do.
+        conn.close().await.unwrap();
+        let mut conn = crate::connect(&config).await.unwrap();
+        assert_eq!(version(&mut conn).await.unwrap(), LATEST);
+    }
+
+    #[tokio::test]
+    async fn moved_entries_keep_the_votes_cast_before() {
+        let mut conn = memory().await;
+        conn.execute(
+            r#"
+                INSERT INTO entries (path, id) VALUES ('a.md', 1), ('b.md', 2);
+                INSERT INTO entry_votes (left_path, right_path, vote, at) VALUES
+                    ('a.md', 'b.md', 1, 10);
+            "#,
+        )
+        .await
+        .unwrap();
+
+        let mut tx = conn.begin().await.unwrap();
+        move_entry(&mut tx, "a.md", "c.md", false).await.unwrap();
+        tx.commit().await.unwrap();
+        // A new entry where the moved one was, with a vote of its own.
+        conn.execute(
+            r#"
+                INSERT INTO entries (path, id) VALUES ('a.md', 3);
+                INSERT INTO entry_votes (left_path, right_path, vote, at) VALUES
+                    ('a.md', 'b.md', -1, 20);
+            "#,
+        )
+        .await
+        .unwrap();
+
+        let stored: Vec<String> = query_scalar("SELECT left_path FROM entry_votes ORDER BY id")
+            .fetch_all(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(stored, ["a.md", "a.md"]);
+        let resolved: Vec<String> =
+            query_scalar("SELECT left_path FROM resolved_votes ORDER BY id")
+                .fetch_all(&mut conn)
+                .await
+                .unwrap();
+        assert_eq!(resolved, ["c.md", "a.md"]);
+    }
+
+    #[tokio::test]
+    async fn purged_ids_are_not_handed_out_again() {
+        let mut conn = memory().await;
+        for path in ["a.md", "b.md"] {
+            let id = next_entry_id(&mut conn).await.unwrap();
+            query("INSERT INTO entries (path, id) VALUES (?1, ?2)")
+                .bind(path)
+                .bind(id)
+                .execute(&mut conn)
+                .await
+                .unwrap();
+        }
+        purge_entry(&mut conn, "b.md").await.unwrap();
+
+        assert_eq!(next_entry_id(&mut conn).await.unwrap(), 3);
+    }
+}
@@ -0,0 +1,55 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::File;
+
+const ORDINALS: [&str; 10] = [
+    "first", "second", "third", "fourth", "fifth", "sixth", "seventh", "eighth", "ninth", "tenth",
+];
+
+/// Titles often start with Markdown heading or list markers, which read aloud
+/// badly.
+fn spoken_title(title: &str) -> &str {
+    title
+        .trim_start_matches(|c: char| matches!(c, '#' | '*' | '-' | '>') || c.is_whitespace())
+        .trim_end()
+}
+
+/// The top `n` of the ranking as short plain sentences, one per line.
+pub fn digest(ranking: &[&File], n: usize) -> String {
+    let mut lines = vec![match ranking.len() {
+        0 => return "You have no active entries.\n".to_string(),
+        1 => "You have 1 active entry.".to_string(),
+        len => format!("You have {} active entries.", len),
+    }];
+    for (i, item) in ranking.iter().take(n).enumerate() {
+        let title = spoken_title(&item.title);
+        lines.push(match ORDINALS.get(i) {
+            Some(&"first") => format!("Your top priority is {}.", title),
+            Some(ordinal) => format!("The {} is {}.", ordinal, title),
+            None => format!("Number {} is {}.", i + 1, title),
+        });
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Pipes `text` to the configured speak_command.
+pub fn say(command: &[String], text: &str) -> Result<()> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| anyhow!("speak_command is empty"))?;
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("starting {}", program))?;
+    child.stdin.take().unwrap().write_all(text.as_bytes())?;
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("{} failed with {}", program, status);
+    }
+    Ok(())
+}
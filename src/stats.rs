@@ -0,0 +1,129 @@
+//! Aggregate numbers on the database, see prio stats.
+
+use std::borrow::BorrowMut;
+
+use anyhow::Result;
+use chrono::{Duration, Local, TimeZone, Utc};
+use sqlx::{query, SqliteConnection};
+
+use crate::rating::{Params, MAX_SETTLE_VOTES};
+use crate::File;
+
+/// How many days votes_per_day goes back.
+pub const DAYS: i64 = 30;
+
+/// The number of entries listed as most and least compared.
+const LISTED: usize = 5;
+
+#[derive(Debug, Clone)]
+pub struct Stats<'a> {
+    pub entries: usize,
+    pub active: usize,
+    pub deleted: usize,
+    /// All votes, including ones on criteria that don't count.
+    pub votes: i64,
+    /// Votes cast on each of the last DAYS days, oldest first, by local
+    /// date.
+    pub votes_per_day: Vec<(String, usize)>,
+    /// Entries that aren't deleted with the most counted votes, most first.
+    pub most_compared: Vec<&'a File>,
+    /// Active entries with the fewest counted votes, fewest first.
+    pub least_compared: Vec<&'a File>,
+    /// Of the ratings of the active entries, None without any.
+    pub average_deviation: Option<f64>,
+}
+
+impl Stats<'_> {
+    pub fn votes_last_days(&self) -> usize {
+        self.votes_per_day.iter().map(|(_, n)| n).sum()
+    }
+}
+
+pub async fn compute<'a>(conn: &mut SqliteConnection, items: &'a [File]) -> Result<Stats<'a>> {
+    let votes = query!(r#"SELECT COUNT(*) AS "count!: i64" FROM entry_votes"#)
+        .fetch_one(conn.borrow_mut())
+        .await?
+        .count;
+
+    let today = Local::now().date_naive();
+    let days: Vec<_> = (0..DAYS).rev().map(|i| today - Duration::days(i)).collect();
+    let since = Local
+        .from_local_datetime(&days[0].and_hms_opt(0, 0, 0).unwrap())
+        .earliest()
+        .map_or(0, |t| t.timestamp());
+    let mut votes_per_day: Vec<_> = days
+        .iter()
+        .map(|day| (day.format("%Y-%m-%d").to_string(), 0))
+        .collect();
+    let recent = query!("SELECT at FROM entry_votes WHERE at >= ?1", since)
+        .fetch_all(conn)
+        .await?;
+    for vote in recent {
+        let day = Utc
+            .timestamp_opt(vote.at, 0)
+            .unwrap()
+            .with_timezone(&Local)
+            .date_naive();
+        if let Some(i) = days.iter().position(|d| *d == day) {
+            votes_per_day[i].1 += 1;
+        }
+    }
+
+    let active: Vec<_> = items.iter().filter(|f| f.is_active()).collect();
+    let mut most_compared: Vec<_> = items.iter().filter(|f| !f.is_deleted()).collect();
+    most_compared.sort_by(|a, b| b.votes.cmp(&a.votes).then_with(|| a.path.cmp(&b.path)));
+    most_compared.truncate(LISTED);
+    let mut least_compared = active.clone();
+    least_compared.sort_by(|a, b| a.votes.cmp(&b.votes).then_with(|| a.path.cmp(&b.path)));
+    least_compared.truncate(LISTED);
+
+    let average_deviation = (!active.is_empty())
+        .then(|| active.iter().map(|f| f.rating.deviation).sum::<f64>() / active.len() as f64);
+
+    Ok(Stats {
+        entries: items.len(),
+        active: active.len(),
+        deleted: items.iter().filter(|f| f.is_deleted()).count(),
+        votes,
+        votes_per_day,
+        most_compared,
+        least_compared,
+        average_deviation,
+    })
+}
+
+/// What it takes to settle the ratings, see prio needs-votes.
+#[derive(Debug, Clone, Default)]
+pub struct Budget {
+    /// Votes needed per entry with a deviation above the threshold, in the
+    /// order given, None if too many to estimate.
+    pub unsettled: Vec<Option<usize>>,
+    /// Roughly how many votes it takes to settle all of them, every vote
+    /// counting for two entries.
+    pub votes: usize,
+}
+
+/// The budget for entries with `deviations`, against the median deviation
+/// as opponent. Entries that would take too many votes count as
+/// MAX_SETTLE_VOTES.
+pub fn budget(params: &Params, deviations: &[f64], threshold: f64) -> Budget {
+    let mut sorted = deviations.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let Some(&median) = sorted.get(sorted.len() / 2) else {
+        return Budget::default();
+    };
+
+    let unsettled: Vec<_> = deviations
+        .iter()
+        .filter(|&&d| d >= threshold)
+        .map(|&d| params.votes_to_settle(d, median, threshold))
+        .collect();
+    let votes: usize = unsettled
+        .iter()
+        .map(|n| n.unwrap_or(MAX_SETTLE_VOTES))
+        .sum();
+    Budget {
+        unsettled,
+        votes: votes.div_ceil(2),
+    }
+}
@@ -0,0 +1,106 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, SqliteConnection};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Active,
+    Done,
+    Dropped,
+    Snoozed,
+    Deleted,
+}
+
+impl Status {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::Active => "active",
+            Status::Done => "done",
+            Status::Dropped => "dropped",
+            Status::Snoozed => "snoozed",
+            Status::Deleted => "deleted",
+        }
+    }
+}
+
+impl FromStr for Status {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "active" => Status::Active,
+            "done" => Status::Done,
+            "dropped" => Status::Dropped,
+            "snoozed" => Status::Snoozed,
+            "deleted" => Status::Deleted,
+            _ => bail!("unknown status {:?}", s),
+        })
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StatusChange {
+    pub status: Status,
+    pub actor: String,
+    pub reason: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+pub async fn set_status(
+    conn: &mut SqliteConnection,
+    path: &str,
+    status: Status,
+    actor: &str,
+    reason: Option<&str>,
+) -> Result<()> {
+    let status = status.as_str();
+    let ts = Utc::now().timestamp();
+
+    query!(
+        r#"
+            INSERT INTO status_history
+                (path, status, actor, reason, at)
+            VALUES
+                (?1, ?2, ?3, ?4, ?5)
+        "#,
+        path,
+        status,
+        actor,
+        reason,
+        ts
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+pub async fn current_status(conn: &mut SqliteConnection, path: &str) -> Result<Status> {
+    let status = query!(
+        r#"
+            SELECT status
+            FROM status_history
+            WHERE path = ?1
+            ORDER BY at DESC, rowid DESC
+            LIMIT 1
+        "#,
+        path
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    match status {
+        Some(r) => r.status.parse(),
+        None => Ok(Status::Active),
+    }
+}
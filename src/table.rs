@@ -0,0 +1,139 @@
+use std::io::Write;
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use chrono::Utc;
+use tabwriter::TabWriter;
+
+use crate::config::Config;
+use crate::util::{format_age, path_str};
+use crate::File;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    Rank,
+    Title,
+    Path,
+    /// Rating plus inherited offset, and deviation
+    Score,
+    /// Inherited from .prio.toml files
+    Offset,
+    Votes,
+    /// Time since the entry was created
+    Age,
+    Words,
+    Minutes,
+    Lang,
+    Tags,
+    Status,
+    /// One of Config::fields
+    Field(String),
+}
+
+impl FromStr for Column {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "rank" => Column::Rank,
+            "title" => Column::Title,
+            "path" => Column::Path,
+            "score" => Column::Score,
+            "offset" => Column::Offset,
+            "votes" => Column::Votes,
+            "age" => Column::Age,
+            "words" => Column::Words,
+            "minutes" => Column::Minutes,
+            "lang" => Column::Lang,
+            "tags" => Column::Tags,
+            "status" => Column::Status,
+            "" => bail!("empty column name"),
+            _ => Column::Field(s.to_string()),
+        })
+    }
+}
+
+const DEFAULT: &[Column] = &[
+    Column::Rank,
+    Column::Title,
+    Column::Score,
+    Column::Votes,
+    Column::Age,
+    Column::Minutes,
+    Column::Tags,
+];
+
+impl Column {
+    fn header(&self) -> &str {
+        match self {
+            Column::Rank => "#",
+            Column::Title => "title",
+            Column::Path => "path",
+            Column::Score => "score",
+            Column::Offset => "offset",
+            Column::Votes => "votes",
+            Column::Age => "age",
+            Column::Words => "words",
+            Column::Minutes => "min",
+            Column::Lang => "lang",
+            Column::Tags => "tags",
+            Column::Status => "status",
+            Column::Field(name) => name,
+        }
+    }
+
+    fn cell(&self, rank: usize, item: &File, bounds: (f64, f64)) -> String {
+        match self {
+            Column::Rank => rank.to_string(),
+            Column::Title => item.title.clone(),
+            Column::Path => path_str(&item.path).to_string(),
+            Column::Score => format!(
+                "{}±{}",
+                item.score().clamp(bounds.0, bounds.1) as i64,
+                item.rating.deviation as i64
+            ),
+            Column::Offset => format!("{:+}", item.inherited.offset as i64),
+            Column::Votes => item.votes.to_string(),
+            Column::Age => format_age(Utc::now() - item.created_at()),
+            Column::Words => item.words().to_string(),
+            Column::Minutes => item.reading_minutes().to_string(),
+            Column::Lang => item.lang.clone().unwrap_or_default(),
+            Column::Tags => item.tags().join(","),
+            Column::Status => item.status().to_string(),
+            Column::Field(name) => item.fields.get(name).cloned().unwrap_or_default(),
+        }
+    }
+}
+
+/// Prints the ranked entries as a table with aligned columns, using the
+/// default columns if none are given. Scores are clamped to `bounds`, see
+/// Config::clamp.
+pub fn print(
+    config: &Config,
+    rows: &[(usize, &File)],
+    columns: &[Column],
+    bounds: (f64, f64),
+) -> Result<()> {
+    let columns = if columns.is_empty() { DEFAULT } else { columns };
+    for column in columns {
+        if let Column::Field(name) = column {
+            if !config.fields.contains_key(name) {
+                bail!("unknown column {:?}", name);
+            }
+        }
+    }
+
+    let mut tw = TabWriter::new(std::io::stdout().lock()).padding(2);
+    let headers: Vec<_> = columns.iter().map(|c| c.header()).collect();
+    writeln!(tw, "{}", headers.join("\t"))?;
+    for &(rank, item) in rows {
+        // Tabs and newlines in titles would break the alignment.
+        let cells: Vec<_> = columns
+            .iter()
+            .map(|c| c.cell(rank, item, bounds).replace(['\t', '\n'], " "))
+            .collect();
+        writeln!(tw, "{}", cells.join("\t"))?;
+    }
+    tw.flush()?;
+    Ok(())
+}
@@ -0,0 +1,38 @@
+//! Shows the current operation in the terminal title, so a prio running in a
+//! background tmux pane or tab can be told apart.
+
+use std::io::{IsTerminal, Write};
+
+fn write(title: &str) {
+    let mut stderr = std::io::stderr();
+    if stderr.is_terminal() {
+        // Not being able to set the title is not worth failing over.
+        let _ = write!(stderr, "\x1b]0;{}\x07", title);
+        let _ = stderr.flush();
+    }
+}
+
+/// Clears the title again when dropped.
+pub struct Title(());
+
+impl Title {
+    pub fn new() -> Self {
+        Title(())
+    }
+
+    pub fn set(&self, title: &str) {
+        write(&format!("prio: {}", title));
+    }
+}
+
+impl Default for Title {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Title {
+    fn drop(&mut self) {
+        write("");
+    }
+}
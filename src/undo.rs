@@ -0,0 +1,375 @@
+use anyhow::{bail, Result};
+use sqlx::{query, Connection, SqliteConnection};
+use tokio::fs;
+
+use crate::audit::{self, Change};
+use crate::config::Config;
+use crate::fields;
+use crate::quarantine;
+use crate::status::{current_status, set_status, Status};
+use crate::util::actor;
+use crate::votes::{self, StoredVote};
+
+async fn revert_status(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    path: &str,
+    expected: Status,
+    status: Status,
+    reason: &str,
+) -> Result<()> {
+    let current = current_status(conn, path).await?;
+    if current != expected {
+        bail!(
+            "{} is {} now instead of {}, refusing to make it {}",
+            path,
+            current,
+            expected,
+            status
+        );
+    }
+    set_status(conn, path, status, &actor(), Some(reason)).await?;
+
+    // prio remove also quarantined the file, put it back or remove it again.
+    // Files deleted before there was a quarantine are restored from the
+    // database.
+    let full_path = config.locate(path);
+    if expected == Status::Deleted && !full_path.exists() {
+        if let Some(item) = quarantine::find(config, path).await? {
+            return quarantine::restore(config, &item).await;
+        }
+        let content = query!(
+            r#"
+                SELECT content AS "content!"
+                FROM file_contents
+                WHERE path = ?1 AND content IS NOT NULL
+                ORDER BY at DESC
+                LIMIT 1
+            "#,
+            path
+        )
+        .fetch_one(conn)
+        .await?
+        .content;
+
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(&full_path, content).await?;
+    } else if status == Status::Deleted {
+        quarantine::put(config, path).await?;
+    }
+    Ok(())
+}
+
+/// Changes a field from `expected` to `value`, unless it was changed since.
+async fn revert_field(
+    conn: &mut SqliteConnection,
+    path: &str,
+    name: &str,
+    expected: Option<&str>,
+    value: Option<&str>,
+) -> Result<()> {
+    let current = fields::stored(conn, path, name).await?;
+    if current.as_deref() != expected {
+        bail!(
+            "{} of {} was changed since, refusing to change it",
+            name,
+            path
+        );
+    }
+    fields::store(conn, path, name, value).await
+}
+
+async fn delete_vote(conn: &mut SqliteConnection, vote_id: i64) -> Result<()> {
+    if !votes::delete(conn, vote_id).await? {
+        bail!("vote {} no longer exists", vote_id);
+    }
+    Ok(())
+}
+
+async fn set_vote(
+    conn: &mut SqliteConnection,
+    vote_id: i64,
+    expected: i64,
+    vote: i64,
+) -> Result<()> {
+    match votes::get(conn, vote_id).await? {
+        None => bail!("vote {} no longer exists", vote_id),
+        Some(current) if current.vote != expected => {
+            bail!(
+                "vote {} was changed since, refusing to change it back",
+                vote_id
+            )
+        }
+        Some(_) => {}
+    }
+    votes::set_outcome(conn, vote_id, vote).await?;
+    Ok(())
+}
+
+/// Reverts `change`, the changes of a bulk change last to first.
+async fn revert(conn: &mut SqliteConnection, config: &Config, change: &Change) -> Result<()> {
+    match change {
+        Change::Vote { vote_id, .. } => delete_vote(conn, *vote_id).await?,
+        Change::VoteEdit { vote_id, from, to } => set_vote(conn, *vote_id, *to, *from).await?,
+        Change::VoteDelete {
+            vote_id,
+            winner,
+            loser,
+            vote,
+            at,
+            criterion,
+            confidence,
+        } => {
+            let vote = StoredVote {
+                id: *vote_id,
+                left_path: winner.clone(),
+                right_path: loser.clone(),
+                vote: *vote,
+                at: *at,
+                criterion: criterion.clone(),
+                confidence: confidence.map(|c| c.as_str().to_string()),
+            };
+            votes::restore(conn, &vote).await?;
+        }
+        Change::Status { path, from, to, .. } => {
+            revert_status(conn, config, path, *to, *from, "undo").await?;
+        }
+        Change::Field {
+            path,
+            name,
+            from,
+            to,
+        } => revert_field(conn, path, name, to.as_deref(), from.as_deref()).await?,
+        Change::Bulk { changes } => {
+            for change in changes.iter().rev() {
+                Box::pin(revert(conn, config, change)).await?;
+            }
+        }
+        Change::Add { .. }
+        | Change::Content { .. }
+        | Change::Rename { .. }
+        | Change::Purge { .. } => {
+            unreachable!("not undoable")
+        }
+    }
+    Ok(())
+}
+
+/// Makes `change` again after it was reverted.
+async fn reapply(conn: &mut SqliteConnection, config: &Config, change: &Change) -> Result<()> {
+    match change {
+        Change::Vote {
+            vote_id,
+            winner,
+            loser,
+            vote,
+            at,
+            criterion,
+            confidence,
+        } => {
+            let vote = StoredVote {
+                id: *vote_id,
+                left_path: winner.clone(),
+                right_path: loser.clone(),
+                vote: *vote,
+                at: *at,
+                criterion: criterion.clone(),
+                confidence: confidence.map(|c| c.as_str().to_string()),
+            };
+            votes::restore(conn, &vote).await?;
+        }
+        Change::VoteEdit { vote_id, from, to } => set_vote(conn, *vote_id, *from, *to).await?,
+        Change::VoteDelete { vote_id, .. } => delete_vote(conn, *vote_id).await?,
+        Change::Status { path, from, to, .. } => {
+            revert_status(conn, config, path, *from, *to, "redo").await?;
+        }
+        Change::Field {
+            path,
+            name,
+            from,
+            to,
+        } => revert_field(conn, path, name, from.as_deref(), to.as_deref()).await?,
+        Change::Bulk { changes } => {
+            for change in changes {
+                Box::pin(reapply(conn, config, change)).await?;
+            }
+        }
+        Change::Add { .. }
+        | Change::Content { .. }
+        | Change::Rename { .. }
+        | Change::Purge { .. } => {
+            unreachable!("not undoable")
+        }
+    }
+    Ok(())
+}
+
+/// Reverts the last change that is still in effect, or the last such vote,
+/// returning it.
+pub async fn undo(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    only_votes: bool,
+) -> Result<Option<Change>> {
+    let mut tx = conn.begin().await?;
+    let conn = &mut *tx;
+
+    let Some((id, change)) = audit::last_undoable(conn, only_votes).await? else {
+        return Ok(None);
+    };
+    revert(conn, config, &change).await?;
+
+    audit::set_undone(conn, id, true).await?;
+    tx.commit().await?;
+    Ok(Some(change))
+}
+
+/// Applies the last undone change again, returning it.
+pub async fn redo(conn: &mut SqliteConnection, config: &Config) -> Result<Option<Change>> {
+    let mut tx = conn.begin().await?;
+    let conn = &mut *tx;
+
+    let Some((id, change)) = audit::next_redoable(conn).await? else {
+        return Ok(None);
+    };
+    reapply(conn, config, &change).await?;
+
+    audit::set_undone(conn, id, false).await?;
+    tx.commit().await?;
+    Ok(Some(change))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use chrono::Utc;
+
+    use super::*;
+    use crate::{insert_vote, schema, Vote};
+
+    async fn vote(conn: &mut SqliteConnection, winner: &str, loser: &str) {
+        let vote = Vote {
+            left_path: PathBuf::from(winner),
+            right_path: PathBuf::from(loser),
+            vote: 1,
+            at: Utc::now(),
+            criterion: None,
+            confidence: None,
+        };
+        insert_vote(conn, &vote, "test").await.unwrap();
+    }
+
+    async fn vote_count(conn: &mut SqliteConnection) -> usize {
+        votes::list(conn, None).await.unwrap().len()
+    }
+
+    #[tokio::test]
+    async fn undo_redo_undo() {
+        let mut conn = schema::memory().await;
+        let config = Config::default();
+        vote(&mut conn, "a.md", "b.md").await;
+
+        let undone = undo(&mut conn, &config, false).await.unwrap();
+        assert!(matches!(undone, Some(Change::Vote { .. })));
+        assert_eq!(vote_count(&mut conn).await, 0);
+
+        let redone = redo(&mut conn, &config).await.unwrap();
+        assert!(matches!(redone, Some(Change::Vote { .. })));
+        assert_eq!(vote_count(&mut conn).await, 1);
+        assert!(redo(&mut conn, &config).await.unwrap().is_none());
+
+        assert!(undo(&mut conn, &config, false).await.unwrap().is_some());
+        assert_eq!(vote_count(&mut conn).await, 0);
+        assert!(undo(&mut conn, &config, false).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn new_change_ends_redo() {
+        let mut conn = schema::memory().await;
+        let config = Config::default();
+        vote(&mut conn, "a.md", "b.md").await;
+        undo(&mut conn, &config, false).await.unwrap();
+
+        vote(&mut conn, "b.md", "c.md").await;
+        let undone: i64 = sqlx::query_scalar("SELECT undone FROM audit_log ORDER BY id LIMIT 1")
+            .fetch_one(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(undone, 2);
+        assert!(redo(&mut conn, &config).await.unwrap().is_none());
+
+        // The new vote can still be undone, the old one stays undone.
+        assert!(undo(&mut conn, &config, false).await.unwrap().is_some());
+        assert!(undo(&mut conn, &config, false).await.unwrap().is_none());
+        assert_eq!(vote_count(&mut conn).await, 0);
+    }
+
+    #[tokio::test]
+    async fn nothing_before_a_purge_is_undone_or_redone() {
+        let mut conn = schema::memory().await;
+        let config = Config::default();
+        query!("INSERT INTO entries (path, id) VALUES ('a.md', 1), ('b.md', 2), ('c.md', 3)")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        vote(&mut conn, "a.md", "b.md").await;
+        vote(&mut conn, "b.md", "c.md").await;
+        undo(&mut conn, &config, false).await.unwrap();
+
+        let (revisions, votes) = schema::purge_entry(&mut conn, "a.md").await.unwrap();
+        let change = Change::Purge {
+            path: "a.md".to_string(),
+            revisions,
+            votes,
+        };
+        audit::record(&mut conn, &change, "test").await.unwrap();
+
+        assert!(redo(&mut conn, &config).await.unwrap().is_none());
+        assert!(undo(&mut conn, &config, false).await.unwrap().is_none());
+        assert!(undo(&mut conn, &config, true).await.unwrap().is_none());
+        assert_eq!(vote_count(&mut conn).await, 0);
+    }
+
+    #[tokio::test]
+    async fn bulk_changes_are_undone_together() {
+        let mut conn = schema::memory().await;
+        let config = Config::default();
+        query!("INSERT INTO entries (path, id) VALUES ('a.md', 1), ('b.md', 2)")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        let mut changes = vec![];
+        for path in ["a.md", "b.md"] {
+            set_status(&mut conn, path, Status::Done, "test", None)
+                .await
+                .unwrap();
+            changes.push(Change::Status {
+                path: path.to_string(),
+                from: Status::Active,
+                to: Status::Done,
+                reason: None,
+            });
+        }
+        audit::record(&mut conn, &Change::Bulk { changes }, "test")
+            .await
+            .unwrap();
+
+        let undone = undo(&mut conn, &config, false).await.unwrap();
+        assert!(matches!(undone, Some(Change::Bulk { .. })));
+        for path in ["a.md", "b.md"] {
+            assert_eq!(
+                current_status(&mut conn, path).await.unwrap(),
+                Status::Active
+            );
+        }
+        assert!(undo(&mut conn, &config, false).await.unwrap().is_none());
+
+        redo(&mut conn, &config).await.unwrap();
+        for path in ["a.md", "b.md"] {
+            assert_eq!(current_status(&mut conn, path).await.unwrap(), Status::Done);
+        }
+    }
+}
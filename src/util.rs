@@ -1,5 +1,175 @@
+use std::hash::Hasher;
 use std::path::Path;
 
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone, Utc};
+use metrohash::MetroHash64;
+use unicode_normalization::UnicodeNormalization;
+
 pub fn path_str(path: &Path) -> &str {
     path.to_str().unwrap()
 }
+
+pub fn content_hash(content: &[u8]) -> u64 {
+    let mut hasher = MetroHash64::new();
+    hasher.write(content);
+    hasher.finish()
+}
+
+/// The canonical form of an entry path: NFC normalized and without trailing
+/// slashes, so the same file always gets the same key whatever the file
+/// system or user typed.
+pub fn normalize_path(path: &str) -> String {
+    path.trim_end_matches('/').nfc().collect()
+}
+
+/// The names in `@name` mentions in `text`, in order and without
+/// duplicates. An `@` directly after a letter or digit, as in an email
+/// address, isn't a mention.
+pub fn mentions(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = vec![];
+    let mut prev = None;
+    for (i, c) in text.char_indices() {
+        if c == '@' && !prev.is_some_and(char::is_alphanumeric) {
+            let rest = &text[i + 1..];
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-' || c == '.'))
+                .unwrap_or(rest.len());
+            let name = rest[..end].trim_end_matches(['.', '-']);
+            if !name.is_empty() && !names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+                names.push(name.to_string());
+            }
+        }
+        prev = Some(c);
+    }
+    names
+}
+
+/// A file name stem for `text`: lowercase words joined by dashes, at most
+/// about 50 characters long.
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for word in text.split(|c: char| !c.is_alphanumeric()) {
+        if word.is_empty() {
+            continue;
+        }
+        if slug.len() + word.len() > 50 && !slug.is_empty() {
+            break;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.extend(word.chars().flat_map(char::to_lowercase));
+    }
+    if slug.is_empty() {
+        slug.push_str("entry");
+    }
+    slug
+}
+
+/// Who to record as the author of a change made from the command line.
+pub fn actor() -> String {
+    std::env::var("USER").unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Parses durations like `36h`, `7d`, `2w`, `3m` (30 days) or `1y`.
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let split = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| anyhow!("duration {:?} is missing a unit", s))?;
+    let (n, unit) = s.split_at(split);
+    let n: i64 = n.parse().map_err(|_| anyhow!("invalid duration {:?}", s))?;
+
+    Ok(match unit {
+        "h" => Duration::hours(n),
+        "d" => Duration::days(n),
+        "w" => Duration::weeks(n),
+        "m" => Duration::days(n * 30),
+        "y" => Duration::days(n * 365),
+        _ => return Err(anyhow!("unknown duration unit {:?}", unit)),
+    })
+}
+
+/// Formats a timestamp in local time, for human consumption.
+pub fn format_time(at: DateTime<Utc>) -> String {
+    at.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+/// Formats a duration in its largest whole unit, like `3d` or `5w`, the
+/// inverse of parse_duration.
+pub fn format_age(age: Duration) -> String {
+    let days = age.num_days();
+    match days {
+        0 => format!("{}h", age.num_hours()),
+        1..=13 => format!("{}d", days),
+        14..=89 => format!("{}w", days / 7),
+        90..=729 => format!("{}m", days / 30),
+        _ => format!("{}y", days / 365),
+    }
+}
+
+/// Parses either a local date (`2024-01-31`) or a duration relative to now (`7d`).
+pub fn parse_since(s: &str) -> Result<DateTime<Utc>> {
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        let time = date.and_hms_opt(0, 0, 0).unwrap();
+        let time = Local
+            .from_local_datetime(&time)
+            .earliest()
+            .ok_or_else(|| anyhow!("invalid local time {}", time))?;
+        return Ok(time.with_timezone(&Utc));
+    }
+    Ok(Utc::now() - parse_duration(s)?)
+}
+
+/// Replaces every `{name}` in the template by `lookup(name)`, or by nothing if
+/// that returns None.
+pub fn render_template(template: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut res = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        res.push_str(&rest[..start]);
+        if let Some(value) = lookup(&rest[start + 1..start + len]) {
+            res.push_str(&value);
+        }
+        rest = &rest[start + len + 1..];
+    }
+    res.push_str(rest);
+    res
+}
+
+/// Splits a command line into words like a shell would, supporting single and
+/// double quotes and backslash escapes.
+pub fn split_words(line: &str) -> Result<Vec<String>> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', Some('\'')) => word.get_or_insert_with(String::new).push(c),
+            ('\\', _) => {
+                let c = chars.next().ok_or_else(|| anyhow!("trailing backslash"))?;
+                word.get_or_insert_with(String::new).push(c);
+            }
+            ('\'' | '"', None) => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (c, Some(q)) if c == q => quote = None,
+            (c, None) if c.is_whitespace() => words.extend(word.take()),
+            (c, _) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err(anyhow!("unterminated quote"));
+    }
+    words.extend(word);
+    Ok(words)
+}
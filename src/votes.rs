@@ -0,0 +1,205 @@
+//! Votes as stored, by the id prio votes lists them with. They are read with
+//! the current paths of their entries, see resolved_votes in schema.sql. For
+//! the votes the ratings are computed from see counted_votes.
+
+use anyhow::Result;
+use sqlx::{query, query_as, SqliteConnection};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredVote {
+    pub id: i64,
+    pub left_path: String,
+    pub right_path: String,
+    pub vote: i64,
+    pub at: i64,
+    pub criterion: Option<String>,
+    pub confidence: Option<String>,
+}
+
+/// All votes, or those involving the entry at `path`, oldest first.
+pub async fn list(conn: &mut SqliteConnection, path: Option<&str>) -> Result<Vec<StoredVote>> {
+    Ok(query_as!(
+        StoredVote,
+        r#"
+            SELECT
+                id AS "id!: i64",
+                left_path AS "left_path!: String",
+                right_path AS "right_path!: String",
+                vote AS "vote!: i64",
+                at AS "at!: i64",
+                criterion,
+                confidence
+            FROM resolved_votes
+            WHERE ?1 IS NULL OR left_path = ?1 OR right_path = ?1
+            ORDER BY at, id
+        "#,
+        path
+    )
+    .fetch_all(conn)
+    .await?)
+}
+
+pub async fn get(conn: &mut SqliteConnection, id: i64) -> Result<Option<StoredVote>> {
+    Ok(query_as!(
+        StoredVote,
+        r#"
+            SELECT
+                id AS "id!: i64",
+                left_path AS "left_path!: String",
+                right_path AS "right_path!: String",
+                vote AS "vote!: i64",
+                at AS "at!: i64",
+                criterion,
+                confidence
+            FROM resolved_votes
+            WHERE id = ?1
+        "#,
+        id
+    )
+    .fetch_optional(conn)
+    .await?)
+}
+
+/// Puts a deleted vote back under the id it had.
+pub async fn restore(conn: &mut SqliteConnection, vote: &StoredVote) -> Result<()> {
+    query!(
+        r#"
+            INSERT INTO entry_votes
+                (id, left_path, right_path, vote, at, criterion, confidence)
+            VALUES
+                (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        "#,
+        vote.id,
+        vote.left_path,
+        vote.right_path,
+        vote.vote,
+        vote.at,
+        vote.criterion,
+        vote.confidence
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Changes the outcome of vote `id`, returning false if there is none.
+pub async fn set_outcome(conn: &mut SqliteConnection, id: i64, vote: i64) -> Result<bool> {
+    let res = query!("UPDATE entry_votes SET vote = ?2 WHERE id = ?1", id, vote)
+        .execute(conn)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Deletes vote `id`, returning false if there is none.
+pub async fn delete(conn: &mut SqliteConnection, id: i64) -> Result<bool> {
+    let res = query!("DELETE FROM entry_votes WHERE id = ?1", id)
+        .execute(conn)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// The number of votes cast at or after `at`, counted or not.
+pub async fn count_since(conn: &mut SqliteConnection, at: i64) -> Result<i64> {
+    Ok(query!(
+        r#"SELECT COUNT(*) AS "count!: i64" FROM entry_votes WHERE at >= ?1"#,
+        at
+    )
+    .fetch_one(conn)
+    .await?
+    .count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema;
+
+    fn vote(id: i64, left: &str, right: &str, at: i64) -> StoredVote {
+        StoredVote {
+            id,
+            left_path: left.to_string(),
+            right_path: right.to_string(),
+            vote: 1,
+            at,
+            criterion: None,
+            confidence: None,
+        }
+    }
+
+    async fn with_votes(votes: &[StoredVote]) -> SqliteConnection {
+        let mut conn = schema::memory().await;
+        for v in votes {
+            restore(&mut conn, v).await.unwrap();
+        }
+        conn
+    }
+
+    #[tokio::test]
+    async fn list_is_oldest_first_and_filters_by_entry() {
+        let mut conn = with_votes(&[
+            vote(1, "a.md", "b.md", 20),
+            vote(2, "b.md", "c.md", 10),
+            vote(3, "c.md", "a.md", 30),
+        ])
+        .await;
+
+        let ids = |votes: Vec<StoredVote>| votes.into_iter().map(|v| v.id).collect::<Vec<_>>();
+        assert_eq!(ids(list(&mut conn, None).await.unwrap()), [2, 1, 3]);
+        assert_eq!(ids(list(&mut conn, Some("a.md")).await.unwrap()), [1, 3]);
+        assert!(list(&mut conn, Some("d.md")).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn get_and_restore_keep_the_id() {
+        let v = StoredVote {
+            criterion: Some("urgency".to_string()),
+            confidence: Some("leaning".to_string()),
+            ..vote(7, "a.md", "b.md", 10)
+        };
+        let mut conn = with_votes(std::slice::from_ref(&v)).await;
+
+        assert_eq!(get(&mut conn, 7).await.unwrap(), Some(v));
+        assert_eq!(get(&mut conn, 8).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn set_outcome_and_delete_report_missing_votes() {
+        let mut conn = with_votes(&[vote(1, "a.md", "b.md", 10)]).await;
+
+        assert!(set_outcome(&mut conn, 1, -1).await.unwrap());
+        assert_eq!(get(&mut conn, 1).await.unwrap().unwrap().vote, -1);
+        assert!(!set_outcome(&mut conn, 2, -1).await.unwrap());
+
+        assert!(delete(&mut conn, 1).await.unwrap());
+        assert!(!delete(&mut conn, 1).await.unwrap());
+        assert_eq!(get(&mut conn, 1).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn deleted_ids_are_not_reused() {
+        let mut conn =
+            with_votes(&[vote(1, "a.md", "b.md", 10), vote(2, "a.md", "b.md", 20)]).await;
+        delete(&mut conn, 2).await.unwrap();
+
+        let id = query!("INSERT INTO entry_votes (left_path, right_path, vote, at) VALUES ('a.md', 'b.md', 1, 30)")
+            .execute(&mut conn)
+            .await
+            .unwrap()
+            .last_insert_rowid();
+        assert_eq!(id, 3);
+    }
+
+    #[tokio::test]
+    async fn count_since_includes_the_start() {
+        let mut conn = with_votes(&[
+            vote(1, "a.md", "b.md", 10),
+            vote(2, "a.md", "b.md", 20),
+            vote(3, "a.md", "b.md", 30),
+        ])
+        .await;
+
+        assert_eq!(count_since(&mut conn, 20).await.unwrap(), 2);
+        assert_eq!(count_since(&mut conn, 31).await.unwrap(), 0);
+        assert_eq!(count_since(&mut conn, 0).await.unwrap(), 3);
+    }
+}
@@ -0,0 +1,137 @@
+use std::process::Command;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+/// A fetched web page.
+#[derive(Debug, Clone)]
+pub struct Page {
+    /// HTTP status of the last response, after following redirects.
+    pub status: u16,
+    pub body: String,
+}
+
+impl Page {
+    pub fn ok(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    pub fn dead(&self) -> bool {
+        dead(self.status)
+    }
+}
+
+/// Whether a response with `status` means the page is gone.
+pub fn dead(status: u16) -> bool {
+    matches!(status, 404 | 410)
+}
+
+/// Fetches `url` with curl, following redirects. Only fails if there was no
+/// response at all, check Page::status for the rest.
+pub fn fetch(url: &str) -> Result<Page> {
+    let output = Command::new("curl")
+        .args(["--silent", "--show-error", "--location", "--max-time", "20"])
+        .args(["--write-out", "\n%{http_code}", "--"])
+        .arg(url)
+        .output()
+        .context("starting curl")?;
+    if !output.status.success() {
+        bail!(
+            "fetching {}: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (body, status) = stdout
+        .rsplit_once('\n')
+        .ok_or_else(|| anyhow!("fetching {}: no status from curl", url))?;
+    Ok(Page {
+        status: status.trim().parse()?,
+        body: body.to_string(),
+    })
+}
+
+/// Index of the first case insensitive match of `needle`, which has to be
+/// ASCII, in `haystack` from `from` on.
+fn find(haystack: &str, needle: &str, from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .as_bytes()
+        .windows(needle.len())
+        .position(|w| w.eq_ignore_ascii_case(needle.as_bytes()))
+        .map(|i| from + i)
+}
+
+/// The contents of the first `<tag ...>...</tag>` element.
+fn element<'a>(html: &'a str, tag: &str) -> Option<&'a str> {
+    let open = find(html, &format!("<{}", tag), 0)?;
+    let start = open + html[open..].find('>')? + 1;
+    let end = find(html, &format!("</{}", tag), start)?;
+    Some(&html[start..end])
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The title of the HTML page, if it has one.
+pub fn title(html: &str) -> Option<String> {
+    let title = collapse_whitespace(&decode_entities(element(html, "title")?));
+    (!title.is_empty()).then_some(title)
+}
+
+/// The readable text of the HTML page: the paragraphs in its article or main
+/// element if it has one, or else in its body. Nowhere near what a browser's
+/// reader mode does, but enough to skim an entry.
+pub fn text(html: &str) -> String {
+    let content = element(html, "article")
+        .or_else(|| element(html, "main"))
+        .or_else(|| element(html, "body"))
+        .unwrap_or(html);
+
+    let mut paragraphs = vec![];
+    let mut from = 0;
+    while let Some(open) = find(content, "<p", from) {
+        // Not <pre>, <param> and the like.
+        let next = content[open + 2..].chars().next();
+        if !matches!(next, Some('>' | ' ' | '\t' | '\n')) {
+            from = open + 2;
+            continue;
+        }
+        let Some(start) = content[open..].find('>').map(|i| open + i + 1) else {
+            break;
+        };
+        let end = find(content, "</p", start).unwrap_or(content.len());
+        let paragraph = collapse_whitespace(&decode_entities(&strip_tags(&content[start..end])));
+        if !paragraph.is_empty() {
+            paragraphs.push(paragraph);
+        }
+        from = end;
+    }
+    paragraphs.join("\n\n")
+}
+
+fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text
+}